@@ -1,13 +1,154 @@
-use std::env;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::process::ExitCode;
 
+use clap::{Parser, Subcommand};
+
+use fchess::pgn::PgnReader;
 use fchess::Board;
 use fchess::Book;
+use fchess::BookBuilder;
+use fchess::BookPolicy;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build a PolyGlot book from the games of PGN files
+    Build {
+        /// PGN files of the games
+        #[arg(required = true)]
+        pgns: Vec<String>,
+
+        /// book file to write
+        #[arg(short, long, default_value = "book.bin")]
+        output: String,
+
+        /// plies of each game to keep
+        #[arg(short, long, default_value_t = 20)]
+        plies: usize,
+
+        /// leave out moves played in fewer games
+        #[arg(long, default_value_t = 1)]
+        min_count: u32,
+
+        /// only count the moves of players rated at least this
+        #[arg(long)]
+        min_elo: Option<u32>,
+    },
+    /// Print the candidate moves of a position and the one a policy picks
+    Probe {
+        /// PolyGlot book file
+        book: String,
+
+        #[arg(default_value = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")]
+        fen: String,
+
+        /// best, weighted or uniform
+        #[arg(long, default_value = "best")]
+        policy: String,
+
+        /// lowest weight of the moves the uniform policy picks from
+        #[arg(long, default_value_t = 1)]
+        min_weight: u16,
+
+        /// seed of the random policies
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+/// Build and probe PolyGlot opening books
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn main() -> io::Result<ExitCode> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Build {
+            pgns,
+            output,
+            plies,
+            min_count,
+            min_elo,
+        } => {
+            let mut builder = BookBuilder::new(plies);
+            builder.set_min_count(min_count);
+            builder.set_min_elo(min_elo);
+
+            let (mut games, mut counted) = (0, 0);
+            for filename in pgns {
+                for game in PgnReader::open(&filename)? {
+                    match game {
+                        Ok(game) => {
+                            games += 1;
+                            counted += builder.add_game(&game) as usize;
+                        }
+                        Err(err) => println!("Skipping game of {filename}: {err}"),
+                    }
+                }
+            }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+            let book = builder.build();
+            book.write_to(BufWriter::new(File::create(&output)?))?;
+            println!(
+                "{counted} of {games} games counted, {} entries written to {output}",
+                book.len()
+            );
+        }
+        Command::Probe {
+            book,
+            fen,
+            policy,
+            min_weight,
+            seed,
+        } => {
+            let mut book = Book::open(&book)?;
+            book.set_policy(match policy.as_str() {
+                "best" => BookPolicy::Best,
+                "weighted" => BookPolicy::WeightedRandom,
+                "uniform" => BookPolicy::Uniform { min_weight },
+                _ => {
+                    eprintln!("Invalid policy {policy}, expected best, weighted or uniform");
+                    return Ok(ExitCode::FAILURE);
+                }
+            });
+            if let Some(seed) = seed {
+                book.set_seed(seed);
+            }
+            let Some(board) = Board::try_from_fen(&fen) else {
+                eprintln!("Invalid position {fen}");
+                return Ok(ExitCode::FAILURE);
+            };
 
-    let book = Book::from_filename(args.get(1).unwrap());
+            let candidates = book.entries(&board)?;
+            if candidates.is_empty() {
+                println!("No book move");
+                return Ok(ExitCode::SUCCESS);
+            }
+            println!(
+                "{:<8} {:>6} {:>7} {:>10}",
+                "Move", "Weight", "Share", "Learn"
+            );
+            for candidate in &candidates {
+                println!(
+                    "{:<8} {:>6} {:>6.1}% {:>10}",
+                    candidate.mov.to_san(&board),
+                    candidate.weight,
+                    candidate.percentage,
+                    candidate.learn
+                );
+            }
+            match book.get_move(&board) {
+                Some(mov) => println!("Chosen: {}", mov.to_san(&board)),
+                None => println!("Chosen: none"),
+            }
+        }
+    }
 
-    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
-    println!("{:?}", book.get_best_move(&board).unwrap());
+    Ok(ExitCode::SUCCESS)
 }
@@ -1,10 +1,14 @@
+use std::fs;
+
 use clap::Parser;
 
+use fchess::pgn::PgnGame;
 use fchess::Board;
 use fchess::Book;
 use fchess::Move;
 use fchess::Side;
 use fchess::Solver;
+use fchess::Tablebases;
 
 /// fchess cli interface
 #[derive(Parser, Debug)]
@@ -13,6 +17,14 @@ struct Args {
     /// path to polyglot openning book
     #[arg(short, long)]
     book: Option<String>,
+
+    /// directory with endgame tablebases
+    #[arg(short, long)]
+    tablebases: Option<String>,
+
+    /// write the game to this PGN file after every move
+    #[arg(short, long)]
+    pgn: Option<String>,
 }
 
 fn main() -> rustyline::Result<()> {
@@ -24,10 +36,26 @@ fn main() -> rustyline::Result<()> {
 
     let args = Args::parse();
 
-    let book = args.book.map(|book| Book::from_filename(&book));
+    let book = args.book.and_then(|filename| match Book::open(&filename) {
+        Ok(book) => Some(book),
+        Err(err) => {
+            println!("Failed to load book {filename}: {err}");
+            None
+        }
+    });
 
-    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0");
-    let solver = Solver::new();
+    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let mut game = PgnGame::new(board.clone());
+    game.set_tag("Event", "fchess cli");
+    game.set_tag("White", "Human");
+    game.set_tag("Black", "fchess");
+    let mut solver = Solver::new();
+    if let Some(directory) = &args.tablebases {
+        match Tablebases::from_directory(directory) {
+            Ok(tablebases) => solver.set_tablebases(Some(tablebases)),
+            Err(err) => println!("Failed to load tablebases from {directory}: {err}"),
+        }
+    }
     println!("{board}");
 
     loop {
@@ -60,10 +88,16 @@ fn main() -> rustyline::Result<()> {
         })
         .unwrap();
 
-        board = match board.apply(mov) {
+        board = match board.apply(mov.clone()) {
             Some(board) => board,
             None => continue,
         };
+        game.push(mov);
+        if let Some(filename) = &args.pgn {
+            if let Err(err) = fs::write(filename, game.to_string()) {
+                println!("Failed to write {filename}: {err}");
+            }
+        }
         println!("{board}");
     }
 }
@@ -0,0 +1,149 @@
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+
+use fchess::epd::load_epds;
+use fchess::{Score, SearchInfo, SearchLimits, Solver, Tablebases};
+
+/// Run the solver on each position of an EPD test suite, scoring its bm, am and dm operations
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// EPD file of the suite
+    suite: String,
+
+    /// search depth, the acd of each position when no limit is given
+    #[arg(short, long)]
+    depth: Option<u8>,
+
+    /// milliseconds per position
+    #[arg(short, long)]
+    movetime: Option<u64>,
+
+    /// nodes per position
+    #[arg(short, long)]
+    nodes: Option<u64>,
+
+    /// transposition table size in megabytes
+    #[arg(long)]
+    hash: Option<usize>,
+
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// directory with endgame tablebases
+    #[arg(short, long)]
+    tablebases: Option<String>,
+
+    /// fail unless at least this many positions are solved, all of them when not given
+    #[arg(long)]
+    min_solved: Option<usize>,
+}
+
+fn format_score(score: Option<Score>) -> String {
+    match score {
+        Some(Score::Centipawns(cp)) => format!("{:+.2}", cp as f64 / 100.0),
+        Some(Score::Mate(moves)) => format!("#{moves}"),
+        None => "-".to_string(),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let epds = match load_epds(&args.suite) {
+        Ok(epds) => epds,
+        Err(err) => {
+            println!("Failed to load {}: {err}", args.suite);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut solver = Solver::new();
+    if let Some(hash) = args.hash {
+        solver.set_hash_size(hash);
+    }
+    if let Some(threads) = args.threads {
+        solver.set_threads(threads);
+    }
+    if let Some(directory) = &args.tablebases {
+        match Tablebases::from_directory(directory) {
+            Ok(tablebases) => solver.set_tablebases(Some(tablebases)),
+            Err(err) => println!("Failed to load tablebases from {directory}: {err}"),
+        }
+    }
+
+    let start = Instant::now();
+    let (mut solved, mut scored) = (0, 0);
+    for (index, epd) in epds.iter().enumerate() {
+        let mut limits = SearchLimits {
+            depth: args.depth,
+            nodes: args.nodes,
+            movetime: args.movetime.map(Duration::from_millis),
+            ..Default::default()
+        };
+        if limits.depth.is_none() && limits.nodes.is_none() && limits.movetime.is_none() {
+            limits.depth = epd
+                .get_analysis_depth()
+                .map(|depth| depth.min(u8::MAX as u32) as u8);
+        }
+
+        solver.new_game();
+        let mut score = None;
+        let best = solver.search_with_progress(&epd.board, &limits, |info| {
+            if let SearchInfo::Iteration {
+                multipv: 1,
+                score: iteration_score,
+                ..
+            } = info
+            {
+                score = Some(*iteration_score);
+            }
+        });
+
+        let id = epd
+            .get_id()
+            .map_or_else(|| (index + 1).to_string(), str::to_string);
+        let san = best
+            .as_ref()
+            .map_or("none".to_string(), |mov| mov.to_san(&epd.board));
+        let verdict = match best.as_ref().and_then(|mov| epd.is_solved_by(mov, score)) {
+            Some(true) => {
+                solved += 1;
+                scored += 1;
+                "ok"
+            }
+            Some(false) => {
+                scored += 1;
+                "FAIL"
+            }
+            None if best.is_none() => {
+                scored += 1;
+                "FAIL"
+            }
+            None => "-",
+        };
+        let expected: Vec<String> = ["bm", "am", "dm"]
+            .iter()
+            .filter_map(|&opcode| {
+                epd.get_operation(opcode)
+                    .map(|operands| format!("{opcode} {}", operands.join(" ")))
+            })
+            .collect();
+        println!(
+            "{id}: {san} {} {verdict} ({})",
+            format_score(score),
+            expected.join("; ")
+        );
+    }
+
+    println!(
+        "Solved {solved} of {scored} in {:.1}s",
+        start.elapsed().as_secs_f64()
+    );
+    if solved < args.min_solved.unwrap_or(scored) {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -1,20 +1,111 @@
-use std::env;
+use std::fs;
 use std::io;
+use std::process::ExitCode;
 
-use fchess::Board;
-
-fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    let input = if args.len() == 2 {
-        args[1].to_string()
-    } else {
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {}
-            Err(err) => println!("{err:?}"),
-        };
-        input
+use clap::Parser;
+
+use fchess::svg::Diagram;
+use fchess::{Board, Move, Square};
+
+/// Draw a position given as FEN, read from standard input when not given
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    fen: Option<String>,
+
+    /// text or svg
+    #[arg(short, long, default_value = "text")]
+    format: String,
+
+    /// file to write the diagram to instead of standard output
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// draw the board from black's side
+    #[arg(long)]
+    flip: bool,
+
+    /// leave out the coordinates
+    #[arg(long)]
+    no_coordinates: bool,
+
+    /// size of a square in pixels
+    #[arg(long, default_value_t = 45)]
+    square_size: u32,
+
+    /// move to highlight, like e2e4
+    #[arg(long)]
+    last_move: Option<String>,
+
+    /// arrow between two squares, like g1f3, can be repeated
+    #[arg(long)]
+    arrow: Vec<String>,
+
+    /// square to circle, like d5, can be repeated
+    #[arg(long)]
+    circle: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let input = match args.fen {
+        Some(fen) => fen,
+        None => {
+            let mut input = String::new();
+            if let Err(err) = io::stdin().read_line(&mut input) {
+                eprintln!("{err:?}");
+                return ExitCode::FAILURE;
+            }
+            input
+        }
+    };
+    let board = Board::from_fen(input.trim());
+
+    let diagram = match args.format.as_str() {
+        "text" => format!("{board}"),
+        "svg" => {
+            let mut diagram = Diagram::new();
+            diagram.set_flipped(args.flip);
+            diagram.set_coordinates(!args.no_coordinates);
+            diagram.set_square_size(args.square_size);
+            if let Some(text) = &args.last_move {
+                let Some(mov) = Move::from_full_algebraic(text) else {
+                    eprintln!("Invalid move {text}");
+                    return ExitCode::FAILURE;
+                };
+                diagram.set_last_move(Some(mov));
+            }
+            for text in &args.arrow {
+                let Some(mov) = Move::from_full_algebraic(text) else {
+                    eprintln!("Invalid arrow {text}");
+                    return ExitCode::FAILURE;
+                };
+                diagram.add_arrow(mov.get_src(), mov.get_dst());
+            }
+            for text in &args.circle {
+                let Some(square) = Square::from_algebraic(text) else {
+                    eprintln!("Invalid square {text}");
+                    return ExitCode::FAILURE;
+                };
+                diagram.add_circle(square);
+            }
+            diagram.render(&board)
+        }
+        format => {
+            eprintln!("Invalid format {format}, expected text or svg");
+            return ExitCode::FAILURE;
+        }
     };
 
-    println!("{}", Board::from_fen(&input));
+    match args.output {
+        Some(output) => {
+            if let Err(err) = fs::write(&output, diagram) {
+                eprintln!("Could not write {output}: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{diagram}"),
+    }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::time::Duration;
+
+use clap::Parser;
+
+use fchess::engine::Engine;
+use fchess::tournament::{
+    load_openings, play_game, Adjudication, MatchScore, Opening, Player, SolverPlayer, Sprt,
+    SprtVerdict, TimeControl,
+};
+use fchess::{NnueEvaluator, Side, Solver, Tablebases};
+
+/// Play a match between two engines, reporting the Elo difference and an SPRT verdict
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// command line of the first engine, or `fchess` for the solver in this process
+    #[arg(long)]
+    engine1: String,
+
+    /// command line of the second engine, or `fchess` for the solver in this process
+    #[arg(long)]
+    engine2: String,
+
+    /// option of the first engine as NAME=VALUE, may be repeated
+    #[arg(long = "option1")]
+    options1: Vec<String>,
+
+    /// option of the second engine as NAME=VALUE, may be repeated
+    #[arg(long = "option2")]
+    options2: Vec<String>,
+
+    /// number of games, played in pairs with colours reversed
+    #[arg(short, long, default_value_t = 2)]
+    games: usize,
+
+    /// EPD or PGN file of opening positions, the start position when not given
+    #[arg(long)]
+    openings: Option<String>,
+
+    /// keep at most this many plies of each PGN opening
+    #[arg(long)]
+    opening_plies: Option<usize>,
+
+    /// clock of each side as base+increment in seconds
+    #[arg(long, default_value = "10+0.1")]
+    tc: TimeControl,
+
+    /// search to a fixed depth instead of playing on the clock
+    #[arg(long)]
+    depth: Option<u8>,
+
+    /// search a fixed number of nodes instead of playing on the clock
+    #[arg(long)]
+    nodes: Option<u64>,
+
+    /// milliseconds per move instead of playing on the clock
+    #[arg(long)]
+    movetime: Option<u64>,
+
+    /// adjudicate a draw once both sides scored within draw-score for this many moves
+    #[arg(long, default_value_t = 0)]
+    draw_moves: usize,
+
+    #[arg(long, default_value_t = 10)]
+    draw_score: i32,
+
+    /// adjudicate a win once both sides agreed on resign-score for this many moves
+    #[arg(long, default_value_t = 0)]
+    resign_moves: usize,
+
+    #[arg(long, default_value_t = 800)]
+    resign_score: i32,
+
+    /// adjudicate a draw after this many moves
+    #[arg(long, default_value_t = 0)]
+    max_moves: usize,
+
+    /// append the games to this PGN file
+    #[arg(long)]
+    pgn: Option<String>,
+
+    /// Elo difference of the null hypothesis
+    #[arg(long, default_value_t = 0.0)]
+    elo0: f64,
+
+    /// Elo difference of the alternative hypothesis
+    #[arg(long, default_value_t = 5.0)]
+    elo1: f64,
+
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    #[arg(long, default_value_t = 0.05)]
+    beta: f64,
+
+    /// stop the match as soon as the SPRT accepts a hypothesis
+    #[arg(long)]
+    sprt: bool,
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// NAME=VALUE pairs of the command line
+fn parse_options(options: &[String]) -> io::Result<Vec<(&str, &str)>> {
+    options
+        .iter()
+        .map(|option| {
+            option.split_once('=').ok_or_else(|| {
+                invalid_input(format!("invalid option {option}, expected NAME=VALUE"))
+            })
+        })
+        .collect()
+}
+
+fn solver(options: &[(&str, &str)]) -> io::Result<Solver> {
+    let mut solver = Solver::new();
+    for &(name, value) in options {
+        let number = || {
+            value
+                .parse()
+                .map_err(|_| invalid_input(format!("invalid value {value} for option {name}")))
+        };
+        match name {
+            "Hash" => solver.set_hash_size(number()?),
+            "Threads" => solver.set_threads(number()?),
+            "Move Overhead" => solver.set_move_overhead(Duration::from_millis(number()? as u64)),
+            "EvalFile" => solver.set_evaluator(Box::new(NnueEvaluator::from_filename(value)?)),
+            "TablebasePath" => solver.set_tablebases(Some(Tablebases::from_directory(value)?)),
+            _ => return Err(invalid_input(format!("no option {name}"))),
+        }
+    }
+    Ok(solver)
+}
+
+fn player(command: &str, options: &[String], name: &str) -> io::Result<Box<dyn Player>> {
+    let options = parse_options(options)?;
+    if command == "fchess" {
+        return Ok(Box::new(SolverPlayer::new(name, solver(&options)?)));
+    }
+
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| invalid_input("empty engine command".to_string()))?;
+    let mut engine = Engine::spawn(*program, args)?;
+    for (name, value) in options {
+        engine.set_option(name, Some(value))?;
+    }
+    Ok(Box::new(engine))
+}
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+
+    let mut first = player(&args.engine1, &args.options1, "fchess 1")?;
+    let mut second = player(&args.engine2, &args.options2, "fchess 2")?;
+    let mut openings = match &args.openings {
+        Some(filename) => load_openings(filename)?,
+        None => vec![Opening::default()],
+    };
+    if openings.is_empty() {
+        return Err(invalid_input("no openings".to_string()));
+    }
+    if let Some(plies) = args.opening_plies {
+        openings
+            .iter_mut()
+            .for_each(|opening| opening.moves.truncate(plies));
+    }
+
+    let time_control = if let Some(depth) = args.depth {
+        TimeControl::Depth(depth)
+    } else if let Some(nodes) = args.nodes {
+        TimeControl::Nodes(nodes)
+    } else if let Some(movetime) = args.movetime {
+        TimeControl::MoveTime(Duration::from_millis(movetime))
+    } else {
+        args.tc
+    };
+    let adjudication = Adjudication {
+        draw_moves: args.draw_moves,
+        draw_score: args.draw_score,
+        resign_moves: args.resign_moves,
+        resign_score: args.resign_score,
+        max_moves: args.max_moves,
+    };
+    let sprt = Sprt {
+        elo0: args.elo0,
+        elo1: args.elo1,
+        alpha: args.alpha,
+        beta: args.beta,
+    };
+    let mut pgn = match &args.pgn {
+        Some(filename) => Some(File::options().create(true).append(true).open(filename)?),
+        None => None,
+    };
+
+    let (first_name, second_name) = (first.get_name(), second.get_name());
+    let mut score = MatchScore::default();
+    for round in 0..args.games {
+        // each opening is played twice, the first engine taking white then black
+        let opening = &openings[(round / 2) % openings.len()];
+        let side = if round % 2 == 0 {
+            Side::White
+        } else {
+            Side::Black
+        };
+        let game = match side {
+            Side::White => play_game(
+                first.as_mut(),
+                second.as_mut(),
+                opening,
+                &time_control,
+                &adjudication,
+            )?,
+            Side::Black => play_game(
+                second.as_mut(),
+                first.as_mut(),
+                opening,
+                &time_control,
+                &adjudication,
+            )?,
+        };
+
+        score.add(game.result, side);
+        println!(
+            "Game {}: {} vs {} {} {{{}}}",
+            round + 1,
+            game.white,
+            game.black,
+            game.result,
+            game.reason
+        );
+        if let Some(pgn) = &mut pgn {
+            writeln!(pgn, "{}", game.to_pgn(round + 1))?;
+            pgn.flush()?;
+        }
+
+        let verdict = sprt.verdict(&score);
+        if args.sprt && verdict.is_some() {
+            break;
+        }
+    }
+
+    println!(
+        "Score of {first_name} vs {second_name}: {} - {} - {} [{:.3}] {}",
+        score.wins,
+        score.losses,
+        score.draws,
+        score.score(),
+        score.games()
+    );
+    if let Some((elo, error)) = score.elo() {
+        println!("Elo difference: {elo:.1} +/- {error:.1}");
+    }
+    let (lower, upper) = sprt.bounds();
+    let verdict = match sprt.verdict(&score) {
+        Some(SprtVerdict::AcceptH0) => "H0 accepted",
+        Some(SprtVerdict::AcceptH1) => "H1 accepted",
+        None => "inconclusive",
+    };
+    println!(
+        "SPRT: llr {:.2} ({lower:.2}, {upper:.2}) [{:.1}, {:.1}], {verdict}",
+        sprt.llr(&score),
+        sprt.elo0,
+        sprt.elo1
+    );
+
+    Ok(())
+}
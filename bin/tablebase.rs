@@ -0,0 +1,83 @@
+use std::process::ExitCode;
+use std::time::Instant;
+
+use clap::{Parser, Subcommand};
+
+use fchess::Board;
+use fchess::Material;
+use fchess::Tablebases;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate tables, and those of the material sets they convert to
+    Generate {
+        /// material sets such as KQvK or KRvKP
+        #[arg(default_values_t = ["KQvK".to_string(), "KRvK".to_string(), "KPvK".to_string(), "KBNvK".to_string()])]
+        materials: Vec<String>,
+    },
+    /// Print the tablebase value and best move of a position
+    Probe { fen: String },
+}
+
+/// Generate and probe endgame tablebases
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// directory holding the .fctb tables
+    #[arg(short, long, default_value = "tablebases")]
+    directory: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let mut tablebases = Tablebases::from_directory(&args.directory).unwrap_or_default();
+
+    match args.command {
+        Command::Generate { materials } => {
+            for name in materials {
+                let Some(material) = Material::from_name(&name) else {
+                    println!("Invalid material set {name}");
+                    continue;
+                };
+
+                let start = Instant::now();
+                tablebases.generate(&material);
+                let Some(table) = tablebases.get(&material) else {
+                    println!("{name}: no table needed, two kings alone are a draw");
+                    continue;
+                };
+                println!(
+                    "{}: longest mate {} plies, {:.1}s",
+                    table.get_material().name(),
+                    table.longest_mate(),
+                    start.elapsed().as_secs_f64()
+                );
+            }
+            if let Err(err) = tablebases.save(&args.directory) {
+                eprintln!("Could not write to {}: {err}", args.directory);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Probe { fen } => {
+            let Some(board) = Board::try_from_fen(&fen) else {
+                eprintln!("Invalid FEN {fen}");
+                return ExitCode::FAILURE;
+            };
+            match tablebases.probe(&board) {
+                Some(outcome) => {
+                    println!("{outcome:?}");
+                    if let Some(mov) = tablebases.best_move(&board) {
+                        println!("best move: {}", mov.to_algebraic());
+                    }
+                }
+                None => println!("Position not covered by the tablebases"),
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,83 @@
+use clap::{Parser, ValueEnum};
+
+use fchess::load_dataset;
+use fchess::EvalParams;
+use fchess::Tuner;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Method {
+    /// Texel local search, nudging one weight at a time
+    Local,
+    /// Gradient descent with a finite difference gradient
+    Gradient,
+}
+
+/// Tune the evaluation parameters on a dataset of positions labelled with game results
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// FEN/EPD file with one position and game result per line
+    dataset: String,
+
+    /// initial parameters (JSON or YAML), defaults to the built-in weights
+    #[arg(short, long)]
+    params: Option<String>,
+
+    /// where to save the tuned parameters after every iteration
+    #[arg(short, long, default_value = "tuned.yaml")]
+    output: String,
+
+    #[arg(short, long, value_enum, default_value_t = Method::Local)]
+    method: Method,
+
+    #[arg(short, long, default_value_t = 100)]
+    iterations: usize,
+
+    /// centipawns moved by the steepest weight on each gradient descent step
+    #[arg(short, long, default_value_t = 2.0)]
+    learning_rate: f64,
+
+    /// sigmoid scaling constant, fitted to the dataset when not given
+    #[arg(short, long)]
+    k: Option<f64>,
+}
+
+fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+
+    let params = match &args.params {
+        Some(filename) => EvalParams::from_filename(filename)?,
+        None => EvalParams::default(),
+    };
+    let positions = load_dataset(&args.dataset)?;
+    println!("positions: {}", positions.len());
+
+    let mut tuner = Tuner::new(positions);
+    match args.k {
+        Some(k) => tuner.set_k(k),
+        None => {
+            tuner.fit_k(&params);
+        }
+    }
+    println!("k: {}", tuner.get_k());
+    println!("initial error: {}", tuner.error(&params));
+
+    let output = args.output.clone();
+    let progress = |iteration: usize, error: f64, params: &EvalParams| {
+        println!("iteration {iteration}: error {error}");
+        if let Err(err) = params.save(&output) {
+            println!("Failed to save {output}: {err}");
+        }
+    };
+
+    let tuned = match args.method {
+        Method::Local => tuner.local_search(params, args.iterations, progress),
+        Method::Gradient => {
+            tuner.gradient_descent(params, args.iterations, args.learning_rate, progress)
+        }
+    };
+
+    tuned.save(&args.output)?;
+    println!("final error: {}", tuner.error(&tuned));
+    Ok(())
+}
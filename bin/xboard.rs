@@ -0,0 +1,7 @@
+use std::io;
+
+use fchess::xboard::XboardEngine;
+
+fn main() -> io::Result<()> {
+    XboardEngine::new().run(io::stdin().lock(), io::stdout())
+}
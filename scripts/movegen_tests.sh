@@ -2,5 +2,5 @@
 
 set -e
 
-cargo run --release --bin test_movegen -- testcases/movegen/standard.yaml
-cargo run --release --bin test_movegen -- testcases/movegen/promotion.yaml
+cargo run --release --features serde --bin test_movegen -- testcases/movegen/standard.yaml
+cargo run --release --features serde --bin test_movegen -- testcases/movegen/promotion.yaml
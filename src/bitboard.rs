@@ -21,3 +21,39 @@ impl BitboardExt for Bitboard {
         self.shift(s) & columns
     }
 }
+
+/// Bitboard with every square of the given file set
+pub fn file_mask(file: u8) -> Bitboard {
+    0x0101010101010101 << file
+}
+
+/// Bitboard with every square of the given rank set
+pub fn rank_mask(rank: u8) -> Bitboard {
+    0xFF << (8 * rank)
+}
+
+/// Bitboard with the files next to the given file set
+pub fn adjacent_files_mask(file: u8) -> Bitboard {
+    let mut mask = 0;
+    if file > 0 {
+        mask |= file_mask(file - 1);
+    }
+    if file < 7 {
+        mask |= file_mask(file + 1);
+    }
+    mask
+}
+
+/// Iterate over the indices of the set bits of a bitboard
+pub fn squares(bitboard: Bitboard) -> impl Iterator<Item = u8> {
+    let mut bitboard = bitboard;
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            None
+        } else {
+            let index = bitboard.trailing_zeros() as u8;
+            bitboard &= bitboard - 1;
+            Some(index)
+        }
+    })
+}
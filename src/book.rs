@@ -1,23 +1,65 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fmt;
 use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::board::Board;
+use crate::move_generator::MoveGenerator;
 use crate::moves::Move;
+use crate::pgn::PgnGame;
 use crate::piece::PieceType;
 use crate::side::Side;
 use crate::square::Square;
 
-#[derive(Clone, Copy)]
+/// Size of an entry in a `.bin` file
+const ENTRY_SIZE: usize = 16;
+
+/// Move of a position in the book, stored as 16 big endian bytes
+#[derive(Clone, Copy, PartialEq)]
 pub struct Entry {
     key: u64,
     mov: u16,
     weight: u16,
+    learn: u32,
 }
 
 impl Entry {
-    fn new(key: u64, mov: u16, weight: u16) -> Entry {
-        Entry { key, mov, weight }
+    fn new(key: u64, mov: u16, weight: u16, learn: u32) -> Entry {
+        Entry {
+            key,
+            mov,
+            weight,
+            learn,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Entry {
+        Entry::new(
+            u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        )
     }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.mov.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
     fn get_from(&self) -> Square {
         let file: u8 = (self.mov >> 6 & 0x7) as u8;
         let rank: u8 = (self.mov >> 9 & 0x7) as u8;
@@ -32,37 +74,107 @@ impl Entry {
         Square::from_rank_file(rank, file)
     }
 
-    fn get_key(&self) -> u64 {
+    /// Zobryst hash of the position
+    pub fn get_key(&self) -> u64 {
         self.key
     }
 
-    fn get_weight(&self) -> u16 {
+    pub fn get_weight(&self) -> u16 {
         self.weight
     }
 
-    fn to_move(self, board: &Board) -> Option<Move> {
-        Some(
-            match (
-                (self.get_from().get_rank(), self.get_from().get_file()),
-                (self.get_to().get_rank(), self.get_to().get_file()),
-                board.piece_at(self.get_from()),
-            ) {
-                ((0, 4), (0, 7), Some(PieceType::WhiteKing)) => {
-                    Move::new(self.get_from(), Square::from_rank_file(0, 6))
-                }
-                ((0, 4), (0, 0), Some(PieceType::WhiteKing)) => {
-                    Move::new(self.get_from(), Square::from_rank_file(0, 2))
-                }
-                ((7, 4), (7, 7), Some(PieceType::BlackKing)) => {
-                    Move::new(self.get_from(), Square::from_rank_file(7, 6))
-                }
-                ((7, 4), (7, 0), Some(PieceType::BlackKing)) => {
-                    Move::new(self.get_from(), Square::from_rank_file(7, 2))
-                }
-                _ => Move::new(self.get_from(), self.get_to()),
-            },
-        )
+    /// Learning data, left for the engine reading the book to interpret
+    pub fn get_learn(&self) -> u32 {
+        self.learn
+    }
+
+    /// Promotion piece of bits 12 to 14 for `side`, `Err` when the bits hold no PolyGlot piece
+    fn get_promotion(&self, side: Side) -> Result<Option<PieceType>, ()> {
+        let pieces = match side {
+            Side::White => [
+                PieceType::WhiteKnight,
+                PieceType::WhiteBishop,
+                PieceType::WhiteRook,
+                PieceType::WhiteQueen,
+            ],
+            Side::Black => [
+                PieceType::BlackKnight,
+                PieceType::BlackBishop,
+                PieceType::BlackRook,
+                PieceType::BlackQueen,
+            ],
+        };
+        match self.mov >> 12 {
+            0 => Ok(None),
+            piece @ 1..=4 => Ok(Some(pieces[piece as usize - 1])),
+            _ => Err(()),
+        }
+    }
+
+    /// Move of the entry among the `legal_moves` of `board`, `None` when the entry is corrupt
+    /// or its move is not legal there
+    fn to_move(self, board: &Board, legal_moves: &[Move]) -> Option<Move> {
+        let from = self.get_from();
+        let mut to = self.get_to();
+        let promotion = self.get_promotion(board.get_turn()).ok()?;
+
+        // castling is written as the king taking its own rook
+        let king = matches!(
+            board.piece_at(from),
+            Some(PieceType::WhiteKing | PieceType::BlackKing)
+        );
+        if king && from.get_file() == 4 && from.get_rank() == to.get_rank() {
+            match to.get_file() {
+                7 => to = Square::from_rank_file(to.get_rank(), 6),
+                0 => to = Square::from_rank_file(to.get_rank(), 2),
+                _ => {}
+            }
+        }
+
+        let mov = match promotion {
+            Some(promotion) => Move::with_promotion(from, to, promotion),
+            None => Move::new(from, to),
+        };
+        // castling without the right to, or through attacked squares, is not legal either
+        legal_moves
+            .iter()
+            .find(|legal| {
+                legal
+                    .to_algebraic()
+                    .eq_ignore_ascii_case(&mov.to_algebraic())
+            })
+            .cloned()
+    }
+}
+
+/// PolyGlot encoding of a legal move on `board`: destination, origin and promotion in 3 bit
+/// fields, castling written as the king taking its own rook
+fn encode_move(mov: &Move, board: &Board) -> u16 {
+    let (from, mut to) = (mov.get_src(), mov.get_dst());
+    let king = matches!(
+        board.piece_at(from),
+        Some(PieceType::WhiteKing | PieceType::BlackKing)
+    );
+    if king && from.get_file() == 4 && from.get_rank() == to.get_rank() {
+        match to.get_file() {
+            6 => to = Square::from_rank_file(to.get_rank(), 7),
+            2 => to = Square::from_rank_file(to.get_rank(), 0),
+            _ => {}
+        }
     }
+    let promotion = match mov.get_promotion() {
+        Some(PieceType::WhiteKnight | PieceType::BlackKnight) => 1,
+        Some(PieceType::WhiteBishop | PieceType::BlackBishop) => 2,
+        Some(PieceType::WhiteRook | PieceType::BlackRook) => 3,
+        Some(PieceType::WhiteQueen | PieceType::BlackQueen) => 4,
+        _ => 0,
+    };
+
+    (to.get_file() as u16)
+        | (to.get_rank() as u16) << 3
+        | (from.get_file() as u16) << 6
+        | (from.get_rank() as u16) << 9
+        | promotion << 12
 }
 
 impl fmt::Debug for Entry {
@@ -70,44 +182,163 @@ impl fmt::Debug for Entry {
         write!(f, "{:x} ", self.key)?;
         write!(f, "{:?} ", self.get_from())?;
         write!(f, "{:?} ", self.get_to())?;
-        write!(f, "{}", self.get_weight())
+        write!(f, "{} ", self.get_weight())?;
+        write!(f, "{}", self.get_learn())
     }
 }
 
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Where the entries of a book are read from
+enum Entries {
+    Memory(Vec<Entry>),
+    /// File read on demand, holding `len` entries
+    File {
+        file: Mutex<File>,
+        len: usize,
+    },
+}
+
+/// Candidate move of a book position
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookMove {
+    pub mov: Move,
+    pub weight: u16,
+    pub learn: u32,
+    /// Share of the weights of the position, in percent
+    pub percentage: f64,
+}
+
+/// How a move is picked among the candidates of a position
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BookPolicy {
+    /// The candidate with the highest weight
+    #[default]
+    Best,
+    /// A candidate with a probability proportional to its weight
+    WeightedRandom,
+    /// Any candidate of at least `min_weight`, with the same probability
+    Uniform { min_weight: u16 },
+}
+
 /// Representation of a PolyGlot book
-pub struct Book(Vec<Entry>);
+pub struct Book {
+    entries: Entries,
+    policy: BookPolicy,
+    max_plies: Option<u32>,
+    /// State of the xorshift64* generator used by the random policies
+    random: AtomicU64,
+}
 
 impl Book {
+    fn new(entries: Entries) -> Book {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |time| time.as_nanos() as u64);
+        Book {
+            entries,
+            policy: BookPolicy::Best,
+            max_plies: None,
+            random: AtomicU64::new(seed.max(1)),
+        }
+    }
+
+    /// Book loaded in memory from a `.bin` file, panicking when it cannot be read
+    #[deprecated(note = "use `Book::open`, which returns an error instead of panicking")]
     pub fn from_filename(filename: &str) -> Book {
-        let bytes = fs::read(filename).unwrap();
-        let struct_size = 8 + 2 + 2 + 4;
-        let mut book: Book = Book(vec![]);
-        for i in 0..(bytes.len() / struct_size) {
-            let key: u64 = ((bytes[struct_size * i] as u64) << (7 * 8))
-                + ((bytes[struct_size * i + 1] as u64) << (6 * 8))
-                + ((bytes[struct_size * i + 2] as u64) << (5 * 8))
-                + ((bytes[struct_size * i + 3] as u64) << (4 * 8))
-                + ((bytes[struct_size * i + 4] as u64) << (3 * 8))
-                + ((bytes[struct_size * i + 5] as u64) << (2 * 8))
-                + ((bytes[struct_size * i + 6] as u64) << 8)
-                + (bytes[struct_size * i + 7] as u64);
-
-            let mov: u16 =
-                ((bytes[struct_size * i + 8] as u16) << 8) + (bytes[struct_size * i + 9] as u16);
-            let weight: u16 =
-                ((bytes[struct_size * i + 10] as u16) << 8) + bytes[struct_size * i + 11] as u16;
-
-            book.0.push(Entry::new(key, mov, weight));
-        }
-        book
-    }
-
-    fn binary_search(&self, key: u64) -> usize {
+        Book::open(filename).expect("cannot open book")
+    }
+
+    /// Book loaded in memory from a `.bin` file, checking that the entries are sorted by key
+    pub fn open<P: AsRef<Path>>(filename: P) -> io::Result<Book> {
+        Book::from_bytes(&fs::read(filename)?)
+    }
+
+    /// Book whose entries are read from the file as positions are looked up, for books too
+    /// large to keep in memory. The order of the entries is not checked.
+    pub fn open_on_demand<P: AsRef<Path>>(filename: P) -> io::Result<Book> {
+        let file = File::open(filename)?;
+        let size = file.metadata()?.len() as usize;
+        if !size.is_multiple_of(ENTRY_SIZE) {
+            return Err(invalid_data(format!(
+                "book size {size} is not a multiple of {ENTRY_SIZE} bytes"
+            )));
+        }
+
+        Ok(Book::new(Entries::File {
+            file: Mutex::new(file),
+            len: size / ENTRY_SIZE,
+        }))
+    }
+
+    /// Book of the contents of a `.bin` file, checking that the entries are sorted by key
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Book> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(invalid_data(format!(
+                "book size {} is not a multiple of {ENTRY_SIZE} bytes",
+                bytes.len()
+            )));
+        }
+
+        let (chunks, _) = bytes.as_chunks::<ENTRY_SIZE>();
+        let entries: Vec<Entry> = chunks.iter().map(Entry::from_bytes).collect();
+        if let Some(index) = entries
+            .windows(2)
+            .position(|pair| pair[0].key > pair[1].key)
+        {
+            return Err(invalid_data(format!(
+                "entry {} is out of order, books must be sorted by key",
+                index + 1
+            )));
+        }
+
+        Ok(Book::new(Entries::Memory(entries)))
+    }
+
+    /// Write the book in the `.bin` format
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for index in 0..self.len() {
+            writer.write_all(&self.get_entry(index)?.to_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.entries {
+            Entries::Memory(entries) => entries.len(),
+            Entries::File { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Entry at `index` in the order of the file
+    pub fn get_entry(&self, index: usize) -> io::Result<Entry> {
+        match &self.entries {
+            Entries::Memory(entries) => entries.get(index).copied().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("no entry {index}"))
+            }),
+            Entries::File { file, .. } => {
+                let mut file = file.lock().unwrap();
+                let mut bytes = [0; ENTRY_SIZE];
+                file.seek(SeekFrom::Start((index * ENTRY_SIZE) as u64))?;
+                file.read_exact(&mut bytes)?;
+                Ok(Entry::from_bytes(&bytes))
+            }
+        }
+    }
+
+    /// Index of the first entry with a key not below `key`
+    fn binary_search(&self, key: u64) -> io::Result<usize> {
         let mut lo: usize = 0;
-        let mut hi: usize = self.0.len();
+        let mut hi: usize = self.len();
         while lo < hi {
             let mid = (lo + hi) / 2;
-            let entry = self.0[mid];
+            let entry = self.get_entry(mid)?;
             if entry.get_key() < key {
                 lo = mid + 1;
             } else {
@@ -115,29 +346,454 @@ impl Book {
             }
         }
 
-        lo
+        Ok(lo)
     }
 
-    fn find_all(&self, board: &Board, turn: &Side) -> Vec<Entry> {
+    fn find_all(&self, board: &Board, turn: &Side) -> io::Result<Vec<Entry>> {
         let hash = board.zobryst_hash(turn);
-        let index = self.binary_search(hash);
-
         let mut entries: Vec<Entry> = vec![];
-        for entry in self.0[index..].iter() {
+        for index in self.binary_search(hash)?..self.len() {
+            let entry = self.get_entry(index)?;
             if entry.get_key() != hash {
                 break;
             }
 
-            entries.push(*entry);
+            entries.push(entry);
         }
 
-        entries
+        Ok(entries)
     }
 
+    /// Candidate with the highest weight, whatever the policy
     pub fn get_best_move(&self, board: &Board) -> Option<Move> {
-        let all_entries = self.find_all(board, &board.get_turn());
-        let best_entry = all_entries.iter().max_by_key(|entry| entry.get_weight())?;
+        let candidates = self.entries(board).ok()?;
+        candidates.into_iter().next().map(|candidate| candidate.mov)
+    }
+
+    /// Legal candidate moves of a position, by decreasing weight
+    pub fn entries(&self, board: &Board) -> io::Result<Vec<BookMove>> {
+        let entries = self.find_all(board, &board.get_turn())?;
+        let legal_moves = MoveGenerator::new().legal_moves(board);
+        // corrupt entries and illegal moves are skipped
+        let mut moves: Vec<(Move, Entry)> = entries
+            .into_iter()
+            .filter_map(|entry| Some((entry.to_move(board, &legal_moves)?, entry)))
+            .collect();
+        moves.sort_by_key(|(_, entry)| Reverse(entry.get_weight()));
+
+        let total: u32 = moves
+            .iter()
+            .map(|(_, entry)| entry.get_weight() as u32)
+            .sum();
+        Ok(moves
+            .into_iter()
+            .map(|(mov, entry)| BookMove {
+                mov,
+                weight: entry.get_weight(),
+                learn: entry.get_learn(),
+                percentage: match total {
+                    0 => 0.0,
+                    _ => 100.0 * entry.get_weight() as f64 / total as f64,
+                },
+            })
+            .collect())
+    }
+
+    pub fn get_policy(&self) -> BookPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: BookPolicy) {
+        self.policy = policy;
+    }
+
+    /// Stop playing book moves after this many plies of the game, counted from the move
+    /// number of the position
+    pub fn set_max_plies(&mut self, max_plies: Option<u32>) {
+        self.max_plies = max_plies;
+    }
+
+    /// Seed the random policies, making their choices repeatable
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = AtomicU64::new(seed.max(1));
+    }
+
+    fn next_random(&self) -> u64 {
+        let mut state = self.random.load(Ordering::Relaxed);
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        self.random.store(state, Ordering::Relaxed);
+        state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// Move chosen by the policy, or `None` when out of book
+    pub fn get_move(&self, board: &Board) -> Option<Move> {
+        let ply = 2 * (board.get_full_move_clock().max(1) as u32 - 1) + board.get_turn() as u32;
+        if self.max_plies.is_some_and(|max_plies| ply >= max_plies) {
+            return None;
+        }
+
+        let mut candidates = self.entries(board).ok()?;
+        match self.policy {
+            BookPolicy::Best => candidates.into_iter().next(),
+            BookPolicy::WeightedRandom => {
+                let total: u64 = candidates.iter().map(|mov| mov.weight as u64).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = self.next_random() % total;
+                candidates.into_iter().find(|mov| {
+                    let found = pick < mov.weight as u64;
+                    pick = pick.saturating_sub(mov.weight as u64);
+                    found
+                })
+            }
+            BookPolicy::Uniform { min_weight } => {
+                candidates.retain(|mov| mov.weight >= min_weight);
+                if candidates.is_empty() {
+                    return None;
+                }
+                let pick = self.next_random() % candidates.len() as u64;
+                Some(candidates.swap_remove(pick as usize))
+            }
+        }
+        .map(|mov| mov.mov)
+    }
+}
+
+/// Games and points, 2 for a win and 1 for a draw, of a move played in a position
+#[derive(Clone, Copy, Debug, Default)]
+struct MoveStats {
+    games: u32,
+    points: u32,
+}
+
+/// Book made from the moves played in a collection of games
+pub struct BookBuilder {
+    max_plies: usize,
+    min_count: u32,
+    min_elo: Option<u32>,
+    stats: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    /// Builder keeping the first `max_plies` plies of each game
+    pub fn new(max_plies: usize) -> BookBuilder {
+        BookBuilder {
+            max_plies,
+            min_count: 1,
+            min_elo: None,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Leave out moves played in fewer than `min_count` games
+    pub fn set_min_count(&mut self, min_count: u32) {
+        self.min_count = min_count;
+    }
+
+    /// Only count the moves of players rated at least `min_elo`
+    pub fn set_min_elo(&mut self, min_elo: Option<u32>) {
+        self.min_elo = min_elo;
+    }
+
+    /// Count the moves of a finished game, returning whether any was counted
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        let points = match game.result.as_str() {
+            "1-0" => [2, 0],
+            "0-1" => [0, 2],
+            "1/2-1/2" => [1, 1],
+            _ => return false,
+        };
+        let rated = |tag: &str| match self.min_elo {
+            Some(min_elo) => game
+                .get_tag(tag)
+                .and_then(|elo| elo.parse::<u32>().ok())
+                .is_some_and(|elo| elo >= min_elo),
+            None => true,
+        };
+        let rated = [rated("WhiteElo"), rated("BlackElo")];
+
+        let mut counted = false;
+        let mut board = game.board.clone();
+        for node in game.mainline_nodes().into_iter().take(self.max_plies) {
+            let side = board.get_turn() as usize;
+            if rated[side] {
+                let key = board.zobryst_hash(&board.get_turn());
+                let stats = self
+                    .stats
+                    .entry((key, encode_move(&node.mov, &board)))
+                    .or_default();
+                stats.games += 1;
+                stats.points += points[side];
+                counted = true;
+            }
+            board = board.apply(node.mov.clone()).unwrap();
+        }
+        counted
+    }
+
+    /// Book of the counted moves, sorted by key and then by decreasing weight. Moves that
+    /// never scored are left out, and the weights of a position are scaled down together when
+    /// they do not fit in 16 bits.
+    pub fn build(&self) -> Book {
+        let mut positions: HashMap<u64, Vec<(u16, u32)>> = HashMap::new();
+        for (&(key, mov), stats) in &self.stats {
+            if stats.games >= self.min_count && stats.points > 0 {
+                positions.entry(key).or_default().push((mov, stats.points));
+            }
+        }
+
+        let mut entries = vec![];
+        for (key, moves) in positions {
+            let max = moves.iter().map(|&(_, points)| points).max().unwrap_or(0);
+            let scale = max.div_ceil(u16::MAX as u32).max(1);
+            entries.extend(
+                moves
+                    .into_iter()
+                    .map(|(mov, points)| Entry::new(key, mov, (points / scale).max(1) as u16, 0)),
+            );
+        }
+        entries.sort_by_key(|entry| (entry.key, Reverse(entry.weight), entry.mov));
+        Book::new(Entries::Memory(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::board::STARTPOS;
+    use crate::pgn::read_games;
+
+    #[rstest]
+    #[case(STARTPOS, "e2e4", 0x031c)]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", 0x0107)]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1", 0x0100)]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8g8", 0x0f3f)]
+    #[case("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q", 0x4c38)]
+    #[case("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8n", 0x1c38)]
+    fn test_encode_move(#[case] fen: &str, #[case] mov: &str, #[case] encoded: u16) {
+        let board = Board::from_fen(fen);
+        let mov = Move::from_full_algebraic(mov).unwrap();
+        assert_eq!(encode_move(&mov, &board), encoded);
+    }
+
+    #[test]
+    fn test_build_book() {
+        let games = read_games(
+            "[WhiteElo \"2400\"]\n[BlackElo \"2000\"]\n\n1. e4 e5 2. Nf3 1-0\n\n\
+             [WhiteElo \"2300\"]\n[BlackElo \"2500\"]\n\n1. e4 c5 0-1\n\n\
+             1. d4 d5 1/2-1/2\n\n\
+             1. c4 *\n",
+        )
+        .unwrap();
+        let mut builder = BookBuilder::new(2);
+        let counted: Vec<bool> = games.iter().map(|game| builder.add_game(game)).collect();
+        assert_eq!(counted, [true, true, true, false]);
+
+        let mut bytes = vec![];
+        builder.build().write_to(&mut bytes).unwrap();
+        let book = Book::from_bytes(&bytes).unwrap();
+        // e4 scored 2 of 4 points, d4 1 of 2, e5 lost and c5 won
+        assert_eq!(book.len(), 4);
+        let start = Board::from_fen(STARTPOS);
+        assert_eq!(book.get_best_move(&start).unwrap().to_algebraic(), "e2e4");
+        let after_e4 = start
+            .apply(Move::from_full_algebraic("e2e4").unwrap())
+            .unwrap();
+        assert_eq!(
+            book.get_best_move(&after_e4).unwrap().to_algebraic(),
+            "c7c5"
+        );
+        let entries: Vec<Entry> = (0..book.len())
+            .map(|index| book.get_entry(index).unwrap())
+            .collect();
+        assert!(entries.windows(2).all(|pair| {
+            (pair[0].key, Reverse(pair[0].weight)) <= (pair[1].key, Reverse(pair[1].weight))
+        }));
+
+        builder.set_min_count(2);
+        assert_eq!(builder.build().len(), 1);
+
+        let mut builder = BookBuilder::new(2);
+        builder.set_min_elo(Some(2400));
+        games.iter().for_each(|game| {
+            builder.add_game(game);
+        });
+        // only the moves of the 2400 white player and the 2500 black player count
+        let book = builder.build();
+        assert_eq!(book.len(), 2);
+        assert!(book.get_best_move(&after_e4).is_some());
+    }
+
+    fn entry_bytes(entries: &[(u64, u16, u16, u32)]) -> Vec<u8> {
+        entries
+            .iter()
+            .flat_map(|&(key, mov, weight, learn)| Entry::new(key, mov, weight, learn).to_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_open_book() {
+        let bytes = entry_bytes(&[
+            (1, 0x031c, 10, 7),
+            (0x463b96181691fc9c, 0x0314, 5, 0),
+            (0x463b96181691fc9c, 0x031c, 20, 0x01020304),
+            (u64::MAX, 0x0000, 1, 0),
+        ]);
+        let filename = std::env::temp_dir().join(format!("fchess-book-{}.bin", std::process::id()));
+        fs::write(&filename, &bytes).unwrap();
+        let in_memory = Book::open(&filename).unwrap();
+        let on_demand = Book::open_on_demand(&filename).unwrap();
+        #[allow(deprecated)]
+        let panicking = Book::from_filename(filename.to_str().unwrap());
+        fs::remove_file(&filename).unwrap();
+
+        let start = Board::from_fen(STARTPOS);
+        for book in [in_memory, on_demand, panicking] {
+            assert_eq!(book.len(), 4);
+            assert_eq!(book.get_entry(0).unwrap().get_learn(), 7);
+            assert_eq!(book.get_entry(2).unwrap().get_learn(), 0x01020304);
+            assert!(book.get_entry(4).is_err());
+            assert_eq!(book.get_best_move(&start).unwrap().to_algebraic(), "e2e4");
+
+            let mut written = vec![];
+            book.write_to(&mut written).unwrap();
+            assert_eq!(written, bytes);
+        }
+    }
+
+    #[test]
+    fn test_open_invalid_book() {
+        assert!(Book::open("no such book.bin").is_err());
+        assert!(Book::open_on_demand("no such book.bin").is_err());
+
+        let bytes = entry_bytes(&[(1, 0, 1, 0), (2, 0, 1, 0)]);
+        assert!(Book::from_bytes(&bytes).is_ok());
+        assert!(Book::from_bytes(&bytes[..20]).is_err());
+
+        let unsorted = entry_bytes(&[(2, 0, 1, 0), (1, 0, 1, 0)]);
+        let err = Book::from_bytes(&unsorted).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("sorted"));
+    }
+
+    fn start_book() -> Book {
+        let start = 0x463b96181691fc9c;
+        let bytes = entry_bytes(&[
+            (start, 0x029a, 1, 0),
+            (start, 0x02db, 10, 0),
+            (start, 0x031c, 30, 0),
+        ]);
+        Book::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_book_entries() {
+        let book = start_book();
+        let entries = book.entries(&Board::from_fen(STARTPOS)).unwrap();
+        let summary: Vec<(String, u16)> = entries
+            .iter()
+            .map(|entry| (entry.mov.to_algebraic(), entry.weight))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("e2e4".to_string(), 30),
+                ("d2d4".to_string(), 10),
+                ("c2c4".to_string(), 1)
+            ]
+        );
+        let total: f64 = entries.iter().map(|entry| entry.percentage).sum();
+        assert!((total - 100.0).abs() < 1e-9);
+        assert!((entries[1].percentage - 1000.0 / 41.0).abs() < 1e-9);
+
+        let after_e4 =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(book.entries(&after_e4).unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case(BookPolicy::Best, &["e2e4"])]
+    #[case(BookPolicy::WeightedRandom, &["c2c4", "d2d4", "e2e4"])]
+    #[case(BookPolicy::Uniform { min_weight: 5 }, &["d2d4", "e2e4"])]
+    #[case(BookPolicy::Uniform { min_weight: 100 }, &[])]
+    fn test_book_policy(#[case] policy: BookPolicy, #[case] expected: &[&str]) {
+        let mut book = start_book();
+        book.set_policy(policy);
+        book.set_seed(42);
+        let start = Board::from_fen(STARTPOS);
+
+        let mut counts = HashMap::new();
+        for _ in 0..1000 {
+            if let Some(mov) = book.get_move(&start) {
+                *counts.entry(mov.to_algebraic()).or_insert(0) += 1;
+            }
+        }
+        let mut moves: Vec<&str> = counts.keys().map(String::as_str).collect();
+        moves.sort();
+        assert_eq!(moves, expected);
+        if policy == BookPolicy::WeightedRandom {
+            // 30 of 41 of the weight
+            assert!((650..810).contains(&counts["e2e4"]));
+        }
+    }
+
+    #[test]
+    fn test_book_seed_and_depth() {
+        let mut book = start_book();
+        book.set_policy(BookPolicy::WeightedRandom);
+        let start = Board::from_fen(STARTPOS);
+        let draw = |book: &mut Book| -> Vec<Option<Move>> {
+            book.set_seed(7);
+            (0..20).map(|_| book.get_move(&start)).collect()
+        };
+        assert_eq!(draw(&mut book), draw(&mut book));
+
+        book.set_max_plies(Some(1));
+        assert!(book.get_move(&start).is_some());
+        book.set_max_plies(Some(0));
+        assert!(book.get_move(&start).is_none());
+        let later = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3");
+        book.set_max_plies(Some(4));
+        assert!(book.get_move(&later).is_none());
+    }
+
+    fn candidates(fen: &str, moves: &[u16]) -> Vec<String> {
+        let board = Board::from_fen(fen);
+        let key = board.zobryst_hash(&board.get_turn());
+        let entries: Vec<(u64, u16, u16, u32)> = moves
+            .iter()
+            .enumerate()
+            .map(|(index, &mov)| (key, mov, 100 - index as u16, 0))
+            .collect();
+        let book = Book::from_bytes(&entry_bytes(&entries)).unwrap();
+        book.entries(&board)
+            .unwrap()
+            .iter()
+            .map(|candidate| candidate.mov.to_algebraic().to_lowercase())
+            .collect()
+    }
 
-        best_entry.to_move(board)
+    #[rstest]
+    #[case("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", &[0x4c38, 0x1c38, 0x2c38, 0x3c38], &["a7a8q", "a7a8n", "a7a8b", "a7a8r"])]
+    #[case("4k3/8/8/8/8/8/p7/4K3 b - - 0 1", &[0x4200, 0x3200], &["a2a1q", "a2a1r"])]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &[0x0107, 0x0100], &["e1g1", "e1c1"])]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", &[0x0f3f, 0x0f38], &["e8g8", "e8c8"])]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1", &[0x0107, 0x0100], &["e1c1"])]
+    #[case("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1", &[0x0107, 0x0100, 0x0105], &["e1f1"])]
+    // a queen promotion without its piece bits is no legal move
+    #[case("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", &[0x0c38, 0x010c], &["e1e2"])]
+    // unknown promotion pieces, the unused top bit and moves of missing pieces are corrupt
+    #[case(STARTPOS, &[0x531c, 0x831c, 0x0e24, 0x031c], &["e2e4"])]
+    fn test_book_move_decoding(
+        #[case] fen: &str,
+        #[case] moves: &[u16],
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(candidates(fen, moves), expected);
     }
 }
@@ -0,0 +1,207 @@
+//! Client side of the Universal Chess Interface, driving any UCI engine run as a subprocess.
+
+use std::ffi::OsStr;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+
+use crate::moves::Move;
+use crate::solver::SearchLimits;
+use crate::uci::{move_to_uci, Info, UciCommand, UciOption, UciResponse};
+
+/// Outcome of a `go` command
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchResult {
+    /// `None` when the position has no legal move
+    pub best_move: Option<Move>,
+    pub ponder: Option<Move>,
+    /// Every `info` line sent during the search, in order
+    pub info: Vec<Info>,
+}
+
+impl SearchResult {
+    /// Last reported line with a score, normally the final principal variation
+    pub fn last_scored(&self) -> Option<&Info> {
+        self.info.iter().rev().find(|info| info.score.is_some())
+    }
+}
+
+fn protocol_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// UCI engine subprocess, ready to search once created
+pub struct Engine {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<UciOption>,
+}
+
+impl Engine {
+    /// Start `program` and perform the `uci` handshake
+    pub fn spawn<S: AsRef<OsStr>>(program: S, args: &[S]) -> io::Result<Engine> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+
+        let mut engine = Engine {
+            child,
+            input,
+            output,
+            name: None,
+            author: None,
+            options: vec![],
+        };
+        engine.send(&UciCommand::Uci)?;
+        loop {
+            match engine.read()? {
+                UciResponse::IdName(name) => engine.name = Some(name),
+                UciResponse::IdAuthor(author) => engine.author = Some(author),
+                UciResponse::Option(option) => engine.options.push(option),
+                UciResponse::UciOk => break,
+                _ => {}
+            }
+        }
+
+        Ok(engine)
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// Options advertised during the handshake
+    pub fn get_options(&self) -> &[UciOption] {
+        &self.options
+    }
+
+    pub fn send(&mut self, command: &UciCommand) -> io::Result<()> {
+        writeln!(self.input, "{command}")?;
+        self.input.flush()
+    }
+
+    /// Next response of the engine, skipping lines that are not part of the protocol
+    pub fn read(&mut self) -> io::Result<UciResponse> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.output.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "engine closed its output",
+                ));
+            }
+            if let Ok(response) = line.parse() {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// Wait until the engine has processed every command sent so far
+    pub fn is_ready(&mut self) -> io::Result<()> {
+        self.send(&UciCommand::IsReady)?;
+        while self.read()? != UciResponse::ReadyOk {}
+        Ok(())
+    }
+
+    /// Set an advertised option, checking `value` against its type
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> io::Result<()> {
+        let option = self
+            .options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("no option {name}"))
+            })?;
+        option
+            .parse_value(value)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let name = option.name.clone();
+        self.send(&UciCommand::SetOption {
+            name,
+            value: value.map(str::to_string),
+        })?;
+        self.is_ready()
+    }
+
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send(&UciCommand::UciNewGame)?;
+        self.is_ready()
+    }
+
+    /// Position reached by playing `moves` from `fen`, or from the start position when `None`
+    pub fn set_position(&mut self, fen: Option<&str>, moves: &[Move]) -> io::Result<()> {
+        self.send(&UciCommand::Position {
+            fen: fen.map(str::to_string),
+            moves: moves.iter().map(move_to_uci).collect(),
+        })
+    }
+
+    /// Search the current position until the engine answers with its best move
+    pub fn go(&mut self, limits: &SearchLimits) -> io::Result<SearchResult> {
+        self.go_with_progress(limits, |_| {})
+    }
+
+    /// Search like `go`, calling `progress` with each `info` line as it arrives
+    pub fn go_with_progress<F: FnMut(&Info)>(
+        &mut self,
+        limits: &SearchLimits,
+        mut progress: F,
+    ) -> io::Result<SearchResult> {
+        if limits.infinite || limits.ponder {
+            return Err(protocol_error(
+                "searches that wait for stop need send and read".to_string(),
+            ));
+        }
+        self.send(&UciCommand::Go(limits.clone()))?;
+
+        let mut result = SearchResult::default();
+        loop {
+            match self.read()? {
+                UciResponse::Info(info) => {
+                    progress(&info);
+                    result.info.push(info);
+                }
+                UciResponse::BestMove { mov, ponder } => {
+                    result.best_move = mov;
+                    result.ponder = ponder;
+                    return Ok(result);
+                }
+                response => {
+                    return Err(protocol_error(format!(
+                        "unexpected response while searching: {response}"
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Ask the engine to exit and wait for it
+    pub fn quit(mut self) -> io::Result<ExitStatus> {
+        self.send(&UciCommand::Quit)?;
+        self.child.wait()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.send(&UciCommand::Quit);
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
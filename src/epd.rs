@@ -0,0 +1,369 @@
+//! Extended Position Description: the first four fields of a FEN followed by operations
+//! written as `opcode operand...;`, as used by test suites and opening collections.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::board::Board;
+use crate::moves::Move;
+use crate::solver::Score;
+
+/// Position with its operations
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    pub board: Board,
+    /// Operations in the order they were written, string operands without their quotes
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Opcodes whose operand is a string, always written quoted
+fn is_string_opcode(opcode: &str) -> bool {
+    opcode == "id"
+        || (opcode.len() == 2 && opcode.starts_with('c') && opcode.ends_with(char::is_numeric))
+}
+
+/// Opcodes and operands of the text after the position
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut operations = vec![];
+    let mut words: Vec<String> = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => {
+                chars.next();
+                let mut operation = words.drain(..);
+                if let Some(opcode) = operation.next() {
+                    operations.push((opcode, operation.collect()));
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                            word.extend(chars.next())
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                if words.is_empty() {
+                    return Err(format!("string \"{word}\" without an opcode"));
+                }
+                words.push(word);
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ';' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                words.push(word);
+            }
+        }
+    }
+
+    // the last semicolon is often left out
+    let mut operation = words.into_iter();
+    if let Some(opcode) = operation.next() {
+        operations.push((opcode, operation.collect()));
+    }
+    Ok(operations)
+}
+
+impl Epd {
+    pub fn new(board: Board) -> Epd {
+        Epd {
+            board,
+            operations: vec![],
+        }
+    }
+
+    /// Operands of the first operation with `opcode`
+    pub fn get_operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Set an operation, keeping its place when it is already present
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    fn get_string(&self, opcode: &str) -> Option<&str> {
+        self.get_operation(opcode)
+            .and_then(|operands| operands.first())
+            .map(String::as_str)
+    }
+
+    fn get_number(&self, opcode: &str) -> Option<u32> {
+        self.get_string(opcode)
+            .and_then(|operand| operand.parse().ok())
+    }
+
+    fn get_moves(&self, opcode: &str) -> Vec<Move> {
+        self.get_operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|san| Move::from_san(san, &self.board))
+            .collect()
+    }
+
+    /// Name of the position, `id`
+    pub fn get_id(&self) -> Option<&str> {
+        self.get_string("id")
+    }
+
+    /// Main comment, `c0`
+    pub fn get_comment(&self) -> Option<&str> {
+        self.get_string("c0")
+    }
+
+    /// Moves that solve the position, `bm`
+    pub fn get_best_moves(&self) -> Vec<Move> {
+        self.get_moves("bm")
+    }
+
+    /// Moves that fail the position, `am`
+    pub fn get_avoid_moves(&self) -> Vec<Move> {
+        self.get_moves("am")
+    }
+
+    /// Moves of the side to move until mate, `dm`
+    pub fn get_direct_mate(&self) -> Option<u32> {
+        self.get_number("dm")
+    }
+
+    /// Depth the position was analysed to, `acd`
+    pub fn get_analysis_depth(&self) -> Option<u32> {
+        self.get_number("acd")
+    }
+
+    /// Whether playing `mov` with `score` solves the position: it must be one of the best
+    /// moves, none of the moves to avoid and mate soon enough. `None` when there is nothing to
+    /// check.
+    pub fn is_solved_by(&self, mov: &Move, score: Option<Score>) -> Option<bool> {
+        let best = self.get_best_moves();
+        let avoid = self.get_avoid_moves();
+        let mate = self.get_direct_mate();
+        if best.is_empty() && avoid.is_empty() && mate.is_none() {
+            return None;
+        }
+
+        let matches = |moves: &[Move]| {
+            moves.iter().any(|other| {
+                other
+                    .to_algebraic()
+                    .eq_ignore_ascii_case(&mov.to_algebraic())
+            })
+        };
+        let mates = match (mate, score) {
+            (None, _) => true,
+            (Some(mate), Some(Score::Mate(moves))) => moves > 0 && moves as u32 <= mate,
+            (Some(_), _) => false,
+        };
+        Some((best.is_empty() || matches(&best)) && !matches(&avoid) && mates)
+    }
+}
+
+impl FromStr for Epd {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Epd, String> {
+        let line = line.trim_start();
+        let mut fields = vec![];
+        let mut rest = line;
+        for _ in 0..4 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+
+        let fen = format!("{} 0 1", fields.join(" "));
+        let mut board = Board::try_from_fen(&fen)
+            .ok_or_else(|| format!("invalid position {}", fields.join(" ")))?;
+        let operations = parse_operations(rest)?;
+
+        for (opcode, operands) in &operations {
+            let operand = operands.first().map(String::as_str).unwrap_or_default();
+            match opcode.as_str() {
+                "bm" | "am" => {
+                    if let Some(san) = operands
+                        .iter()
+                        .find(|san| Move::from_san(san, &board).is_none())
+                    {
+                        return Err(format!("illegal move {san} in {opcode}"));
+                    }
+                }
+                "dm" | "acd" if operand.parse::<u32>().is_err() => {
+                    return Err(format!("invalid number {operand} in {opcode}"));
+                }
+                "hmvc" => board.set_half_move_clock(
+                    operand
+                        .parse()
+                        .map_err(|_| format!("invalid half move clock {operand}"))?,
+                ),
+                "fmvn" => board.set_full_move_clock(
+                    operand
+                        .parse()
+                        .map_err(|_| format!("invalid full move number {operand}"))?,
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(Epd { board, operations })
+    }
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fen = self.board.to_fen();
+        let fields: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {opcode}")?;
+            for operand in operands {
+                if is_string_opcode(opcode)
+                    || operand.is_empty()
+                    || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"')
+                {
+                    let escaped = operand.replace('\\', "\\\\").replace('"', "\\\"");
+                    write!(f, " \"{escaped}\"")?;
+                } else {
+                    write!(f, " {operand}")?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+/// Positions of an EPD file, one per line, skipping blank lines
+pub fn parse_epds(text: &str) -> Result<Vec<Epd>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            line.parse()
+                .map_err(|err| format!("line {}: {err}", number + 1))
+        })
+        .collect()
+}
+
+pub fn load_epds<P: AsRef<Path>>(filename: P) -> io::Result<Vec<Epd>> {
+    parse_epds(&fs::read_to_string(filename)?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_epd() {
+        let epd: Epd = "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - \
+                        bm O-O; am Ng5; id \"test 1\"; c0 \"quiet; castle\"; acd 12;"
+            .parse()
+            .unwrap();
+
+        assert_eq!(epd.get_id(), Some("test 1"));
+        assert_eq!(epd.get_comment(), Some("quiet; castle"));
+        assert_eq!(epd.get_analysis_depth(), Some(12));
+        assert_eq!(epd.get_direct_mate(), None);
+        assert_eq!(
+            epd.get_best_moves(),
+            vec![Move::from_full_algebraic("e1g1").unwrap()]
+        );
+        assert_eq!(
+            epd.get_avoid_moves(),
+            vec![Move::from_full_algebraic("f3g5").unwrap()]
+        );
+        assert_eq!(
+            epd.board.to_fen(),
+            "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_epd_round_trip() {
+        let line = "8/8/8/8/8/5k2/8/5K1R w - - bm Rh3+ Kg1; id \"a \\\"quoted\\\" name\"; hmvc 7;";
+        let epd: Epd = line.parse().unwrap();
+
+        assert_eq!(epd.get_id(), Some("a \"quoted\" name"));
+        assert_eq!(epd.board.get_half_move_clock(), 7);
+        assert_eq!(epd.to_string(), line);
+        assert_eq!(epd.to_string().parse::<Epd>().unwrap(), epd);
+    }
+
+    #[rstest]
+    #[case("not a position")]
+    #[case("8/8/8/8/8/8/8/8 w - - bm e4;")]
+    #[case("4k3/8/8/8/8/8/4P3/4K3 w - - bm e5;")]
+    #[case("4k3/8/8/8/8/8/4P3/4K3 w - - dm two;")]
+    #[case("4k3/8/8/8/8/8/4P3/4K3 w - - id \"open;")]
+    fn test_parse_epd_rejects(#[case] line: &str) {
+        assert!(line.parse::<Epd>().is_err());
+    }
+
+    #[rstest]
+    #[case("e1g1", None, Some(true))]
+    #[case("c4f7", None, Some(true))]
+    #[case("d2d3", None, Some(false))]
+    #[case("f3g5", None, Some(false))]
+    fn test_is_solved_by(
+        #[case] mov: &str,
+        #[case] score: Option<Score>,
+        #[case] solved: Option<bool>,
+    ) {
+        let epd: Epd = "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - \
+                        bm O-O Bxf7+; am Ng5;"
+            .parse()
+            .unwrap();
+        let mov = Move::from_full_algebraic(mov).unwrap();
+        assert_eq!(epd.is_solved_by(&mov, score), solved);
+    }
+
+    #[rstest]
+    #[case(Some(Score::Mate(1)), Some(true))]
+    #[case(Some(Score::Mate(3)), Some(false))]
+    #[case(Some(Score::Centipawns(900)), Some(false))]
+    fn test_is_solved_by_mate(#[case] score: Option<Score>, #[case] solved: Option<bool>) {
+        let epd: Epd = "k7/8/2K5/8/8/8/8/7R w - - dm 2;".parse().unwrap();
+        let mov = Move::from_full_algebraic("h1h7").unwrap();
+        assert_eq!(epd.is_solved_by(&mov, score), solved);
+
+        let plain: Epd = "k7/8/2K5/8/8/8/8/7R w - - id \"x\";".parse().unwrap();
+        assert_eq!(plain.is_solved_by(&mov, score), None);
+    }
+
+    #[test]
+    fn test_parse_epds() {
+        let epds = parse_epds("4k3/8/8/8/8/8/4P3/4K3 w - - bm e4;\n\n4k3/8/8/8/8/8/8/4K3 b - -\n")
+            .unwrap();
+        assert_eq!(epds.len(), 2);
+        assert!(epds[1].operations.is_empty());
+
+        let err = parse_epds("4k3/8/8/8/8/8/4P3/4K3 w - -\nbad").unwrap_err();
+        assert!(err.starts_with("line 2:"));
+    }
+}
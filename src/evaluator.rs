@@ -0,0 +1,309 @@
+use crate::bitboard::{file_mask, squares, Bitboard};
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+use crate::moves::{Move, Scope};
+use crate::params::EvalParams;
+use crate::pawns::{relative_rank_mask, PawnHashTable};
+use crate::piece::{Piece, PieceType};
+use crate::side::Side;
+use crate::square::Square;
+
+const PIECES: [PieceType; 5] = [
+    PieceType::WhitePawn,
+    PieceType::WhiteKnight,
+    PieceType::WhiteBishop,
+    PieceType::WhiteRook,
+    PieceType::WhiteQueen,
+];
+
+/// Pieces that take part in mobility and king attacks
+const ACTIVE_PIECES: [PieceType; 4] = [
+    PieceType::WhiteKnight,
+    PieceType::WhiteBishop,
+    PieceType::WhiteRook,
+    PieceType::WhiteQueen,
+];
+
+/// Static evaluation of chess positions
+pub trait Evaluator {
+    /// Evaluate the board in centipawns from the point of view of the side to move
+    fn evaluate(&mut self, board: &Board) -> i32;
+
+    /// Called by the search when moving from `board` to `child` by playing `mov`
+    fn push(&mut self, _board: &Board, _mov: &Move, _child: &Board) {}
+
+    /// Called by the search when going back to the parent position
+    fn pop(&mut self) {}
+
+    /// Copy of the evaluator for another search thread
+    fn boxed_clone(&self) -> Box<dyn Evaluator + Send>;
+}
+
+/// Hand-crafted evaluation: material, pawn structure, king safety and mobility
+#[derive(Clone, Debug, Default)]
+pub struct ClassicalEvaluator {
+    move_generator: MoveGenerator,
+    pawn_table: PawnHashTable,
+    params: EvalParams,
+}
+
+impl ClassicalEvaluator {
+    pub fn new() -> ClassicalEvaluator {
+        ClassicalEvaluator::with_params(EvalParams::default())
+    }
+
+    pub fn with_params(params: EvalParams) -> ClassicalEvaluator {
+        ClassicalEvaluator {
+            move_generator: MoveGenerator::new(),
+            pawn_table: PawnHashTable::default(),
+            params,
+        }
+    }
+
+    pub fn get_params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: EvalParams) {
+        if !self.params.same_pawn_weights(&params) {
+            self.pawn_table.clear();
+        }
+        self.params = params;
+    }
+
+    fn material(&self, board: &Board) -> i32 {
+        PIECES
+            .iter()
+            .zip(self.params.piece_values)
+            .map(|(piece_type, value)| {
+                value
+                    * (board.occupied(Scope::from(*piece_type)).count_ones() as i32
+                        - board.occupied(Scope::from(!*piece_type)).count_ones() as i32)
+            })
+            .sum()
+    }
+
+    fn pawn_structure(&mut self, board: &Board) -> i32 {
+        let entry = self.pawn_table.probe(board, &self.params);
+        entry.get_score() + entry.king_proximity(board, &self.params)
+    }
+
+    fn king_zone(&self, board: &Board, side: Side) -> Bitboard {
+        board.king_square(side).map_or(0, |king| {
+            self.move_generator
+                .king_attacks(PieceType::NoPiece, king, !0)
+                .mov
+        })
+    }
+
+    /// Mobility of the pieces of `side` and the attack units they direct at the enemy king zone
+    fn activity(&self, board: &Board, side: Side) -> (i32, i32) {
+        let mut side_board = board.clone();
+        side_board.set_turn(side);
+
+        let safe = !self.move_generator.pawn_attacks(board, !side);
+        let king_zone = self.king_zone(board, !side);
+
+        let mut mobility = 0;
+        let mut attackers = 0;
+        let mut attack_units = 0;
+        for (index, &piece_type) in ACTIVE_PIECES.iter().enumerate() {
+            let weight = self.params.mobility[index];
+            let expected = self.params.mobility_offset[index];
+            let attack_weight = self.params.king_attack_weights[index];
+            let piece_type = match side {
+                Side::White => piece_type,
+                Side::Black => !piece_type,
+            };
+            for index in squares(board.occupied(Scope::from(piece_type))) {
+                let targets = self
+                    .move_generator
+                    .attack(
+                        &side_board,
+                        &Piece::new(Square::from_index(index), piece_type),
+                    )
+                    .mov;
+
+                mobility += weight * ((targets & safe).count_ones() as i32 - expected);
+                if targets & king_zone != 0 {
+                    attackers += 1;
+                    attack_units += attack_weight * (targets & king_zone).count_ones() as i32;
+                }
+            }
+        }
+
+        (
+            mobility,
+            attack_units * self.params.king_attackers_scale[attackers.min(7)] / 100,
+        )
+    }
+
+    /// Pawn shield in front of the king of `side` and open files next to it
+    fn king_shelter(&self, board: &Board, side: Side) -> i32 {
+        let Some(king) = board.king_square(side) else {
+            return 0;
+        };
+        let (own, enemy) = match side {
+            Side::White => (Scope::WhitePawn, Scope::BlackPawn),
+            Side::Black => (Scope::BlackPawn, Scope::WhitePawn),
+        };
+        let own = board.occupied(own);
+        let enemy = board.occupied(enemy);
+
+        let mut score = 0;
+        for file in king.get_file().saturating_sub(1)..=(king.get_file() + 1).min(7) {
+            let own_file = own & file_mask(file);
+            if own_file & relative_rank_mask(side, king.get_rank(), 1) != 0 {
+                score += self.params.king_shield_close;
+            } else if own_file & relative_rank_mask(side, king.get_rank(), 2) != 0 {
+                score += self.params.king_shield_far;
+            }
+
+            if own_file == 0 && enemy & file_mask(file) == 0 {
+                score += self.params.king_open_file;
+            } else if own_file == 0 {
+                score += self.params.king_semi_open_file;
+            }
+        }
+
+        score
+    }
+
+    /// Material `side` can use to attack the enemy king
+    fn attacking_phase(&self, board: &Board, side: Side) -> i32 {
+        ACTIVE_PIECES
+            .iter()
+            .zip(self.params.king_attack_phase)
+            .map(|(piece_type, weight)| {
+                let piece_type = match side {
+                    Side::White => *piece_type,
+                    Side::Black => !*piece_type,
+                };
+                weight * board.occupied(Scope::from(piece_type)).count_ones() as i32
+            })
+            .sum::<i32>()
+            .min(self.params.king_safety_full_phase)
+    }
+
+    /// Mobility and king safety from white's point of view
+    fn king_safety_and_mobility(&self, board: &Board) -> i32 {
+        let (white_mobility, white_attack) = self.activity(board, Side::White);
+        let (black_mobility, black_attack) = self.activity(board, Side::Black);
+
+        let full_phase = self.params.king_safety_full_phase.max(1);
+        let white_safety = (self.king_shelter(board, Side::White) - black_attack)
+            * self.attacking_phase(board, Side::Black)
+            / full_phase;
+        let black_safety = (self.king_shelter(board, Side::Black) - white_attack)
+            * self.attacking_phase(board, Side::White)
+            / full_phase;
+
+        white_mobility - black_mobility + white_safety - black_safety
+    }
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        let score = self.material(board)
+            + self.pawn_structure(board)
+            + self.king_safety_and_mobility(board);
+
+        match board.get_turn() {
+            Side::White => score,
+            Side::Black => -score,
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Evaluator + Send> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_starting_position_is_balanced() {
+        let mut evaluator = ClassicalEvaluator::new();
+        assert_eq!(evaluator.evaluate(&Board::from_basic_board()), 0);
+    }
+
+    #[rstest]
+    #[case(
+        "4k3/8/8/3p4/8/8/PPP5/4K3 w - - 0 1",
+        "4k3/ppp5/8/8/3P4/8/8/4K3 b - - 0 1"
+    )]
+    #[case(
+        "r3k3/1pp5/8/8/4P3/8/2P5/3QK3 w - - 0 1",
+        "3qk3/2p5/8/4p3/8/8/1PP5/R3K3 b - - 0 1"
+    )]
+    fn test_evaluation_is_symmetric(#[case] fen: &str, #[case] mirrored_fen: &str) {
+        let mut evaluator = ClassicalEvaluator::new();
+        assert_eq!(
+            evaluator.evaluate(&Board::from_fen(fen)),
+            evaluator.evaluate(&Board::from_fen(mirrored_fen))
+        );
+    }
+
+    #[test]
+    fn test_king_shelter() {
+        let sheltered = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let exposed = Board::from_fen("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1");
+
+        let evaluator = ClassicalEvaluator::new();
+        let params = EvalParams::default();
+
+        assert_eq!(
+            evaluator.king_shelter(&sheltered, Side::White),
+            3 * params.king_shield_close
+        );
+        assert_eq!(
+            evaluator.king_shelter(&exposed, Side::White),
+            3 * params.king_semi_open_file
+        );
+    }
+
+    #[test]
+    fn test_king_attackers() {
+        let evaluator = ClassicalEvaluator::new();
+        let attacked = Board::from_fen("6k1/5ppp/8/6N1/8/3Q4/8/6K1 w - - 0 1");
+        let quiet = Board::from_fen("6k1/5ppp/8/8/8/8/N2Q4/6K1 w - - 0 1");
+
+        assert!(evaluator.activity(&attacked, Side::White).1 > 0);
+        assert_eq!(evaluator.activity(&quiet, Side::White).1, 0);
+    }
+
+    #[test]
+    fn test_mobility_excludes_pawn_attacks() {
+        let evaluator = ClassicalEvaluator::new();
+        let free = Board::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let harassed = Board::from_fen("4k3/3p4/8/8/3N4/8/8/4K3 w - - 0 1");
+
+        assert_eq!(evaluator.activity(&free, Side::White).0, 4 * (8 - 4));
+        assert_eq!(evaluator.activity(&harassed, Side::White).0, 4 * (6 - 4));
+    }
+
+    #[test]
+    fn test_set_params_invalidates_pawn_table() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/P7/4K3 w - - 0 1");
+        let mut evaluator = ClassicalEvaluator::new();
+        let before = evaluator.evaluate(&board);
+
+        let mut params = EvalParams::default();
+        params.isolated_pawn -= 20;
+        evaluator.set_params(params);
+
+        assert_eq!(evaluator.evaluate(&board), before - 20);
+    }
+
+    #[test]
+    fn test_extra_pawn_is_good() {
+        let mut evaluator = ClassicalEvaluator::new();
+        let board = Board::from_fen("4k3/8/8/8/8/8/PP6/4K3 w - - 0 1");
+
+        assert!(evaluator.evaluate(&board) > 0);
+    }
+}
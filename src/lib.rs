@@ -6,13 +6,19 @@ extern crate num;
 extern crate num_derive;
 
 pub use crate::board::Board;
-pub use crate::book::Book;
+pub use crate::book::{Book, BookBuilder, BookMove, BookPolicy};
+pub use crate::evaluator::{ClassicalEvaluator, Evaluator};
 pub use crate::move_generator::MoveGenerator;
 pub use crate::moves::Move;
 pub use crate::moveset::MoveSet;
+pub use crate::nnue::{Network, NnueEvaluator};
+pub use crate::params::EvalParams;
+pub use crate::piece::PieceType;
 pub use crate::side::Side;
-pub use crate::solver::Solver;
+pub use crate::solver::{Score, SearchInfo, SearchLimits, Solver};
 pub use crate::square::Square;
+pub use crate::tablebase::{Material, Outcome, Table, Tablebases};
+pub use crate::tuner::{load_dataset, Tuner, TuningPosition};
 
 mod bitboard;
 mod bitwise;
@@ -20,13 +26,30 @@ mod board;
 mod book;
 mod common;
 mod dumb7fill;
+pub mod engine;
+pub mod epd;
+mod evaluator;
 mod move_generator;
 mod moves;
 mod moveset;
+mod nnue;
+pub mod packed;
+mod params;
+mod pawns;
+pub mod pgn;
 mod piece;
+#[cfg(feature = "serde")]
+mod serialize;
 mod side;
 mod solver;
 mod square;
+pub mod svg;
+mod tablebase;
+pub mod tournament;
+mod transposition;
+mod tuner;
+pub mod uci;
+pub mod xboard;
 
 #[cfg(test)]
 mod tests {
@@ -313,6 +336,10 @@ mod tests {
     //   └───┴───┴───┴───┴───┴───┴───┴───┘
     //     a   b   c   d   e   f   g   h
     #[case("8/8/8/nn4nn/P6P/8/8/8", ("a4", PieceType::WhitePawn), vec!["b5"])]
+    #[case("8/8/8/8/8/4n3/4P3/8", ("e2", PieceType::WhitePawn), vec![])]
+    #[case("8/8/8/8/4N3/8/4P3/8", ("e2", PieceType::WhitePawn), vec!["e3"])]
+    #[case("8/4p3/4N3/8/8/8/8/8 b", ("e7", PieceType::BlackPawn), vec![])]
+    #[case("8/4p3/8/4N3/8/8/8/8 b", ("e7", PieceType::BlackPawn), vec!["e6"])]
     fn test_generate_move_for_piece(
         #[case] initial_fen: &str,
         #[case] source_piece: (&str, PieceType),
@@ -407,6 +434,11 @@ mod tests {
         "rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQ - 1 1"
     )]
     #[case("8/3P4/8/8/8/8/3p4/8", "d7d8Q", "3Q4/8/8/8/8/8/3p4/8 b")]
+    #[case(
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "d7d5",
+        "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2"
+    )]
     fn test_apply_move(
         #[case] initial_fen: &str,
         #[case] algebraic_move: &str,
@@ -1,12 +1,13 @@
 use std::convert::TryInto;
 
-use crate::bitboard::{Bitboard, BitboardExt};
+use crate::bitboard::{squares, Bitboard, BitboardExt};
 use crate::board::Board;
 use crate::common::*;
 use crate::dumb7fill::dumb7fill;
-use crate::moves::Scope;
+use crate::moves::{Move, Scope};
 use crate::moveset::MoveSet;
 use crate::piece::{Piece, PieceType};
+use crate::side::Side;
 use crate::square::Square;
 
 pub fn generate_knight_moves() -> Vec<u64> {
@@ -185,6 +186,113 @@ impl MoveGenerator {
         MoveSet::new(mov.src, mov.piece, m)
     }
 
+    /// Squares attacked by the pawns of `side`
+    pub fn pawn_attacks(&self, board: &Board, side: Side) -> Bitboard {
+        let (pawns, attacks) = match side {
+            Side::White => (Scope::WhitePawn, &self.white_pawn_attacks),
+            Side::Black => (Scope::BlackPawn, &self.black_pawn_attacks),
+        };
+
+        squares(board.occupied(pawns)).fold(0, |acc, index| acc | attacks[index as usize])
+    }
+
+    /// Whether `square` is attacked by a piece of `side`
+    pub fn is_attacked(&self, board: &Board, square: Square, side: Side) -> bool {
+        let pieces = |piece_type: PieceType| {
+            board.occupied(Scope::from(match side {
+                Side::White => piece_type,
+                Side::Black => !piece_type,
+            }))
+        };
+        let free = !board.occupied(Scope::All);
+        let index = square.get_index() as usize;
+
+        // attacks from the target square with the movement of each piece kind
+        let pawn_attacks = match side {
+            Side::White => self.black_pawn_attacks[index],
+            Side::Black => self.white_pawn_attacks[index],
+        };
+        let queens = pieces(PieceType::WhiteQueen);
+
+        pawn_attacks & pieces(PieceType::WhitePawn) != 0
+            || self.knight_moves[index] & pieces(PieceType::WhiteKnight) != 0
+            || self.king_attacks(PieceType::NoPiece, square, !0).mov & pieces(PieceType::WhiteKing)
+                != 0
+            || self.rook_attacks(PieceType::NoPiece, square, free).mov
+                & (pieces(PieceType::WhiteRook) | queens)
+                != 0
+            || self.bishop_attacks(PieceType::NoPiece, square, free).mov
+                & (pieces(PieceType::WhiteBishop) | queens)
+                != 0
+    }
+
+    /// Whether the king of `side` is attacked
+    pub fn in_check(&self, board: &Board, side: Side) -> bool {
+        board
+            .king_square(side)
+            .is_some_and(|king| self.is_attacked(board, king, !side))
+    }
+
+    /// Castling moves of the side to move: the king and rook are in place, the squares
+    /// between them are empty and the king does not castle out of, through or into check
+    fn castling_moves(&self, board: &Board) -> Vec<Move> {
+        let side = board.get_turn();
+        let (rank, king, rook, short, long) = match side {
+            Side::White => (
+                0,
+                PieceType::WhiteKing,
+                PieceType::WhiteRook,
+                board.get_castling_white_short(),
+                board.get_castling_white_long(),
+            ),
+            Side::Black => (
+                7,
+                PieceType::BlackKing,
+                PieceType::BlackRook,
+                board.get_castling_black_short(),
+                board.get_castling_black_long(),
+            ),
+        };
+        let square = |file: u8| Square::from_rank_file(rank, file);
+        if board.piece_at(square(4)) != Some(king) {
+            return vec![];
+        }
+
+        let mut moves = vec![];
+        for (enabled, rook_file, empty, safe, destination) in [
+            (short, 7, &[5, 6][..], &[4, 5, 6][..], 6),
+            (long, 0, &[1, 2, 3][..], &[4, 3, 2][..], 2),
+        ] {
+            if enabled
+                && board.piece_at(square(rook_file)) == Some(rook)
+                && empty
+                    .iter()
+                    .all(|file| board.piece_at(square(*file)) == Some(PieceType::NoPiece))
+                && safe
+                    .iter()
+                    .all(|file| !self.is_attacked(board, square(*file), !side))
+            {
+                moves.push(Move::new(square(4), square(destination)));
+            }
+        }
+        moves
+    }
+
+    /// Legal moves of the side to move, with promotions and castling
+    pub fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        let side = board.get_turn();
+        self.generate_moves(board)
+            .iter()
+            .flat_map(|moveset| moveset.into_iter())
+            .filter(|mov| {
+                board
+                    .apply(mov.clone())
+                    .is_some_and(|child| !self.in_check(&child, side))
+            })
+            .chain(self.castling_moves(board))
+            .collect()
+    }
+
     pub fn black_pawn_attacks(
         &self,
         piece: PieceType,
@@ -193,12 +301,15 @@ impl MoveGenerator {
         enemy: u64,
         enpassant: Option<Square>,
     ) -> MoveSet {
+        let blocked = friendlies | enemy;
+        let mut mov = self.black_pawn_moves[from.get_index() as usize] & !blocked;
+        if (1 << from.get_index()) >> 8 & blocked != 0 {
+            mov = 0;
+        }
         let mut enemy = enemy;
         if let Some(enpassant) = enpassant {
             enemy |= 1 << enpassant.get_index()
         }
-        let mov = self.black_pawn_moves[from.get_index() as usize];
-        let mov = mov & !friendlies;
         let attack = self.black_pawn_attacks[from.get_index() as usize];
         let attacks = attack & enemy;
         MoveSet::new(from, piece, mov | attacks)
@@ -212,12 +323,15 @@ impl MoveGenerator {
         enemy: u64,
         enpassant: Option<Square>,
     ) -> MoveSet {
+        let blocked = friendlies | enemy;
+        let mut mov = self.white_pawn_moves[from.get_index() as usize] & !blocked;
+        if (1 << from.get_index()) << 8 & blocked != 0 {
+            mov = 0;
+        }
         let mut enemy = enemy;
         if let Some(enpassant) = enpassant {
             enemy |= 1 << enpassant.get_index();
         }
-        let mov = self.white_pawn_moves[from.get_index() as usize];
-        let mov = mov & !friendlies & !enemy;
         let attack = self.white_pawn_attacks[from.get_index() as usize];
         let attacks = attack & enemy;
         MoveSet::new(from, piece, mov | attacks)
@@ -269,3 +383,64 @@ impl MoveGenerator {
         MoveSet::new(from, piece, flood)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn perft(move_generator: &MoveGenerator, board: &Board, depth: u8) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        move_generator
+            .legal_moves(board)
+            .into_iter()
+            .map(|mov| perft(move_generator, &board.apply(mov).unwrap(), depth - 1))
+            .sum()
+    }
+
+    #[rstest]
+    #[case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 3, 8902)]
+    #[case(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        2,
+        2039
+    )]
+    #[case("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2812)]
+    #[case("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 2, 496)]
+    fn test_perft(#[case] fen: &str, #[case] depth: u8, #[case] nodes: usize) {
+        let move_generator = MoveGenerator::new();
+        assert_eq!(perft(&move_generator, &Board::from_fen(fen), depth), nodes);
+    }
+
+    #[rstest]
+    #[case("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", vec!["e1g1", "e1c1"])]
+    #[case("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1", vec!["e1c1"])]
+    #[case("4k3/8/8/8/8/8/3r4/R3K2R w KQ - 0 1", vec!["e1g1"])]
+    #[case("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1", vec![])]
+    #[case("4k3/8/8/8/8/8/8/RN2K1NR w KQ - 0 1", vec![])]
+    #[case("4k3/8/8/8/8/8/8/R3K2R w - - 0 1", vec![])]
+    #[case("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", vec!["e8g8", "e8c8"])]
+    fn test_castling_moves(#[case] fen: &str, #[case] expected: Vec<&str>) {
+        let move_generator = MoveGenerator::new();
+        let moves: Vec<String> = move_generator
+            .castling_moves(&Board::from_fen(fen))
+            .iter()
+            .map(|mov| mov.to_algebraic())
+            .collect();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn test_pinned_piece_cannot_move() {
+        let move_generator = MoveGenerator::new();
+        let board = Board::from_fen("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1");
+
+        assert!(move_generator
+            .legal_moves(&board)
+            .iter()
+            .all(|mov| mov.get_src() != Square::from_algebraic("e2").unwrap()));
+    }
+}
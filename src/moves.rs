@@ -1,6 +1,7 @@
-use regex::{Match, Regex};
+use regex::Regex;
 use std::fmt;
 use std::ops::Not;
+use std::sync::OnceLock;
 
 use crate::board::print_board;
 use crate::board::Board;
@@ -178,6 +179,11 @@ impl Move {
 
     pub fn from_full_algebraic(algebra: &str) -> Option<Move> {
         let mov: Vec<char> = algebra.chars().collect();
+        let is_square =
+            |file: char, rank: char| ('a'..='h').contains(&file) && ('1'..='8').contains(&rank);
+        if mov.len() < 4 || !is_square(mov[0], mov[1]) || !is_square(mov[2], mov[3]) {
+            return None;
+        }
         if mov.len() == 4 {
             let src_rank = (mov[1] as u8) - b'1';
             let src_file = (mov[0] as u8) - b'a';
@@ -206,127 +212,95 @@ impl Move {
         }
     }
 
-    fn san_match_type(piece_type: PieceType, scope: Scope) -> bool {
-        matches!(
-            (piece_type, scope),
-            (_, Scope::All)
-                | (PieceType::WhitePawn, Scope::WhitePawn)
-                | (PieceType::BlackPawn, Scope::BlackPawn)
-                | (PieceType::WhiteRook, Scope::WhiteRook)
-                | (PieceType::BlackRook, Scope::BlackRook)
-                | (PieceType::WhiteKnight, Scope::WhiteKnight)
-                | (PieceType::BlackKnight, Scope::BlackKnight)
-                | (PieceType::WhiteBishop, Scope::WhiteBishop)
-                | (PieceType::BlackBishop, Scope::BlackBishop)
-                | (PieceType::WhiteQueen, Scope::WhiteQueen)
-                | (PieceType::BlackQueen, Scope::BlackQueen)
-                | (PieceType::WhiteKing, Scope::WhiteKing)
-                | (PieceType::BlackKing, Scope::BlackKing)
-        )
-    }
-
-    fn from_san_queen_side_castle(board: &Board) -> Option<Move> {
+    fn from_san_queen_side_castle(board: &Board) -> Move {
         if board.get_turn() == Side::White {
-            Some(Move::new(
+            Move::new(
                 Square::from_algebraic("e1").unwrap(),
                 Square::from_algebraic("c1").unwrap(),
-            ))
+            )
         } else {
-            Some(Move::new(
-                Square::from_algebraic("e7").unwrap(),
-                Square::from_algebraic("c7").unwrap(),
-            ))
+            Move::new(
+                Square::from_algebraic("e8").unwrap(),
+                Square::from_algebraic("c8").unwrap(),
+            )
         }
     }
 
-    fn from_san_king_side_castle(board: &Board) -> Option<Move> {
+    fn from_san_king_side_castle(board: &Board) -> Move {
         if board.get_turn() == Side::White {
-            Some(Move::new(
+            Move::new(
                 Square::from_algebraic("e1").unwrap(),
                 Square::from_algebraic("g1").unwrap(),
-            ))
+            )
         } else {
-            Some(Move::new(
-                Square::from_algebraic("e7").unwrap(),
-                Square::from_algebraic("g7").unwrap(),
-            ))
+            Move::new(
+                Square::from_algebraic("e8").unwrap(),
+                Square::from_algebraic("g8").unwrap(),
+            )
         }
     }
 
+    /// Legal move written in standard algebraic notation, ignoring check marks and annotation
+    /// symbols. `None` when the text is not SAN or matches no legal move or more than one.
     pub fn from_san(algebra: &str, board: &Board) -> Option<Move> {
-        if algebra == "O-O-O" {
-            return Move::from_san_queen_side_castle(board);
-        } else if algebra == "O-O" {
-            return Move::from_san_king_side_castle(board);
+        static SAN: OnceLock<Regex> = OnceLock::new();
+
+        let algebra = algebra.trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = MoveGenerator::new().legal_moves(board);
+        let is_king = |mov: &Move| {
+            matches!(
+                board.piece_at(mov.src),
+                Some(PieceType::WhiteKing) | Some(PieceType::BlackKing)
+            )
+        };
+        let castle = match algebra {
+            "O-O-O" | "0-0-0" => Some(Move::from_san_queen_side_castle(board)),
+            "O-O" | "0-0" => Some(Move::from_san_king_side_castle(board)),
+            _ => None,
+        };
+        if let Some(castle) = castle {
+            return legal_moves
+                .into_iter()
+                .find(|mov| mov.src == castle.src && mov.dst == castle.dst && is_king(mov));
         }
 
-        fn set_empty_string_to_none(m: Match) -> Option<Match> {
-            if m.as_str() == "" {
-                None
-            } else {
-                Some(m)
-            }
-        }
+        let re = SAN.get_or_init(|| {
+            Regex::new(r"^([BNRQK]?)([a-h]?)([1-8]?)x?([a-h])([1-8])(?:=?([BNRQ]))?$").unwrap()
+        });
+        let captures = re.captures(algebra)?;
 
-        let handle_piece_type = |m: Match| -> PieceType {
-            let piece_type = PieceType::from_string(&m.as_str().chars().next().unwrap()).unwrap();
+        let letter = |index: usize| captures.get(index).and_then(|m| m.as_str().chars().next());
+        let piece_type = |letter: char| {
+            let piece_type = PieceType::from_string(&letter).unwrap();
             if board.get_turn() == Side::Black {
                 !piece_type
             } else {
                 piece_type
             }
         };
-
-        let handle_rank = |rank: Match| rank.as_str().chars().next().unwrap() as u8 - b'a';
-        let handle_file = |file: Match| file.as_str().chars().next().unwrap() as u8 - b'1';
-
-        let re = Regex::new(r"([BNRQK]?)([a-h]?)([1-8]?)x?([a-h])([1-8])=?([BNRQK]?)").unwrap();
-        let captures = re.captures(algebra).unwrap();
-        let scope = captures
-            .get(1)
-            .and_then(set_empty_string_to_none)
-            .map(handle_piece_type)
-            .map(Scope::from)
-            .unwrap_or_else(|| Scope::All);
-        let src_file = captures
-            .get(2)
-            .and_then(set_empty_string_to_none)
-            .map(handle_rank);
-        let src_rank = captures
-            .get(3)
-            .and_then(set_empty_string_to_none)
-            .map(handle_file);
-        let dst_rank = captures
-            .get(4)
-            .and_then(set_empty_string_to_none)
-            .map(handle_rank);
-        let dst_file = captures
-            .get(5)
-            .and_then(set_empty_string_to_none)
-            .map(handle_file);
-        let promotion = captures
-            .get(6)
-            .and_then(set_empty_string_to_none)
-            .map(handle_piece_type);
-
-        let dst = Square::from_rank_file(dst_file.unwrap(), dst_rank.unwrap());
-        let move_generator = MoveGenerator::new();
-        let mut resulting_move: Option<Move> = None;
-        let moves = move_generator.generate_moves(board);
-        for moveset in moves {
-            for mov in moveset.into_iter() {
-                let piece_type = board.piece_at(mov.get_src()).unwrap();
-                if (src_rank.is_none() || Some(mov.get_src().get_rank()) == src_rank)
-                    && (src_file.is_none() || Some(mov.get_src().get_file()) == src_file)
-                    && mov.get_dst() == dst
-                    && Move::san_match_type(piece_type, scope)
-                {
-                    resulting_move = Some(mov);
-                }
-            }
+        let piece = piece_type(letter(1).unwrap_or('P'));
+        let src_file = letter(2).map(|file| file as u8 - b'a');
+        let src_rank = letter(3).map(|rank| rank as u8 - b'1');
+        let dst = Square::from_rank_file(
+            letter(5).unwrap() as u8 - b'1',
+            letter(4).unwrap() as u8 - b'a',
+        );
+        let promotion = letter(6).map(piece_type);
+
+        let mut candidates = legal_moves.into_iter().filter(|mov| {
+            mov.dst == dst
+                && board.piece_at(mov.src) == Some(piece)
+                && src_file.is_none_or(|file| mov.src.get_file() == file)
+                && src_rank.is_none_or(|rank| mov.src.get_rank() == rank)
+                && mov
+                    .promotion
+                    .map(|piece| piece.to_char().to_ascii_uppercase())
+                    == promotion.map(|piece| piece.to_char().to_ascii_uppercase())
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(mov), None) => Some(mov),
+            _ => None,
         }
-        resulting_move.as_mut().unwrap().set_promotion(promotion);
-        resulting_move
     }
 
     pub fn from_algebraic(algebra: &str) -> Option<Move> {
@@ -359,24 +333,165 @@ impl Move {
         let src_rank = (self.src.get_file() + b'a') as char;
         let src_file = (self.src.get_rank() + b'1') as char;
 
-        let promotion = if self.promotion.is_some() {
-            self.promotion.unwrap().to_char().to_string().to_uppercase()
+        let promotion = if let Some(promotion) = self.promotion {
+            promotion.to_char().to_string().to_uppercase()
         } else {
             "".to_string()
         };
-        format!(
-            "{src_rank}{src_file}{dst_rank}{dst_file}{promotion}")
+        format!("{src_rank}{src_file}{dst_rank}{dst_file}{promotion}")
+    }
+
+    /// Standard algebraic notation of a legal move on `board`, ending in `+` or `#` when it
+    /// gives check
+    pub fn to_san(&self, board: &Board) -> String {
+        let move_generator = MoveGenerator::new();
+        let piece = match board.piece_at(self.src) {
+            Some(piece) if piece.is_black() => !piece,
+            Some(piece) => piece,
+            None => PieceType::NoPiece,
+        };
+        let capture = board.piece_at(self.dst) != Some(PieceType::NoPiece);
+        let file = |square: Square| ((square.get_file() + b'a') as char).to_string();
+
+        let mut san = match piece {
+            PieceType::WhiteKing if self.src.get_file().abs_diff(self.dst.get_file()) == 2 => {
+                if self.dst.get_file() > self.src.get_file() {
+                    "O-O".to_string()
+                } else {
+                    "O-O-O".to_string()
+                }
+            }
+            PieceType::WhitePawn => {
+                let mut san = String::new();
+                if self.src.get_file() != self.dst.get_file() {
+                    san += &file(self.src);
+                    san += "x";
+                }
+                san += &self.dst.to_algebraic();
+                if let Some(promotion) = self.promotion {
+                    san += "=";
+                    san.push(promotion.to_char().to_ascii_uppercase());
+                }
+                san
+            }
+            _ => {
+                let rivals: Vec<Move> = move_generator
+                    .legal_moves(board)
+                    .into_iter()
+                    .filter(|mov| {
+                        mov.dst == self.dst
+                            && mov.src != self.src
+                            && board.piece_at(mov.src) == board.piece_at(self.src)
+                    })
+                    .collect();
+
+                let mut san = piece.to_char().to_string();
+                if !rivals.is_empty() {
+                    let same_file = rivals
+                        .iter()
+                        .any(|mov| mov.src.get_file() == self.src.get_file());
+                    let same_rank = rivals
+                        .iter()
+                        .any(|mov| mov.src.get_rank() == self.src.get_rank());
+                    if !same_file {
+                        san += &file(self.src);
+                    } else if !same_rank {
+                        san.push((self.src.get_rank() + b'1') as char);
+                    } else {
+                        san += &self.src.to_algebraic();
+                    }
+                }
+                if capture {
+                    san += "x";
+                }
+                san += &self.dst.to_algebraic();
+                san
+            }
+        };
+
+        if let Some(next) = board.apply(self.clone()) {
+            if move_generator.in_check(&next, next.get_turn()) {
+                san.push(if move_generator.legal_moves(&next).is_empty() {
+                    '#'
+                } else {
+                    '+'
+                });
+            }
+        }
+        san
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use rstest::rstest;
+
     use crate::square::Square;
 
     use super::Board;
     use super::Move;
     use super::MoveGenerator;
 
+    #[rstest]
+    #[case(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "e2e4",
+        "e4"
+    )]
+    #[case(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "g1f3",
+        "Nf3"
+    )]
+    #[case("4k3/8/8/3p4/4P3/5N2/8/4K3 w - - 0 1", "e4d5", "exd5")]
+    #[case("4k3/8/8/3p4/4P3/5N2/8/4K3 w - - 0 1", "f3d4", "Nd4")]
+    #[case("4k3/8/8/3p4/8/2N1N3/8/4K3 w - - 0 1", "c3d5", "Ncxd5")]
+    #[case("4k3/8/8/8/8/R7/8/R3K3 w - - 0 1", "a1a2", "R1a2")]
+    #[case("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1d1", "Rad1")]
+    #[case("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1", "O-O")]
+    #[case("r3k3/8/8/8/8/8/8/4K3 b q - 0 1", "e8c8", "O-O-O")]
+    #[case("8/P7/8/8/8/8/8/k3K3 w - - 0 1", "a7a8q", "a8=Q+")]
+    #[case("8/P7/8/8/8/8/8/k3K3 w - - 0 1", "a7a8n", "a8=N")]
+    #[case("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", "Ra8#")]
+    fn test_to_san(#[case] fen: &str, #[case] mov: &str, #[case] san: &str) {
+        let board = Board::from_fen(fen);
+        let mov = MoveGenerator::new()
+            .legal_moves(&board)
+            .into_iter()
+            .find(|legal| legal.to_algebraic().eq_ignore_ascii_case(mov))
+            .unwrap();
+        assert_eq!(mov.to_san(&board), san);
+        assert_eq!(Move::from_san(san, &board), Some(mov));
+    }
+
+    #[rstest]
+    #[case("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", "O-O", "e8g8")]
+    #[case("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", "0-0-0+", "e8c8")]
+    #[case(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "Nf3!?",
+        "g1f3"
+    )]
+    #[case("4k3/8/8/8/8/2N5/3P4/4K3 w - - 0 1", "d4", "d2d4")]
+    #[case("4k3/8/8/8/8/2N5/3P4/4K3 w - - 0 1", "Nd1", "c3d1")]
+    #[case("8/P7/8/8/8/8/8/k3K3 w - - 0 1", "a8Q", "a7a8Q")]
+    fn test_from_san(#[case] fen: &str, #[case] san: &str, #[case] expected: &str) {
+        let mov = Move::from_san(san, &Board::from_fen(fen)).unwrap();
+        assert_eq!(mov.to_algebraic(), expected);
+    }
+
+    #[rstest]
+    #[case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e5")]
+    #[case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "O-O")]
+    #[case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "hello")]
+    #[case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "")]
+    #[case("4k3/8/8/3p4/8/2N1N3/8/4K3 w - - 0 1", "Nxd5")]
+    #[case("8/P7/8/8/8/8/8/k3K3 w - - 0 1", "a8")]
+    #[case("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1", "Kd2")]
+    fn test_from_san_rejects(#[case] fen: &str, #[case] san: &str) {
+        assert_eq!(Move::from_san(san, &Board::from_fen(fen)), None);
+    }
+
     #[test]
     fn test_king_move() {
         let move_generator = MoveGenerator::new();
@@ -0,0 +1,478 @@
+/*!
+Efficiently updatable neural network evaluation.
+
+The network has 768 inputs (6 piece kinds × 2 colours × 64 squares, relative to each side),
+one hidden layer of `H` neurons computed separately for both sides and a single output.
+The hidden layers are concatenated side to move first, clipped to `[0, 255]` and fed to the
+output neuron. All arithmetic is done with integers.
+
+Weight file format, all values little-endian:
+
+| field           | type  | count     | notes                                        |
+|-----------------|-------|-----------|----------------------------------------------|
+| magic           | bytes | 4         | `FNUE`                                       |
+| version         | u32   | 1         | `1`                                          |
+| hidden size `H` | u32   | 1         |                                              |
+| feature weights | i16   | 768 × `H` | `H` weights for each input feature in turn   |
+| feature biases  | i16   | `H`       |                                              |
+| output weights  | i16   | 2 × `H`   | side to move neurons first, then the other's |
+| output bias     | i32   | 1         |                                              |
+
+Input feature `(colour × 6 + kind) × 64 + square` has colour 0 for the pieces of the side the
+hidden layer belongs to, the kinds ordered pawn, rook, knight, bishop, queen, king, and the
+squares numbered a1 = 0 to h8 = 63, flipped vertically for black.
+
+The output is scaled to centipawns by `output × 400 / (255 × 64)`.
+*/
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+
+use crate::bitboard::squares;
+use crate::board::Board;
+use crate::evaluator::Evaluator;
+use crate::moves::{Move, Scope};
+use crate::piece::PieceType;
+use crate::side::Side;
+use crate::square::Square;
+
+const NNUE_MAGIC: &[u8; 4] = b"FNUE";
+const NNUE_VERSION: u32 = 1;
+const INPUTS: usize = 768;
+/// Activations are clipped to `[0, QA]`
+const QA: i32 = 255;
+/// Quantization of the output weights
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid_data("truncated network file"))?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i16s(&mut self, count: usize) -> io::Result<Vec<i16>> {
+        Ok(self
+            .take(
+                count
+                    .checked_mul(2)
+                    .ok_or_else(|| invalid_data("overflow"))?,
+            )?
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|chunk| i16::from_le_bytes(*chunk))
+            .collect())
+    }
+}
+
+/// Weights of an efficiently updatable neural network
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Network {
+    pub fn from_filename(filename: &str) -> io::Result<Network> {
+        Network::from_bytes(&fs::read(filename)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Network> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(4)? != NNUE_MAGIC {
+            return Err(invalid_data("not a network file"));
+        }
+        if reader.u32()? != NNUE_VERSION {
+            return Err(invalid_data("unsupported network version"));
+        }
+        let hidden = reader.u32()? as usize;
+        if hidden == 0 {
+            return Err(invalid_data("empty hidden layer"));
+        }
+
+        let network = Network {
+            hidden,
+            feature_weights: reader.i16s(INPUTS * hidden)?,
+            feature_biases: reader.i16s(hidden)?,
+            output_weights: reader.i16s(2 * hidden)?,
+            output_bias: reader.i32()?,
+        };
+
+        if reader.position != bytes.len() {
+            return Err(invalid_data("trailing bytes after network"));
+        }
+        Ok(network)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = NNUE_MAGIC.to_vec();
+        bytes.extend(NNUE_VERSION.to_le_bytes());
+        bytes.extend((self.hidden as u32).to_le_bytes());
+        for value in self
+            .feature_weights
+            .iter()
+            .chain(self.feature_biases.iter())
+            .chain(self.output_weights.iter())
+        {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// Network with small pseudo-random weights, deterministic for a given seed
+    pub fn random(hidden: usize, seed: u64) -> Network {
+        let mut state = seed.max(1);
+        let mut next = |range: i32| {
+            // xorshift64*
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            let value = state.wrapping_mul(0x2545F4914F6CDD1D) >> 32;
+            (value % (2 * range as u64 + 1)) as i32 - range
+        };
+
+        Network {
+            hidden,
+            feature_weights: (0..INPUTS * hidden).map(|_| next(32) as i16).collect(),
+            feature_biases: (0..hidden).map(|_| next(32) as i16).collect(),
+            output_weights: (0..2 * hidden).map(|_| next(32) as i16).collect(),
+            output_bias: next(1000),
+        }
+    }
+
+    pub fn get_hidden(&self) -> usize {
+        self.hidden
+    }
+
+    fn feature(perspective: Side, piece: usize, square: u32) -> usize {
+        let (color, kind) = (piece / 6, piece % 6);
+        match perspective {
+            Side::White => (color * 6 + kind) * 64 + square as usize,
+            Side::Black => ((1 - color) * 6 + kind) * 64 + (square ^ 56) as usize,
+        }
+    }
+
+    fn update(&self, values: &mut [i32], feature: usize, sign: i32) {
+        let weights = &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden];
+        for (value, weight) in values.iter_mut().zip(weights) {
+            *value += sign * *weight as i32;
+        }
+    }
+
+    fn output(&self, own: &[i32], other: &[i32]) -> i32 {
+        let (own_weights, other_weights) = self.output_weights.split_at(self.hidden);
+        let sum: i64 = own
+            .iter()
+            .zip(own_weights)
+            .chain(other.iter().zip(other_weights))
+            .map(|(value, weight)| (*value).clamp(0, QA) as i64 * *weight as i64)
+            .sum();
+
+        ((sum + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
+
+fn piece_bitboards(board: &Board) -> [u64; 12] {
+    let mut pieces = [0; 12];
+    for (index, bitboard) in pieces.iter_mut().enumerate() {
+        let piece_type: PieceType = num::FromPrimitive::from_usize(index).unwrap();
+        *bitboard = board.occupied(Scope::from(piece_type));
+    }
+    pieces
+}
+
+/// Accumulators allocated up front, enough for the deepest searches without growing
+const STACK_SIZE: usize = 128;
+
+/// Call `update` with the `(piece, square, sign)` of each piece a move removes or adds
+fn move_features<F>(board: &Board, mov: &Move, child: &Board, mut update: F)
+where
+    F: FnMut(usize, u32, i32),
+{
+    let (src, dst) = (mov.get_src(), mov.get_dst());
+    let piece = |board: &Board, square: Square| {
+        board
+            .piece_at(square)
+            .filter(|piece| (*piece as usize) < 12)
+            .map(|piece| piece as usize)
+    };
+    let Some(moving) = piece(board, src) else {
+        return;
+    };
+
+    update(moving, src.get_index() as u32, -1);
+    match piece(board, dst) {
+        Some(captured) => update(captured, dst.get_index() as u32, -1),
+        // a pawn moving diagonally to an empty square captures en passant
+        None if moving % 6 == PieceType::WhitePawn as usize && src.get_file() != dst.get_file() => {
+            let captured = Square::from_rank_file(src.get_rank(), dst.get_file());
+            if let Some(pawn) = piece(board, captured) {
+                update(pawn, captured.get_index() as u32, -1);
+            }
+        }
+        None => {}
+    }
+    // promotions place another piece than the one that moved
+    if let Some(placed) = piece(child, dst) {
+        update(placed, dst.get_index() as u32, 1);
+    }
+
+    // castling also moves the rook
+    if moving % 6 == PieceType::WhiteKing as usize && src.get_file().abs_diff(dst.get_file()) == 2 {
+        let rank = src.get_rank();
+        let (from, to) = if dst.get_file() == 6 { (7, 5) } else { (0, 3) };
+        let (from, to) = (
+            Square::from_rank_file(rank, from),
+            Square::from_rank_file(rank, to),
+        );
+        if let Some(rook) = piece(board, from) {
+            update(rook, from.get_index() as u32, -1);
+            update(rook, to.get_index() as u32, 1);
+        }
+    }
+}
+
+/// Hidden layer values of both sides for one position
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Accumulator {
+    values: [Vec<i32>; 2],
+}
+
+impl Accumulator {
+    fn new(network: &Network) -> Accumulator {
+        let biases: Vec<i32> = network.feature_biases.iter().map(|b| *b as i32).collect();
+        Accumulator {
+            values: [biases.clone(), biases],
+        }
+    }
+
+    /// Values of a position computed from scratch
+    fn refresh(&mut self, network: &Network, board: &Board) {
+        for values in self.values.iter_mut() {
+            for (value, bias) in values.iter_mut().zip(network.feature_biases.iter()) {
+                *value = *bias as i32;
+            }
+        }
+        for (piece, bitboard) in piece_bitboards(board).iter().enumerate() {
+            for square in squares(*bitboard) {
+                self.update(network, piece, square as u32, 1);
+            }
+        }
+    }
+
+    fn update(&mut self, network: &Network, piece: usize, square: u32, sign: i32) {
+        for perspective in [Side::White, Side::Black] {
+            network.update(
+                &mut self.values[perspective as usize],
+                Network::feature(perspective, piece, square),
+                sign,
+            );
+        }
+    }
+
+    fn copy_from(&mut self, other: &Accumulator) {
+        for (values, other) in self.values.iter_mut().zip(other.values.iter()) {
+            values.copy_from_slice(other);
+        }
+    }
+}
+
+/// Neural network evaluation with an accumulator stack that follows the search. Each `push`
+/// copies the parent accumulator into the next preallocated slot and applies the features
+/// changed by the move.
+#[derive(Clone, Debug)]
+pub struct NnueEvaluator {
+    network: Network,
+    stack: Vec<Accumulator>,
+    ply: usize,
+    /// Pieces of the position the bottom of the stack was computed for
+    root: Option<[u64; 12]>,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Network) -> NnueEvaluator {
+        let stack = vec![Accumulator::new(&network); STACK_SIZE];
+        NnueEvaluator {
+            network,
+            stack,
+            ply: 0,
+            root: None,
+        }
+    }
+
+    pub fn from_filename(filename: &str) -> io::Result<NnueEvaluator> {
+        Ok(NnueEvaluator::new(Network::from_filename(filename)?))
+    }
+
+    /// Evaluate from scratch, ignoring the accumulator stack
+    pub fn evaluate_full(&self, board: &Board) -> i32 {
+        let mut accumulator = Accumulator::new(&self.network);
+        accumulator.refresh(&self.network, board);
+        self.score(&accumulator, board)
+    }
+
+    fn score(&self, accumulator: &Accumulator, board: &Board) -> i32 {
+        let turn = board.get_turn();
+        self.network.output(
+            &accumulator.values[turn as usize],
+            &accumulator.values[!turn as usize],
+        )
+    }
+
+    /// Recompute the bottom of the stack when a search starts from another position
+    fn refresh_root(&mut self, board: &Board) {
+        let pieces = piece_bitboards(board);
+        if self.root != Some(pieces) {
+            self.stack[0].refresh(&self.network, board);
+            self.root = Some(pieces);
+        }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        if self.ply == 0 {
+            self.refresh_root(board);
+        }
+        self.score(&self.stack[self.ply], board)
+    }
+
+    fn push(&mut self, board: &Board, mov: &Move, child: &Board) {
+        if self.ply == 0 {
+            self.refresh_root(board);
+        }
+        if self.ply + 1 == self.stack.len() {
+            self.stack.push(Accumulator::new(&self.network));
+        }
+
+        let (parents, children) = self.stack.split_at_mut(self.ply + 1);
+        let accumulator = &mut children[0];
+        accumulator.copy_from(&parents[self.ply]);
+        let network = &self.network;
+        move_features(board, mov, child, |piece, square, sign| {
+            accumulator.update(network, piece, square, sign)
+        });
+        self.ply += 1;
+    }
+
+    fn pop(&mut self) {
+        self.ply = self.ply.saturating_sub(1);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Evaluator + Send> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generator::MoveGenerator;
+
+    const TINY_NETWORK: &str = "testcases/nnue/tiny.nnue";
+
+    #[test]
+    fn test_tiny_network_file() {
+        let network = Network::from_filename(TINY_NETWORK).unwrap();
+
+        assert_eq!(network.get_hidden(), 8);
+        assert_eq!(network, Network::random(8, 0xF0E5));
+        assert_eq!(Network::from_bytes(&network.to_bytes()).unwrap(), network);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_files() {
+        let bytes = Network::random(4, 1).to_bytes();
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(Network::from_bytes(&[b"XXXX", &bytes[4..]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_evaluation_is_deterministic() {
+        let board = Board::from_basic_board();
+        let mut first = NnueEvaluator::from_filename(TINY_NETWORK).unwrap();
+        let mut second = NnueEvaluator::from_filename(TINY_NETWORK).unwrap();
+
+        assert_eq!(first.evaluate(&board), second.evaluate(&board));
+        assert_eq!(first.evaluate(&board), first.evaluate_full(&board));
+        assert_eq!(first.evaluate(&board), -111);
+    }
+
+    #[test]
+    fn test_incremental_matches_full_evaluation() {
+        let mut evaluator = NnueEvaluator::from_filename(TINY_NETWORK).unwrap();
+        let mut board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        evaluator.evaluate(&board);
+
+        // en passant, castling, promotion with capture and a king move
+        for mov in ["e5d6", "e8c8", "b7a8Q", "d8d6", "e1g1", "c8b7"] {
+            let mov = Move::from_full_algebraic(mov).unwrap();
+            let child = board.apply(mov.clone()).unwrap();
+            evaluator.push(&board, &mov, &child);
+            board = child;
+
+            assert_eq!(evaluator.evaluate(&board), evaluator.evaluate_full(&board));
+        }
+
+        for _ in 0..6 {
+            evaluator.pop();
+        }
+        assert_eq!(evaluator.ply, 0);
+    }
+
+    #[test]
+    fn test_incremental_matches_full_evaluation_for_every_move() {
+        let move_generator = MoveGenerator::new();
+        let mut evaluator = NnueEvaluator::from_filename(TINY_NETWORK).unwrap();
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let board = Board::from_fen(fen);
+            for mov in move_generator.legal_moves(&board) {
+                let child = board.apply(mov.clone()).unwrap();
+                evaluator.push(&board, &mov, &child);
+                assert_eq!(
+                    evaluator.evaluate(&child),
+                    evaluator.evaluate_full(&child),
+                    "{}",
+                    mov.to_algebraic()
+                );
+                evaluator.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,309 @@
+/*!
+Packed, fixed-size positions and files of scored positions for training data.
+
+A position is packed into `PACKED_SIZE` bytes, all values little-endian:
+
+| field            | type | count | notes                                                   |
+|------------------|------|-------|---------------------------------------------------------|
+| occupancy        | u64  | 1     | bit `i` set when square `i` (a1 = 0, h8 = 63) has a piece |
+| pieces           | u4   | 32    | piece of each occupied square, in square order, low nibble first |
+| flags            | u8   | 1     | bit 0 black to move, bits 1 to 4 castling rights `KQkq` |
+| en passant       | u8   | 1     | target square, `255` for none                           |
+| half move clock  | u16  | 1     |                                                         |
+| full move number | u16  | 1     |                                                         |
+
+Pieces are coded as `PieceType`: white pawn, rook, knight, bishop, queen and king from 0 to 5,
+then the black pieces from 6 to 11. Unused nibbles are zero.
+
+A record file starts with a header and is followed by records of `RECORD_SIZE` bytes:
+
+| field    | type  | count | notes                                        |
+|----------|-------|-------|----------------------------------------------|
+| magic    | bytes | 4     | `FPOS`, header only                          |
+| version  | u32   | 1     | `1`, header only                             |
+| position | bytes | 30    | packed position                              |
+| score    | i16   | 1     | centipawns from white's point of view        |
+| result   | u8    | 1     | `0` black wins, `1` draw, `2` white wins     |
+| reserved | u8    | 1     | `0`                                          |
+*/
+use std::convert::TryInto;
+use std::io;
+use std::io::{Read, Write};
+
+use crate::board::Board;
+use crate::moves::Scope;
+use crate::piece::PieceType;
+use crate::side::Side;
+use crate::square::Square;
+use crate::tournament::GameResult;
+
+/// Size of a packed position in bytes
+pub const PACKED_SIZE: usize = 30;
+/// Size of a scored position in a record file in bytes
+pub const RECORD_SIZE: usize = PACKED_SIZE + 4;
+
+const RECORD_MAGIC: &[u8; 4] = b"FPOS";
+const RECORD_VERSION: u32 = 1;
+const MAX_PIECES: u32 = 32;
+const NO_ENPASSANT: u8 = 255;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Pack a position, `None` when it has more than 32 pieces
+pub fn encode(board: &Board) -> Option<[u8; PACKED_SIZE]> {
+    let occupancy = board.occupied(Scope::All);
+    if occupancy.count_ones() > MAX_PIECES {
+        return None;
+    }
+
+    let mut bytes = [0; PACKED_SIZE];
+    bytes[..8].copy_from_slice(&occupancy.to_le_bytes());
+    for (i, index) in (0..64).filter(|i| occupancy >> i & 1 == 1).enumerate() {
+        let piece = board.piece_at(Square::from_index(index))? as u8;
+        bytes[8 + i / 2] |= piece << (4 * (i % 2));
+    }
+
+    bytes[24] = (board.get_turn() == Side::Black) as u8
+        | (board.get_castling_white_short() as u8) << 1
+        | (board.get_castling_white_long() as u8) << 2
+        | (board.get_castling_black_short() as u8) << 3
+        | (board.get_castling_black_long() as u8) << 4;
+    bytes[25] = board
+        .get_enpassant()
+        .map_or(NO_ENPASSANT, |square| square.get_index());
+    bytes[26..28].copy_from_slice(&board.get_half_move_clock().to_le_bytes());
+    bytes[28..30].copy_from_slice(&board.get_full_move_clock().to_le_bytes());
+    Some(bytes)
+}
+
+/// Unpack a position, `None` when the bytes are not a packed position
+pub fn decode(bytes: &[u8; PACKED_SIZE]) -> Option<Board> {
+    let occupancy = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    if occupancy.count_ones() > MAX_PIECES || bytes[24] >> 5 != 0 {
+        return None;
+    }
+
+    let mut board = Board::new();
+    for (i, index) in (0..64).filter(|i| occupancy >> i & 1 == 1).enumerate() {
+        let code = bytes[8 + i / 2] >> (4 * (i % 2)) & 0xf;
+        if code > PieceType::BlackKing as u8 {
+            return None;
+        }
+        let piece: PieceType = num::FromPrimitive::from_u8(code)?;
+        board.set_piece(Square::from_index(index), piece);
+    }
+
+    let flags = bytes[24];
+    board.set_turn(if flags & 1 == 0 {
+        Side::White
+    } else {
+        Side::Black
+    });
+    board.set_castling_white_short(flags & 2 != 0);
+    board.set_castling_white_long(flags & 4 != 0);
+    board.set_castling_black_short(flags & 8 != 0);
+    board.set_castling_black_long(flags & 16 != 0);
+
+    board.set_enpassant(match bytes[25] {
+        NO_ENPASSANT => None,
+        index @ (16..=23 | 40..=47) => Some(Square::from_index(index)),
+        _ => return None,
+    });
+    board.set_half_move_clock(u16::from_le_bytes([bytes[26], bytes[27]]));
+    board.set_full_move_clock(u16::from_le_bytes([bytes[28], bytes[29]]));
+    Some(board)
+}
+
+/// Position labelled with a score and the result of its game
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionRecord {
+    pub board: Board,
+    /// Centipawns from white's point of view
+    pub score: i16,
+    pub result: GameResult,
+}
+
+impl PositionRecord {
+    pub fn new(board: Board, score: i16, result: GameResult) -> PositionRecord {
+        PositionRecord {
+            board,
+            score,
+            result,
+        }
+    }
+
+    /// Record as written in a record file, `None` when the position can not be packed
+    pub fn to_bytes(&self) -> Option<[u8; RECORD_SIZE]> {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[..PACKED_SIZE].copy_from_slice(&encode(&self.board)?);
+        bytes[PACKED_SIZE..PACKED_SIZE + 2].copy_from_slice(&self.score.to_le_bytes());
+        bytes[PACKED_SIZE + 2] = match self.result {
+            GameResult::BlackWins => 0,
+            GameResult::Draw => 1,
+            GameResult::WhiteWins => 2,
+        };
+        Some(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> io::Result<PositionRecord> {
+        let board = decode(bytes[..PACKED_SIZE].try_into().unwrap())
+            .ok_or_else(|| invalid_data("invalid packed position"))?;
+        let score = i16::from_le_bytes([bytes[PACKED_SIZE], bytes[PACKED_SIZE + 1]]);
+        let result = match bytes[PACKED_SIZE + 2] {
+            0 => GameResult::BlackWins,
+            1 => GameResult::Draw,
+            2 => GameResult::WhiteWins,
+            _ => return Err(invalid_data("invalid game result")),
+        };
+        Ok(PositionRecord::new(board, score, result))
+    }
+}
+
+/// Writes a record file, header first
+pub struct RecordWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<RecordWriter<W>> {
+        writer.write_all(RECORD_MAGIC)?;
+        writer.write_all(&RECORD_VERSION.to_le_bytes())?;
+        Ok(RecordWriter { writer })
+    }
+
+    pub fn write(&mut self, record: &PositionRecord) -> io::Result<()> {
+        let bytes = record.to_bytes().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "too many pieces to pack")
+        })?;
+        self.writer.write_all(&bytes)
+    }
+
+    /// Flush and return the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the records of a record file one at a time
+pub struct RecordReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Check the header and start reading the records after it
+    pub fn new(mut reader: R) -> io::Result<RecordReader<R>> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != RECORD_MAGIC {
+            return Err(invalid_data("not a record file"));
+        }
+        if u32::from_le_bytes(header[4..].try_into().unwrap()) != RECORD_VERSION {
+            return Err(invalid_data("unsupported record file version"));
+        }
+        Ok(RecordReader { reader })
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<PositionRecord>;
+
+    fn next(&mut self) -> Option<io::Result<PositionRecord>> {
+        let mut bytes = [0; RECORD_SIZE];
+        let mut filled = 0;
+        while filled < RECORD_SIZE {
+            match self.reader.read(&mut bytes[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => return Some(Err(invalid_data("truncated record"))),
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(PositionRecord::from_bytes(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::board::STARTPOS;
+
+    #[rstest]
+    #[case(STARTPOS)]
+    #[case("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 3 40")]
+    #[case("rnbqkbnr/pppp1ppp/8/8/3Pp3/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 2")]
+    #[case("8/8/4k3/8/8/4K3/8/8 b - - 99 255")]
+    #[case("8/8/4k3/8/8/4K3/8/8 w - - 300 1234")]
+    fn test_packed_round_trip(#[case] fen: &str) {
+        let board = Board::from_fen(fen);
+        let bytes = encode(&board).unwrap();
+        assert_eq!(decode(&bytes), Some(board));
+    }
+
+    #[test]
+    fn test_packed_layout() {
+        let bytes = encode(&Board::from_fen(STARTPOS)).unwrap();
+        assert_eq!(&bytes[..8], &0xffff_0000_0000_ffffu64.to_le_bytes());
+        // a1 rook and b1 knight share the first byte
+        assert_eq!(bytes[8], 0x21);
+        assert_eq!(bytes[24], 0b11110);
+        assert_eq!(&bytes[25..], &[NO_ENPASSANT, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_packed_rejects() {
+        let mut too_many = Board::from_fen(STARTPOS);
+        too_many.set_piece(Square::from_algebraic("e4").unwrap(), PieceType::WhiteQueen);
+        assert_eq!(encode(&too_many), None);
+
+        let bytes = encode(&Board::from_fen(STARTPOS)).unwrap();
+
+        let mut bad_piece = bytes;
+        bad_piece[8] = 0x2c;
+        assert_eq!(decode(&bad_piece), None);
+
+        let mut bad_enpassant = bytes;
+        bad_enpassant[25] = 28;
+        assert_eq!(decode(&bad_enpassant), None);
+
+        let mut bad_flags = bytes;
+        bad_flags[24] |= 0x80;
+        assert_eq!(decode(&bad_flags), None);
+    }
+
+    #[test]
+    fn test_record_file_round_trip() {
+        let records = vec![
+            PositionRecord::new(Board::from_fen(STARTPOS), 25, GameResult::Draw),
+            PositionRecord::new(
+                Board::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 3 40"),
+                -310,
+                GameResult::BlackWins,
+            ),
+        ];
+
+        let mut writer = RecordWriter::new(Vec::new()).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes.len(), 8 + 2 * RECORD_SIZE);
+
+        let read: Vec<PositionRecord> = RecordReader::new(bytes.as_slice())
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, records);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        let mut reader = RecordReader::new(truncated).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(RecordReader::new(&b"FNUE\x01\0\0\0"[..]).is_err());
+    }
+}
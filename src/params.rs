@@ -0,0 +1,201 @@
+#[cfg(feature = "serde")]
+use std::fs;
+#[cfg(feature = "serde")]
+use std::io;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Weights of the classical evaluation, in centipawns.
+///
+/// Per piece arrays are ordered knight, bishop, rook, queen (with the pawn first for
+/// `piece_values`) and per rank arrays are indexed by the rank relative to the pawn's side.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct EvalParams {
+    pub piece_values: [i32; 5],
+
+    pub doubled_pawn: i32,
+    pub isolated_pawn: i32,
+    pub backward_pawn: i32,
+    /// Bonus for a pawn defended by or side by side with a friendly pawn
+    pub connected_pawn: [i32; 8],
+    pub passed_pawn: [i32; 8],
+    pub passed_pawn_own_king_distance: i32,
+    pub passed_pawn_enemy_king_distance: i32,
+
+    /// Bonus per square not attacked by enemy pawns
+    pub mobility: [i32; 4],
+    /// Number of safe squares for which the mobility bonus is zero
+    pub mobility_offset: [i32; 4],
+
+    /// Attack units per attacked square of the enemy king zone
+    pub king_attack_weights: [i32; 4],
+    /// Percentage of the king attack units that count, by number of attacking pieces
+    pub king_attackers_scale: [i32; 8],
+    /// Attacking material of each piece, used to scale king safety with what the enemy has left
+    pub king_attack_phase: [i32; 4],
+    /// Attacking material at which king safety fully applies
+    pub king_safety_full_phase: i32,
+    pub king_shield_close: i32,
+    pub king_shield_far: i32,
+    pub king_semi_open_file: i32,
+    pub king_open_file: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            piece_values: [100, 320, 330, 500, 900],
+
+            doubled_pawn: -12,
+            isolated_pawn: -15,
+            backward_pawn: -10,
+            connected_pawn: [0, 3, 5, 8, 14, 24, 40, 0],
+            passed_pawn: [0, 5, 10, 18, 32, 55, 90, 0],
+            passed_pawn_own_king_distance: 2,
+            passed_pawn_enemy_king_distance: 5,
+
+            mobility: [4, 5, 2, 1],
+            mobility_offset: [4, 6, 7, 13],
+
+            king_attack_weights: [20, 20, 40, 80],
+            king_attackers_scale: [0, 0, 50, 75, 88, 94, 97, 99],
+            king_attack_phase: [1, 1, 2, 4],
+            king_safety_full_phase: 12,
+            king_shield_close: 12,
+            king_shield_far: 6,
+            king_semi_open_file: -15,
+            king_open_file: -25,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn is_json(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(".json")
+}
+
+impl EvalParams {
+    /// Load parameters from a JSON file, or a YAML file for any other extension.
+    /// Missing fields keep their default value.
+    #[cfg(feature = "serde")]
+    pub fn from_filename(filename: &str) -> io::Result<EvalParams> {
+        let contents = fs::read_to_string(filename)?;
+        if is_json(filename) {
+            serde_json::from_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        } else {
+            serde_yaml::from_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+
+    /// Save parameters as JSON or YAML depending on the file extension
+    #[cfg(feature = "serde")]
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let contents = if is_json(filename) {
+            serde_json::to_string_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+        } else {
+            serde_yaml::to_string(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+        };
+        fs::write(filename, contents)
+    }
+
+    /// Whether the weights cached in the pawn hash table are the same
+    pub fn same_pawn_weights(&self, other: &EvalParams) -> bool {
+        self.doubled_pawn == other.doubled_pawn
+            && self.isolated_pawn == other.isolated_pawn
+            && self.backward_pawn == other.backward_pawn
+            && self.connected_pawn == other.connected_pawn
+            && self.passed_pawn == other.passed_pawn
+    }
+
+    /// Every weight in a fixed order, for tuning
+    pub fn weights_mut(&mut self) -> Vec<&mut i32> {
+        let mut weights: Vec<&mut i32> = vec![];
+        weights.extend(self.piece_values.iter_mut());
+        weights.push(&mut self.doubled_pawn);
+        weights.push(&mut self.isolated_pawn);
+        weights.push(&mut self.backward_pawn);
+        weights.extend(self.connected_pawn.iter_mut());
+        weights.extend(self.passed_pawn.iter_mut());
+        weights.push(&mut self.passed_pawn_own_king_distance);
+        weights.push(&mut self.passed_pawn_enemy_king_distance);
+        weights.extend(self.mobility.iter_mut());
+        weights.extend(self.mobility_offset.iter_mut());
+        weights.extend(self.king_attack_weights.iter_mut());
+        weights.extend(self.king_attackers_scale.iter_mut());
+        weights.extend(self.king_attack_phase.iter_mut());
+        weights.push(&mut self.king_safety_full_phase);
+        weights.push(&mut self.king_shield_close);
+        weights.push(&mut self.king_shield_far);
+        weights.push(&mut self.king_semi_open_file);
+        weights.push(&mut self.king_open_file);
+        weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    use rstest::rstest;
+
+    use super::EvalParams;
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    #[case("params.json")]
+    #[case("params.yaml")]
+    fn test_save_and_load(#[case] filename: &str) {
+        let mut params = EvalParams::default();
+        params.piece_values[0] = 90;
+        params.king_open_file = -40;
+
+        let path = std::env::temp_dir().join(format!("fchess-{}-{filename}", std::process::id()));
+        let path = path.to_str().unwrap();
+        params.save(path).unwrap();
+        let loaded = EvalParams::from_filename(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.unwrap(), params);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let params: EvalParams = serde_yaml::from_str("doubled_pawn: -30").unwrap();
+
+        assert_eq!(params.doubled_pawn, -30);
+        assert_eq!(params.piece_values, EvalParams::default().piece_values);
+    }
+
+    #[test]
+    fn test_same_pawn_weights() {
+        let params = EvalParams::default();
+        let mut other = params.clone();
+        other.mobility[0] += 1;
+        other.king_safety_full_phase += 1;
+        assert!(params.same_pawn_weights(&other));
+
+        other.passed_pawn[4] += 1;
+        assert!(!params.same_pawn_weights(&other));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_weights_mut_covers_every_weight() {
+        let mut params = EvalParams::default();
+        for weight in params.weights_mut() {
+            *weight = 7;
+        }
+
+        let json = serde_json::to_value(&params).unwrap().to_string();
+        assert!(json
+            .split(|c: char| !c.is_ascii_digit() && c != '-')
+            .filter(|token| !token.is_empty())
+            .all(|token| token == "7"));
+    }
+}
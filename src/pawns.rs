@@ -0,0 +1,293 @@
+use crate::bitboard::{adjacent_files_mask, file_mask, rank_mask, squares, Bitboard};
+use crate::board::Board;
+use crate::moves::Scope;
+use crate::params::EvalParams;
+use crate::side::Side;
+use crate::square::Square;
+
+const PAWN_TABLE_SIZE: usize = 16384;
+
+fn relative_rank(side: Side, rank: u8) -> u8 {
+    match side {
+        Side::White => rank,
+        Side::Black => 7 - rank,
+    }
+}
+
+/// Squares on the ranks in front of `rank` from the point of view of `side`
+fn forward_ranks(side: Side, rank: u8) -> Bitboard {
+    match side {
+        Side::White if rank == 7 => 0,
+        Side::White => !0 << (8 * (rank + 1)),
+        Side::Black => (1 << (8 * rank)) - 1,
+    }
+}
+
+/// Rank `offset` ranks in front of `rank` from the point of view of `side`
+pub fn relative_rank_mask(side: Side, rank: u8, offset: i8) -> Bitboard {
+    let rank = match side {
+        Side::White => rank as i8 + offset,
+        Side::Black => rank as i8 - offset,
+    };
+    if (0..8).contains(&rank) {
+        rank_mask(rank as u8)
+    } else {
+        0
+    }
+}
+
+/// Score the pawns of one side, returning the score and the bitboard of passed pawns
+fn evaluate_pawns(
+    side: Side,
+    own: Bitboard,
+    enemy: Bitboard,
+    params: &EvalParams,
+) -> (i32, Bitboard) {
+    let mut score = 0;
+    let mut passed = 0;
+
+    for index in squares(own) {
+        let square = Square::from_index(index);
+        let file = square.get_file();
+        let rank = square.get_rank();
+        let ahead = forward_ranks(side, rank);
+        let adjacent = adjacent_files_mask(file);
+
+        let doubled = own & file_mask(file) & ahead != 0;
+        let isolated = own & adjacent == 0;
+        let supported = own & adjacent & relative_rank_mask(side, rank, -1) != 0;
+        let phalanx = own & adjacent & rank_mask(rank) != 0;
+        let connected = supported || phalanx;
+        let is_passed = !doubled && enemy & (file_mask(file) | adjacent) & ahead == 0;
+        let backward = !isolated
+            && !connected
+            && own & adjacent & !ahead == 0
+            && enemy & adjacent & relative_rank_mask(side, rank, 2) != 0;
+
+        if doubled {
+            score += params.doubled_pawn;
+        }
+        if isolated {
+            score += params.isolated_pawn;
+        }
+        if backward {
+            score += params.backward_pawn;
+        }
+        if connected {
+            score += params.connected_pawn[relative_rank(side, rank) as usize];
+        }
+        if is_passed {
+            score += params.passed_pawn[relative_rank(side, rank) as usize];
+            passed |= 1 << index;
+        }
+    }
+
+    (score, passed)
+}
+
+/// Pawn structure evaluation of a single pawn configuration
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PawnEntry {
+    key: u64,
+    score: i32,
+    passed: [Bitboard; 2],
+}
+
+impl PawnEntry {
+    pub fn new(key: u64, board: &Board, params: &EvalParams) -> PawnEntry {
+        let white = board.occupied(Scope::WhitePawn);
+        let black = board.occupied(Scope::BlackPawn);
+        let (white_score, white_passed) = evaluate_pawns(Side::White, white, black, params);
+        let (black_score, black_passed) = evaluate_pawns(Side::Black, black, white, params);
+
+        PawnEntry {
+            key,
+            score: white_score - black_score,
+            passed: [white_passed, black_passed],
+        }
+    }
+
+    /// Pawn structure score in centipawns from white's point of view
+    pub fn get_score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn get_passed(&self, side: Side) -> Bitboard {
+        self.passed[side as usize]
+    }
+
+    /// Bonus for passed pawns escorted by their own king and far from the enemy king, growing
+    /// as the pawn advances. Depends on the king positions so it is not cached.
+    pub fn king_proximity(&self, board: &Board, params: &EvalParams) -> i32 {
+        let (Some(white_king), Some(black_king)) = (
+            board.king_square(Side::White),
+            board.king_square(Side::Black),
+        ) else {
+            return 0;
+        };
+
+        let mut score = 0;
+        for (side, own_king, enemy_king, sign) in [
+            (Side::White, white_king, black_king, 1),
+            (Side::Black, black_king, white_king, -1),
+        ] {
+            for index in squares(self.get_passed(side)) {
+                let square = Square::from_index(index);
+                let weight = relative_rank(side, square.get_rank()).saturating_sub(2) as i32;
+                let stop = match side {
+                    Side::White => Square::from_index(index + 8),
+                    Side::Black => Square::from_index(index - 8),
+                };
+
+                score += sign
+                    * weight
+                    * (params.passed_pawn_enemy_king_distance * enemy_king.distance(stop) as i32
+                        - params.passed_pawn_own_king_distance * own_king.distance(stop) as i32);
+            }
+        }
+
+        score
+    }
+}
+
+/// Cache of pawn structure evaluations keyed by the pawn-only Zobryst hash
+#[derive(Clone, Debug)]
+pub struct PawnHashTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl Default for PawnHashTable {
+    fn default() -> Self {
+        Self::new(PAWN_TABLE_SIZE)
+    }
+}
+
+impl PawnHashTable {
+    pub fn new(size: usize) -> PawnHashTable {
+        PawnHashTable {
+            entries: vec![None; size.max(1)],
+        }
+    }
+
+    pub fn probe(&mut self, board: &Board, params: &EvalParams) -> PawnEntry {
+        let key = board.pawn_hash();
+        let index = (key % self.entries.len() as u64) as usize;
+
+        match self.entries[index] {
+            Some(entry) if entry.key == key => entry,
+            _ => {
+                let entry = PawnEntry::new(key, board, params);
+                self.entries[index] = Some(entry);
+                entry
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn bitboard(squares: &[&str]) -> Bitboard {
+        squares.iter().fold(0, |acc, square| {
+            acc | 1 << Square::from_algebraic(square).unwrap().get_index()
+        })
+    }
+
+    #[rstest]
+    #[case("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", vec!["e2"], vec![])]
+    #[case("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1", vec![], vec![])]
+    #[case("4k3/3p4/8/8/8/8/4P3/4K3 w - - 0 1", vec![], vec![])]
+    #[case("4k3/2p5/8/8/8/8/4P3/4K3 w - - 0 1", vec!["e2"], vec!["c7"])]
+    #[case("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1", vec!["e3"], vec![])]
+    #[case("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", vec![], vec![])]
+    fn test_passed_pawns(
+        #[case] fen: &str,
+        #[case] white_passed: Vec<&str>,
+        #[case] black_passed: Vec<&str>,
+    ) {
+        let board = Board::from_fen(fen);
+        let entry = PawnEntry::new(board.pawn_hash(), &board, &EvalParams::default());
+
+        assert_eq!(entry.get_passed(Side::White), bitboard(&white_passed));
+        assert_eq!(entry.get_passed(Side::Black), bitboard(&black_passed));
+    }
+
+    #[rstest]
+    // doubled and isolated pawns on the e file
+    #[case(
+        Side::White,
+        vec!["e2", "e3"],
+        vec!["a7"],
+        |p: &EvalParams| 2 * p.isolated_pawn + p.doubled_pawn + p.passed_pawn[2]
+    )]
+    // isolated passed pawn
+    #[case(Side::White, vec!["a4"], vec!["h7"], |p: &EvalParams| p.isolated_pawn + p.passed_pawn[3])]
+    // pawns side by side are connected
+    #[case(
+        Side::White,
+        vec!["d4", "e4"],
+        vec!["d7", "e7"],
+        |p: &EvalParams| 2 * p.connected_pawn[3]
+    )]
+    // d2 can't advance without being taken by c4 and has no support
+    #[case(
+        Side::White,
+        vec!["d2", "e4"],
+        vec!["c4", "e5"],
+        |p: &EvalParams| p.backward_pawn
+    )]
+    // relative ranks are mirrored for black
+    #[case(
+        Side::Black,
+        vec!["d5", "e5"],
+        vec!["a2"],
+        |p: &EvalParams| 2 * p.connected_pawn[3] + 2 * p.passed_pawn[3]
+    )]
+    fn test_evaluate_pawns(
+        #[case] side: Side,
+        #[case] own: Vec<&str>,
+        #[case] enemy: Vec<&str>,
+        #[case] expected: fn(&EvalParams) -> i32,
+    ) {
+        let params = EvalParams::default();
+        assert_eq!(
+            evaluate_pawns(side, bitboard(&own), bitboard(&enemy), &params).0,
+            expected(&params)
+        );
+    }
+
+    #[test]
+    fn test_king_proximity() {
+        let escorted = Board::from_fen("k7/8/4K3/4P3/8/8/8/8 w - - 0 1");
+        let abandoned = Board::from_fen("8/8/4k3/4P3/8/8/8/K7 w - - 0 1");
+
+        let params = EvalParams::default();
+
+        let escorted = PawnEntry::new(escorted.pawn_hash(), &escorted, &params)
+            .king_proximity(&escorted, &params);
+        let abandoned = PawnEntry::new(abandoned.pawn_hash(), &abandoned, &params)
+            .king_proximity(&abandoned, &params);
+
+        assert!(escorted > 0);
+        assert!(abandoned < 0);
+    }
+
+    #[test]
+    fn test_pawn_hash_table_probe() {
+        let mut table = PawnHashTable::new(16);
+        let board = Board::from_fen("4k3/pp6/8/8/8/8/PPP5/4K3 w - - 0 1");
+
+        let params = EvalParams::default();
+
+        let entry = table.probe(&board, &params);
+        assert_eq!(entry, PawnEntry::new(board.pawn_hash(), &board, &params));
+        assert_eq!(table.probe(&board, &params), entry);
+    }
+}
@@ -0,0 +1,941 @@
+//! Portable Game Notation: a reader streaming games with their tags, comments, NAGs and
+//! variations out of multi-game files, and export of games in the same form.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::board::STARTPOS;
+use crate::move_generator::MoveGenerator;
+use crate::moves::Move;
+use crate::side::Side;
+use crate::solver::Score;
+
+/// Tags every PGN game is expected to have, in export order
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+/// Exported movetext is wrapped before this column
+const LINE_WIDTH: usize = 80;
+
+/// Failure to read a game, at a 1-based line and column of the input
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for PgnError {}
+
+impl From<PgnError> for io::Error {
+    fn from(err: PgnError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Move of a game tree with what was written about it and the moves that follow
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameNode {
+    pub mov: Move,
+    /// Move as written in the game
+    pub san: String,
+    /// Comments before the move, only found at the start of a game or variation
+    pub starting_comments: Vec<String>,
+    /// Comments after the move
+    pub comments: Vec<String>,
+    /// Numeric annotation glyphs, with `!`, `?` and the like as 1 to 6
+    pub nags: Vec<u8>,
+    /// Moves that can follow, the first being the main continuation and the others variations
+    pub children: Vec<GameNode>,
+}
+
+/// Game read from PGN
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    /// Tag pairs in the order they were read
+    pub tags: Vec<(String, String)>,
+    /// Position the game starts from, given by the `FEN` tag or the start position
+    pub board: Board,
+    /// First moves of the game, the first being the mainline and the others variations
+    pub moves: Vec<GameNode>,
+    /// Game termination marker, `*` when the game was not finished or the marker is missing
+    pub result: String,
+}
+
+impl GameNode {
+    /// Node of a legal move on `board`, written in SAN
+    pub fn new(mov: Move, board: &Board) -> GameNode {
+        GameNode {
+            san: mov.to_san(board),
+            mov,
+            starting_comments: vec![],
+            comments: vec![],
+            nags: vec![],
+            children: vec![],
+        }
+    }
+}
+
+impl PgnGame {
+    /// Game starting from `board`, without moves and with the seven tag roster unknown
+    pub fn new(board: Board) -> PgnGame {
+        let tags = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&tag| {
+                let value = match tag {
+                    "Date" => "????.??.??",
+                    "Result" => "*",
+                    _ => "?",
+                };
+                (tag.to_string(), value.to_string())
+            })
+            .collect();
+
+        PgnGame {
+            tags,
+            board,
+            moves: vec![],
+            result: "*".to_string(),
+        }
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Nodes of the mainline
+    pub fn mainline_nodes(&self) -> Vec<&GameNode> {
+        let mut nodes = vec![];
+        let mut next = self.moves.first();
+        while let Some(node) = next {
+            nodes.push(node);
+            next = node.children.first();
+        }
+        nodes
+    }
+
+    /// Moves of the mainline
+    pub fn mainline(&self) -> Vec<Move> {
+        self.mainline_nodes()
+            .into_iter()
+            .map(|node| node.mov.clone())
+            .collect()
+    }
+
+    /// Set a tag, keeping its place when it is already present
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Set the game termination marker along with the `Result` tag
+    pub fn set_result(&mut self, result: &str) {
+        self.result = result.to_string();
+        self.set_tag("Result", result);
+    }
+
+    /// Position at the end of the mainline
+    pub fn mainline_board(&self) -> Board {
+        self.mainline_nodes()
+            .into_iter()
+            .fold(self.board.clone(), |board, node| {
+                board.apply(node.mov.clone()).unwrap()
+            })
+    }
+
+    /// Append a move to the mainline, returning its node, or `None` when it is illegal
+    pub fn push(&mut self, mov: Move) -> Option<&mut GameNode> {
+        let board = self.mainline_board();
+        let mov = MoveGenerator::new()
+            .legal_moves(&board)
+            .into_iter()
+            .find(|legal| {
+                legal
+                    .to_algebraic()
+                    .eq_ignore_ascii_case(&mov.to_algebraic())
+            })?;
+
+        let mut line = &mut self.moves;
+        while !line.is_empty() {
+            line = &mut line[0].children;
+        }
+        line.push(GameNode::new(mov, &board));
+        line.last_mut()
+    }
+}
+
+/// `[%eval]` comment command of a score from white's point of view
+pub fn eval_annotation(score: Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("[%eval {:.2}]", cp as f64 / 100.0),
+        Score::Mate(moves) => format!("[%eval #{moves}]"),
+    }
+}
+
+fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    let (hours, minutes, seconds) = (tenths / 36_000, tenths / 600 % 60, tenths / 10 % 60);
+    match tenths % 10 {
+        0 => format!("{hours}:{minutes:02}:{seconds:02}"),
+        tenth => format!("{hours}:{minutes:02}:{seconds:02}.{tenth}"),
+    }
+}
+
+/// `[%clk]` comment command of the time left on a clock
+pub fn clock_annotation(time: Duration) -> String {
+    format!("[%clk {}]", format_time(time))
+}
+
+/// `[%emt]` comment command of the time spent on a move
+pub fn elapsed_annotation(time: Duration) -> String {
+    format!("[%emt {}]", format_time(time))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    match &words[..] {
+        [] => tokens.push("{}".to_string()),
+        [word] => tokens.push(format!("{{{word}}}")),
+        [first, middle @ .., last] => {
+            tokens.push(format!("{{{first}"));
+            tokens.extend(middle.iter().map(|word| word.to_string()));
+            tokens.push(format!("{last}}}"));
+        }
+    }
+}
+
+/// Tokens of a move, numbered when white plays it or when `number` is set
+fn push_node(tokens: &mut Vec<String>, node: &GameNode, board: &Board, number: bool) {
+    for comment in &node.starting_comments {
+        push_comment(tokens, comment);
+    }
+    let full_move = board.get_full_move_clock();
+    let san = node.mov.to_san(board);
+    tokens.push(match board.get_turn() {
+        Side::White => format!("{full_move}. {san}"),
+        Side::Black if number || !node.starting_comments.is_empty() => {
+            format!("{full_move}... {san}")
+        }
+        Side::Black => san,
+    });
+    tokens.extend(node.nags.iter().map(|nag| format!("${nag}")));
+    for comment in &node.comments {
+        push_comment(tokens, comment);
+    }
+}
+
+/// Tokens of the moves in `nodes` and what follows them, the first being the main
+/// continuation and the others its variations
+fn push_moves(tokens: &mut Vec<String>, nodes: &[GameNode], board: &Board, number: bool) {
+    let Some((main, variations)) = nodes.split_first() else {
+        return;
+    };
+
+    push_node(tokens, main, board, number);
+    for variation in variations {
+        tokens.push("(".to_string());
+        push_node(tokens, variation, board, true);
+        let after = board.apply(variation.mov.clone()).unwrap();
+        let number = !variation.comments.is_empty();
+        push_moves(tokens, &variation.children, &after, number);
+        tokens.push(")".to_string());
+    }
+
+    let after = board.apply(main.mov.clone()).unwrap();
+    let number = !variations.is_empty() || !main.comments.is_empty();
+    push_moves(tokens, &main.children, &after, number);
+}
+
+impl fmt::Display for PgnGame {
+    /// Export format: the seven tag roster first, then the position tags and the rest, with
+    /// the movetext wrapped before the 80th column
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut tags: Vec<(&str, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&tag| {
+                let value = match tag {
+                    "Result" => self.result.clone(),
+                    _ => self.get_tag(tag).unwrap_or("?").to_string(),
+                };
+                (tag, value)
+            })
+            .collect();
+        let fen = self.board.to_fen();
+        if fen != STARTPOS {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", fen));
+        }
+        tags.extend(
+            self.tags
+                .iter()
+                .filter(|(tag, _)| {
+                    !SEVEN_TAG_ROSTER.contains(&tag.as_str()) && tag != "SetUp" && tag != "FEN"
+                })
+                .map(|(tag, value)| (tag.as_str(), value.clone())),
+        );
+        for (tag, value) in tags {
+            writeln!(f, "[{tag} \"{}\"]", escape(&value))?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        push_moves(&mut tokens, &self.moves, &self.board, true);
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            let separator = !line.is_empty() && !line.ends_with('(') && token != ")";
+            if !line.is_empty() && line.len() + separator as usize + token.len() >= LINE_WIDTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            } else if separator {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Open,
+    Close,
+    Nag(u8),
+    MoveNumber,
+    Result(String),
+    Symbol(String),
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Annotation symbols written after a move and their NAG
+const SUFFIXES: [(&str, u8); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+/// Splits the input into tokens, keeping track of their position
+struct Lexer<R: BufRead> {
+    reader: R,
+    line: Vec<char>,
+    position: usize,
+    line_number: usize,
+    peeked: Option<(Token, usize, usize)>,
+    /// Move written together with its number, read after the number
+    pending: Option<(Token, usize, usize)>,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn new(reader: R) -> Lexer<R> {
+        Lexer {
+            reader,
+            line: vec![],
+            position: 0,
+            line_number: 0,
+            peeked: None,
+            pending: None,
+        }
+    }
+
+    fn error(&self, column: usize, message: String) -> PgnError {
+        PgnError {
+            line: self.line_number,
+            column,
+            message,
+        }
+    }
+
+    /// Move on to the next line, returning false at the end of the input
+    fn next_line(&mut self) -> Result<bool, PgnError> {
+        let mut line = String::new();
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .map_err(|err| self.error(1, err.to_string()))?;
+        self.line_number += 1;
+        self.line = line.trim_end_matches(['\n', '\r']).chars().collect();
+        self.position = 0;
+        // lines starting with % are escaped from the PGN data
+        if self.line.first() == Some(&'%') {
+            self.line.clear();
+        }
+        Ok(read > 0)
+    }
+
+    fn peek(&mut self) -> Result<Option<&(Token, usize, usize)>, PgnError> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    /// Next token with its line and column
+    fn next(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.read_token(),
+        }
+    }
+
+    fn read_token(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        if let Some(token) = self.pending.take() {
+            return Ok(Some(token));
+        }
+        loop {
+            while self.position < self.line.len() && self.line[self.position].is_whitespace() {
+                self.position += 1;
+            }
+            if self.position < self.line.len() {
+                break;
+            }
+            if !self.next_line()? {
+                return Ok(None);
+            }
+        }
+
+        let (line, column) = (self.line_number, self.position + 1);
+        let c = self.line[self.position];
+        self.position += 1;
+        let token = match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => self.read_tag(column)?,
+            ';' => {
+                let comment: String = self.line[self.position..].iter().collect();
+                self.position = self.line.len();
+                Token::Comment(comment.trim().to_string())
+            }
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match self.line[self.position..].iter().position(|&c| c == '}') {
+                        Some(end) => {
+                            comment.extend(&self.line[self.position..self.position + end]);
+                            self.position += end + 1;
+                            break;
+                        }
+                        None => {
+                            comment.extend(&self.line[self.position..]);
+                            comment.push(' ');
+                            if !self.next_line()? {
+                                return Err(PgnError {
+                                    line,
+                                    column,
+                                    message: "unterminated comment".to_string(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Token::Comment(comment.trim().to_string())
+            }
+            '$' => {
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                let nag = digits
+                    .parse()
+                    .map_err(|_| self.error(column, format!("invalid NAG ${digits}")))?;
+                Token::Nag(nag)
+            }
+            c if c.is_ascii_alphanumeric() || c == '*' => {
+                self.position -= 1;
+                let symbol =
+                    self.take_while(|c| c.is_ascii_alphanumeric() || "-+#=:/.!?_*".contains(c));
+                if RESULTS.contains(&symbol.as_str()) {
+                    Token::Result(symbol)
+                } else if symbol.starts_with(|c: char| c.is_ascii_digit()) && symbol.contains('.') {
+                    // a move number, possibly written together with the move
+                    let san = symbol.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                    if san.is_empty() {
+                        Token::MoveNumber
+                    } else {
+                        let san_column = column + symbol.len() - san.len();
+                        self.pending = Some((Token::Symbol(san.to_string()), line, san_column));
+                        return Ok(Some((Token::MoveNumber, line, column)));
+                    }
+                } else {
+                    Token::Symbol(symbol)
+                }
+            }
+            c => return Err(self.error(column, format!("unexpected character {c}"))),
+        };
+
+        Ok(Some((token, line, column)))
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> String {
+        let start = self.position;
+        while self.position < self.line.len() && predicate(self.line[self.position]) {
+            self.position += 1;
+        }
+        self.line[start..self.position].iter().collect()
+    }
+
+    /// Tag pair after its opening bracket: a name, a quoted value and a closing bracket
+    fn read_tag(&mut self, column: usize) -> Result<Token, PgnError> {
+        let invalid = |lexer: &Self| lexer.error(column, "invalid tag pair".to_string());
+        let skip_spaces = |lexer: &mut Self| {
+            lexer.take_while(char::is_whitespace);
+        };
+
+        skip_spaces(self);
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        skip_spaces(self);
+        if name.is_empty() || self.line.get(self.position) != Some(&'"') {
+            return Err(invalid(self));
+        }
+        self.position += 1;
+
+        let mut value = String::new();
+        loop {
+            match self.line.get(self.position) {
+                Some('\\') => {
+                    value.extend(self.line.get(self.position + 1));
+                    self.position += 2;
+                }
+                Some('"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(&c) => {
+                    value.push(c);
+                    self.position += 1;
+                }
+                None => return Err(invalid(self)),
+            }
+        }
+
+        skip_spaces(self);
+        if self.line.get(self.position) != Some(&']') {
+            return Err(invalid(self));
+        }
+        self.position += 1;
+        Ok(Token::Tag(name, value))
+    }
+}
+
+/// Move read from the movetext, with the variations replacing it
+struct ParsedMove {
+    node: GameNode,
+    variations: Vec<Vec<ParsedMove>>,
+}
+
+/// Game tree of a line of moves: its first move followed by the rest of the line, then the
+/// variations of that first move
+fn build_tree(line: Vec<ParsedMove>) -> Vec<GameNode> {
+    let mut line = line.into_iter();
+    let Some(ParsedMove {
+        mut node,
+        variations,
+    }) = line.next()
+    else {
+        return vec![];
+    };
+
+    node.children = build_tree(line.collect());
+    let mut nodes = vec![node];
+    for variation in variations {
+        nodes.extend(build_tree(variation));
+    }
+    nodes
+}
+
+/// Games of a PGN input, read one at a time
+pub struct PgnReader<R: BufRead> {
+    lexer: Lexer<R>,
+}
+
+impl PgnReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(filename: P) -> io::Result<PgnReader<BufReader<File>>> {
+        Ok(PgnReader::new(BufReader::new(File::open(filename)?)))
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            lexer: Lexer::new(reader),
+        }
+    }
+
+    /// Moves of a line played from `board` until its end, given by a closing parenthesis in a
+    /// variation or by the result or the next game in the mainline
+    fn read_line(
+        &mut self,
+        board: &Board,
+        variation: bool,
+    ) -> Result<(Vec<ParsedMove>, Option<String>), PgnError> {
+        let mut line: Vec<ParsedMove> = vec![];
+        let mut boards = vec![board.clone()];
+        let mut comments = vec![];
+
+        loop {
+            let Some((token, line_number, column)) = self.lexer.peek()?.cloned() else {
+                if variation {
+                    return Err(self.lexer.error(1, "unterminated variation".to_string()));
+                }
+                return Ok((line, None));
+            };
+            let error = |message: String| PgnError {
+                line: line_number,
+                column,
+                message,
+            };
+            if let Token::Tag(..) = token {
+                if variation {
+                    return Err(error("unterminated variation".to_string()));
+                }
+                // the next game starts without a result for this one
+                return Ok((line, None));
+            }
+            self.lexer.next()?;
+
+            match token {
+                Token::Tag(..) | Token::MoveNumber => {}
+                Token::Comment(comment) => match line.last_mut() {
+                    Some(last) => last.node.comments.push(comment),
+                    None => comments.push(comment),
+                },
+                Token::Nag(nag) => match line.last_mut() {
+                    Some(last) => last.node.nags.push(nag),
+                    None => return Err(error(format!("${nag} before any move"))),
+                },
+                Token::Open => {
+                    if line.is_empty() {
+                        return Err(error("variation before any move".to_string()));
+                    }
+                    let before = &boards[boards.len() - 2];
+                    let (moves, _) = self.read_line(&before.clone(), true)?;
+                    line.last_mut().unwrap().variations.push(moves);
+                }
+                Token::Close if variation => return Ok((line, None)),
+                Token::Close => return Err(error("unexpected )".to_string())),
+                Token::Result(_) if variation => {
+                    return Err(error("result inside a variation".to_string()))
+                }
+                Token::Result(result) => return Ok((line, Some(result))),
+                Token::Symbol(symbol) => {
+                    let position = boards.last().unwrap();
+                    let mut san = symbol.as_str();
+                    let mut nags = vec![];
+                    if let Some((suffix, nag)) =
+                        SUFFIXES.iter().find(|(suffix, _)| san.ends_with(suffix))
+                    {
+                        san = &san[..san.len() - suffix.len()];
+                        nags.push(*nag);
+                    }
+
+                    let mov = Move::from_san(san, position)
+                        .ok_or_else(|| error(format!("illegal move {symbol}")))?;
+                    let next = position.apply(mov.clone()).unwrap();
+                    boards.push(next);
+                    line.push(ParsedMove {
+                        node: GameNode {
+                            mov,
+                            san: san.to_string(),
+                            starting_comments: std::mem::take(&mut comments),
+                            comments: vec![],
+                            nags,
+                            children: vec![],
+                        },
+                        variations: vec![],
+                    });
+                }
+            }
+        }
+    }
+
+    /// Skip the rest of a game after an error, up to its result
+    fn recover(&mut self) {
+        loop {
+            match self.lexer.next() {
+                Ok(Some((Token::Result(_), _, _))) | Ok(None) => return,
+                Ok(Some(_)) => {}
+                // lexer errors are skipped along with the rest of the line
+                Err(_) => self.lexer.position = self.lexer.line.len(),
+            }
+        }
+    }
+
+    fn read_game(&mut self) -> Result<Option<PgnGame>, PgnError> {
+        let mut tags = vec![];
+        let mut board = Board::from_fen(STARTPOS);
+        loop {
+            match self.lexer.peek()?.cloned() {
+                None if tags.is_empty() => return Ok(None),
+                Some((Token::Tag(name, value), line, column)) => {
+                    self.lexer.next()?;
+                    if name == "FEN" {
+                        board = Board::try_from_fen(&value).ok_or_else(|| PgnError {
+                            line,
+                            column,
+                            message: format!("invalid FEN {value}"),
+                        })?;
+                    }
+                    tags.push((name, value));
+                }
+                _ => break,
+            }
+        }
+
+        let (line, result) = self.read_line(&board, false)?;
+        Ok(Some(PgnGame {
+            tags,
+            board,
+            moves: build_tree(line),
+            result: result.unwrap_or_else(|| "*".to_string()),
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_game() {
+            Ok(game) => game.map(Ok),
+            Err(err) => {
+                self.recover();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Every game of a PGN text
+pub fn read_games(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    PgnReader::new(text.as_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "\"Anon\""]
+
+{Opening comment} 1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.}
+3... a6 $1 (3... Nf6 4. O-O (4. d3) 4... Nxe4) ; rest of line
+4. Ba4!? Nf6 1/2-1/2
+
+% escaped line
+[Event "Second"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1.e4 Kd7 *
+"#;
+
+    fn algebraic(moves: &[Move]) -> Vec<String> {
+        moves.iter().map(|mov| mov.to_algebraic()).collect()
+    }
+
+    #[test]
+    fn test_read_games() {
+        let games = read_games(GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let game = &games[0];
+        for tag in SEVEN_TAG_ROSTER {
+            assert!(game.get_tag(tag).is_some());
+        }
+        assert_eq!(game.get_tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.get_tag("Annotator"), Some("\"Anon\""));
+        assert_eq!(game.result, "1/2-1/2");
+        assert_eq!(
+            algebraic(&game.mainline()),
+            ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6"]
+        );
+
+        let nodes = game.mainline_nodes();
+        assert_eq!(nodes[0].starting_comments, ["Opening comment"]);
+        assert_eq!(nodes[4].comments, ["This opening is called the Ruy Lopez."]);
+        assert_eq!(nodes[5].nags, [1]);
+        assert_eq!(nodes[6].nags, [5]);
+        assert_eq!(nodes[6].san, "Ba4");
+
+        // 3... Nf6 is a variation of 3... a6, with 4. d3 a variation of 4. O-O
+        let bb5 = nodes[4];
+        assert_eq!(bb5.children.len(), 2);
+        let nf6 = &bb5.children[1];
+        assert_eq!(nf6.san, "Nf6");
+        assert_eq!(nf6.children[0].mov.to_algebraic(), "e1g1");
+        assert_eq!(nf6.children[0].comments, Vec::<String>::new());
+        assert_eq!(nf6.children[1].san, "d3");
+        assert_eq!(nf6.children[0].children[0].san, "Nxe4");
+        assert_eq!(nodes[5].comments, ["rest of line"]);
+
+        let game = &games[1];
+        assert_eq!(
+            game.board,
+            Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")
+        );
+        assert_eq!(algebraic(&game.mainline()), ["e2e4", "e8d7"]);
+        assert_eq!(game.result, "*");
+    }
+
+    #[test]
+    fn test_games_without_results() {
+        let games = read_games("[Event \"a\"]\n\n1. d4\n[Event \"b\"]\n\n1. c4\n").unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result, "*");
+        assert_eq!(algebraic(&games[1].mainline()), ["c2c4"]);
+        assert_eq!(read_games("").unwrap(), vec![]);
+    }
+
+    #[rstest]
+    #[case("1. e4 e6 2. Ke3 *", 1, 13, "illegal move Ke3")]
+    #[case("1.e4 e6 2.Ke3 *", 1, 11, "illegal move Ke3")]
+    #[case("[Event \"x]\n1. e4 *", 1, 1, "invalid tag pair")]
+    #[case("1. e4 {unfinished\n\n", 1, 7, "unterminated comment")]
+    #[case("1. e4 e5 (1... c5 *", 1, 19, "result inside a variation")]
+    #[case("1. e4 )", 1, 7, "unexpected )")]
+    #[case("\n1. e4 & *", 2, 7, "unexpected character &")]
+    #[case("[FEN \"8/8/8 w\"]\n*", 1, 1, "invalid FEN 8/8/8 w")]
+    fn test_errors(
+        #[case] pgn: &str,
+        #[case] line: usize,
+        #[case] column: usize,
+        #[case] message: &str,
+    ) {
+        assert_eq!(
+            read_games(pgn),
+            Err(PgnError {
+                line,
+                column,
+                message: message.to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_round_trips() {
+        for game in read_games(GAMES).unwrap() {
+            let text = game.to_string();
+            let read = read_games(&text).unwrap();
+            assert_eq!(read.len(), 1);
+            assert_eq!(read[0].board, game.board);
+            assert_eq!(read[0].moves, game.moves);
+            assert_eq!(read[0].result, game.result);
+            assert_eq!(read[0].to_string(), text);
+        }
+
+        let text = read_games(GAMES).unwrap()[0].to_string();
+        assert!(text.starts_with("[Event \"F/S Return Match\"]\n[Site "));
+        assert!(text.contains("[Result \"1/2-1/2\"]\n[Annotator \"\\\"Anon\\\"\"]\n\n"));
+        assert!(text
+            .contains("3... a6 $1 {rest of line} (3... Nf6 4. O-O (4. d3) 4... Nxe4) 4. Ba4 $5"));
+    }
+
+    #[test]
+    fn test_write_game_from_position() {
+        let mut game = PgnGame::new(Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 30"));
+        game.set_tag("White", "fchess");
+        game.set_tag("Opening", "none");
+        game.push(Move::from_full_algebraic("g8h8").unwrap())
+            .unwrap()
+            .comments
+            .push(clock_annotation(Duration::from_millis(61_500)));
+        let node = game
+            .push(Move::from_full_algebraic("a1a8").unwrap())
+            .unwrap();
+        node.comments.push(eval_annotation(Score::Mate(1)));
+        node.nags.push(1);
+        assert!(game
+            .push(Move::from_full_algebraic("h8h7").unwrap())
+            .is_none());
+        game.set_result("1-0");
+
+        assert_eq!(
+            game.to_string(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"fchess\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[SetUp \"1\"]\n\
+             [FEN \"6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 30\"]\n[Opening \"none\"]\n\n\
+             30... Kh8 {[%clk 0:01:01.5]} 31. Ra8# $1 {[%eval #1]} 1-0\n"
+        );
+    }
+
+    #[test]
+    fn test_long_game() {
+        let text = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 255\"]\n\n\
+                    255. Kd2 Kd7 256. Ke3 Ke7 *\n";
+        let game = &read_games(text).unwrap()[0];
+        assert_eq!(game.mainline_board().get_full_move_clock(), 257);
+        assert!(game.to_string().contains("\n\n255. Kd2 Kd7 256. Ke3 Ke7 *"));
+    }
+
+    #[test]
+    fn test_write_wraps_lines() {
+        let mut game = PgnGame::new(Board::from_fen(STARTPOS));
+        for mov in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(60) {
+            game.push(Move::from_full_algebraic(mov).unwrap())
+                .unwrap()
+                .comments
+                .push(eval_annotation(Score::Centipawns(-12)));
+        }
+        let text = game.to_string();
+
+        assert!(text.lines().all(|line| line.len() < LINE_WIDTH));
+        assert!(text.lines().filter(|line| line.len() > 70).count() > 5);
+        assert!(text.contains("1. Nf3 {[%eval -0.12]} 1... Nf6 {[%eval -0.12]} 2. Ng1"));
+        assert_eq!(read_games(&text).unwrap()[0].moves, game.moves);
+    }
+
+    #[rstest]
+    #[case(Duration::from_secs(3 * 3600 + 5), "[%clk 3:00:05]")]
+    #[case(Duration::from_millis(9_849), "[%clk 0:00:09.8]")]
+    fn test_clock_annotation(#[case] time: Duration, #[case] expected: &str) {
+        assert_eq!(clock_annotation(time), expected);
+    }
+
+    #[test]
+    fn test_reader_continues_after_errors() {
+        let pgn = "1. e4 e5 2. Qxf7 *\n\n1. d4 d5 *\n";
+        let games: Vec<Result<PgnGame, PgnError>> = PgnReader::new(pgn.as_bytes()).collect();
+
+        assert_eq!(games.len(), 2);
+        assert!(games[0].is_err());
+        assert_eq!(
+            algebraic(&games[1].as_ref().unwrap().mainline()),
+            ["d2d4", "d7d5"]
+        );
+    }
+}
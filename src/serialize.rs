@@ -0,0 +1,163 @@
+//! Serde support, enabled by the `serde` feature. Positions and moves are written in the text
+//! notations chess software already uses: a board as FEN, a move as UCI text, a square in
+//! algebraic notation and a piece as its FEN letter.
+
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::board::Board;
+use crate::moves::Move;
+use crate::piece::PieceType;
+use crate::square::Square;
+use crate::uci::move_to_uci;
+
+/// Value parsed from a string by `parse`, `what` naming it in the error
+fn deserialize_text<'de, D, T, F>(deserializer: D, what: &str, parse: F) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    F: FnOnce(&str) -> Option<T>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse(&text).ok_or_else(|| de::Error::custom(format!("invalid {what} {text}")))
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        deserialize_text(deserializer, "fen", Board::try_from_fen)
+    }
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&move_to_uci(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        deserialize_text(deserializer, "move", |text| {
+            // a fifth character must name a promotion piece
+            Move::from_full_algebraic(text)
+                .filter(|mov| text.len() == 4 || mov.get_promotion().is_some())
+        })
+    }
+}
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_algebraic())
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        deserialize_text(deserializer, "square", Square::from_algebraic)
+    }
+}
+
+impl Serialize for PieceType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if *self as usize >= PieceType::Marker as usize {
+            return Err(serde::ser::Error::custom(format!(
+                "{self:?} is not a piece"
+            )));
+        }
+        serializer.serialize_char(self.to_char())
+    }
+}
+
+impl<'de> Deserialize<'de> for PieceType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PieceType, D::Error> {
+        deserialize_text(deserializer, "piece", |text| {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => PieceType::from_string(&c),
+                _ => None,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::board::STARTPOS;
+
+    #[rstest]
+    #[case(STARTPOS)]
+    #[case("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 3 40")]
+    fn test_board_serde(#[case] fen: &str) {
+        let board = Board::from_fen(fen);
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, format!("\"{fen}\""));
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+    }
+
+    #[rstest]
+    #[case("e2e4")]
+    #[case("e7e8q")]
+    #[case("a2a1n")]
+    fn test_move_serde(#[case] text: &str) {
+        let mov = Move::from_full_algebraic(text).unwrap();
+        let json = serde_json::to_string(&mov).unwrap();
+        assert_eq!(json, format!("\"{text}\""));
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), mov);
+    }
+
+    #[test]
+    fn test_square_and_piece_serde() {
+        let square = Square::from_algebraic("g7").unwrap();
+        assert_eq!(serde_json::to_string(&square).unwrap(), "\"g7\"");
+        assert_eq!(serde_json::from_str::<Square>("\"g7\"").unwrap(), square);
+
+        assert_eq!(
+            serde_json::to_string(&PieceType::BlackKnight).unwrap(),
+            "\"n\""
+        );
+        assert_eq!(
+            serde_json::from_str::<PieceType>("\"Q\"").unwrap(),
+            PieceType::WhiteQueen
+        );
+        assert!(serde_json::to_string(&PieceType::NoPiece).is_err());
+    }
+
+    #[rstest]
+    #[case("\"8/8/8/8/8/8/8/8 w - - 0 1\"")]
+    #[case("\"e2e9\"")]
+    #[case("\"e7e8x\"")]
+    #[case("\"i1\"")]
+    #[case("\"a0\"")]
+    #[case("\"Qq\"")]
+    #[case("42")]
+    fn test_serde_rejects(#[case] json: &str) {
+        assert!(serde_json::from_str::<Board>(json).is_err());
+        assert!(serde_json::from_str::<Move>(json).is_err());
+        assert!(serde_json::from_str::<Square>(json).is_err());
+        assert!(serde_json::from_str::<PieceType>(json).is_err());
+    }
+
+    #[test]
+    fn test_serde_in_yaml() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct TestCase {
+            board: Board,
+            moves: Vec<Move>,
+        }
+
+        let case = TestCase {
+            board: Board::from_fen(STARTPOS),
+            moves: vec![Move::from_full_algebraic("g1f3").unwrap()],
+        };
+        let yaml = serde_yaml::to_string(&case).unwrap();
+        assert!(yaml.contains("- g1f3"));
+        assert_eq!(serde_yaml::from_str::<TestCase>(&yaml).unwrap(), case);
+    }
+}
@@ -1,61 +1,860 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
 use crate::board::Board;
+use crate::evaluator::{ClassicalEvaluator, Evaluator};
 use crate::move_generator::MoveGenerator;
 use crate::moves::Move;
+use crate::piece::PieceType;
+use crate::side::Side;
+use crate::tablebase::{Outcome, Tablebases};
+use crate::transposition::{Bound, TranspositionTable};
+
+pub(crate) const MATE_SCORE: i32 = 100_000;
+/// Scores beyond this are mates, with the distance to mate encoded in plies
+pub(crate) const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+/// Depth in plies searched when no limit is given
+const DEFAULT_DEPTH: u8 = 4;
+const MAX_DEPTH: u8 = 64;
+/// Moves assumed to be left in the game when the time control does not say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// Time kept aside for communication delays
+const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+/// Entries of the history table, indexed by side, source and destination square
+const HISTORY_SIZE: usize = 2 * 64 * 64;
+/// Search time after which the root move being searched is reported
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Limits of a search, as given by the UCI `go` command
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Search for a mate in that many moves
+    pub mate: Option<u8>,
+    pub movetime: Option<Duration>,
+    /// Search until stopped
+    pub infinite: bool,
+    /// Only consider these root moves
+    pub searchmoves: Vec<Move>,
+    /// Search on the opponent's time until stopped
+    pub ponder: bool,
+}
+
+impl SearchLimits {
+    pub fn with_depth(depth: u8) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    /// Time to spend on a move of `side` keeping `overhead` on the clock, `None` when the
+    /// search is not timed
+    pub fn time_budget(&self, side: Side, overhead: Duration) -> Option<Duration> {
+        if self.movetime.is_some() {
+            return self.movetime;
+        }
+
+        let (time, increment) = match side {
+            Side::White => (self.wtime?, self.winc.unwrap_or_default()),
+            Side::Black => (self.btime?, self.binc.unwrap_or_default()),
+        };
+        let moves = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = time / moves + increment * 3 / 4;
+        Some(budget.min(time.saturating_sub(overhead)))
+    }
+}
+
+/// Score of a search, from the point of view of the side to move
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate, negative when the side to move is getting mated
+    Mate(i32),
+}
+
+impl Score {
+    fn from_value(value: i32) -> Score {
+        if value >= MATE_THRESHOLD {
+            Score::Mate((MATE_SCORE - value) / 2)
+        } else if value <= -MATE_THRESHOLD {
+            Score::Mate(-(MATE_SCORE + value) / 2)
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+}
+
+/// Progress of a running search
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchInfo {
+    /// A completed iteration of the iterative deepening
+    Iteration {
+        depth: u8,
+        /// Deepest ply reached
+        seldepth: u8,
+        /// Rank of the line among the best root moves, from 1
+        multipv: usize,
+        score: Score,
+        nodes: u64,
+        time: Duration,
+        /// Permille of the transposition table in use
+        hashfull: u16,
+        pv: Vec<Move>,
+    },
+    /// A root move about to be searched, numbered from 1
+    CurrentMove { depth: u8, mov: Move, number: usize },
+}
 
-#[derive(Clone, Copy, Debug, Default)]
 pub struct Solver {
     move_generator: MoveGenerator,
+    evaluator: Box<dyn Evaluator + Send>,
+    tablebases: Option<Arc<Tablebases>>,
+    transposition_table: Arc<TranspositionTable>,
+    /// Cutoffs caused by quiet moves, weighted by depth, to order moves
+    history: Vec<u32>,
+    stop: Arc<AtomicBool>,
+    ponderhit: Arc<AtomicBool>,
+    /// Time budget applied once a ponder search turns into a normal one
+    ponder_budget: Option<Duration>,
+    pondering: bool,
+    pv: Vec<Move>,
+    threads: usize,
+    multipv: usize,
+    move_overhead: Duration,
+    nodes: u64,
+    seldepth: i32,
+    max_nodes: Option<u64>,
+    start: Instant,
+    time_budget: Option<Duration>,
+    /// Search time at which the time budget started running, the ponderhit time when pondering
+    budget_start: Duration,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Solver {
     pub fn new() -> Solver {
+        Solver::with_evaluator(Box::new(ClassicalEvaluator::new()))
+    }
+
+    pub fn with_evaluator(evaluator: Box<dyn Evaluator + Send>) -> Solver {
         Solver {
             move_generator: MoveGenerator::new(),
+            evaluator,
+            tablebases: None,
+            transposition_table: Arc::new(TranspositionTable::default()),
+            history: vec![0; HISTORY_SIZE],
+            stop: Arc::new(AtomicBool::new(false)),
+            ponderhit: Arc::new(AtomicBool::new(false)),
+            ponder_budget: None,
+            pondering: false,
+            pv: vec![],
+            threads: 1,
+            multipv: 1,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            nodes: 0,
+            seldepth: 0,
+            max_nodes: None,
+            start: Instant::now(),
+            time_budget: None,
+            budget_start: Duration::ZERO,
         }
     }
 
-    pub fn best_move(&self, board: &Board) -> Option<Move> {
-        let mut best = None;
-        let mut score = -500.0;
-
-        let mut evals = 0;
-        for piece in self.move_generator.generate_moves(board) {
-            for mov in piece.into_iter() {
-                let b = board.apply(mov.clone())?;
-                let (sc, min_max_evals) = self.min_max(&b, 3).unwrap();
-                if score < sc {
-                    best = Some(mov);
-                    score = sc;
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator + Send>) {
+        self.evaluator = evaluator;
+    }
+
+    /// Flag that aborts the running search when set, from any thread
+    pub fn get_stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Flag that turns a running ponder search into a normal timed search when set
+    pub fn get_ponderhit_flag(&self) -> Arc<AtomicBool> {
+        self.ponderhit.clone()
+    }
+
+    /// Principal variation of the last completed iteration
+    pub fn get_pv(&self) -> &[Move] {
+        &self.pv
+    }
+
+    /// Expected reply to `best`, taken from the principal variation or the transposition
+    /// table
+    pub fn get_ponder_move(&self, board: &Board, best: &Move) -> Option<Move> {
+        let child = board.apply(best.clone())?;
+        let mov = match self.pv.as_slice() {
+            [first, reply, ..] if first == best => reply.clone(),
+            _ => {
+                let key = child.zobryst_hash(&child.get_turn());
+                self.transposition_table.probe(key)?.get_move()?.clone()
+            }
+        };
+
+        self.move_generator
+            .legal_moves(&child)
+            .contains(&mov)
+            .then_some(mov)
+    }
+
+    /// Forget what was learned during the previous game
+    pub fn new_game(&mut self) {
+        self.transposition_table.clear();
+        self.history.iter_mut().for_each(|entry| *entry = 0);
+    }
+
+    /// Nodes visited by the last search, over all threads
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn set_tablebases(&mut self, tablebases: Option<Tablebases>) {
+        self.tablebases = tablebases.map(Arc::new);
+    }
+
+    /// Replace the transposition table by an empty one of about `megabytes`
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.transposition_table = Arc::new(TranspositionTable::with_megabytes(megabytes));
+    }
+
+    /// Number of threads searching, sharing the transposition table
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Number of best root moves reported with their principal variation
+    pub fn set_multipv(&mut self, multipv: usize) {
+        self.multipv = multipv.max(1);
+    }
+
+    /// Time kept aside from the clock for communication delays
+    pub fn set_move_overhead(&mut self, move_overhead: Duration) {
+        self.move_overhead = move_overhead;
+    }
+
+    /// Solver for a helper thread, stopped by `stop` and sharing the transposition table
+    fn helper(&self, stop: Arc<AtomicBool>) -> Solver {
+        Solver {
+            move_generator: self.move_generator,
+            evaluator: self.evaluator.boxed_clone(),
+            tablebases: self.tablebases.clone(),
+            transposition_table: self.transposition_table.clone(),
+            history: vec![0; HISTORY_SIZE],
+            stop,
+            ponderhit: Arc::new(AtomicBool::new(false)),
+            ponder_budget: None,
+            pondering: false,
+            pv: vec![],
+            threads: 1,
+            multipv: 1,
+            move_overhead: self.move_overhead,
+            nodes: 0,
+            seldepth: 0,
+            max_nodes: None,
+            start: Instant::now(),
+            time_budget: None,
+            budget_start: Duration::ZERO,
+        }
+    }
+
+    /// Score of a tablebase result `ply` plies from the root, on the same scale as the mates
+    /// found by the search, where the king is captured one ply after the mate
+    fn tablebase_score(outcome: Outcome, ply: i32) -> i32 {
+        match outcome {
+            Outcome::Win(plies) => MATE_SCORE - ply - plies as i32 - 2,
+            Outcome::Draw => 0,
+            Outcome::Loss(plies) => -MATE_SCORE + ply + plies as i32,
+        }
+    }
+
+    pub fn best_move(&mut self, board: &Board) -> Option<Move> {
+        self.search(board, &SearchLimits::default())
+    }
+
+    pub fn search(&mut self, board: &Board, limits: &SearchLimits) -> Option<Move> {
+        self.search_with_progress(board, limits, |_| {})
+    }
+
+    /// Iterative deepening search within `limits`. Without a depth, node or time limit the
+    /// search is `DEFAULT_DEPTH` plies deep, unless it is infinite or pondering.
+    /// `progress` is called after each iteration and, once the search has run for a while,
+    /// before each root move. A tablebase hit at the root is reported as one iteration.
+    pub fn search_with_progress<F>(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        mut progress: F,
+    ) -> Option<Move>
+    where
+        F: FnMut(&SearchInfo),
+    {
+        let mut moves = self.move_generator.legal_moves(board);
+        if !limits.searchmoves.is_empty() {
+            moves.retain(|mov| limits.searchmoves.contains(mov));
+        }
+        if moves.is_empty() {
+            return None;
+        }
+
+        if let Some((outcome, mov)) = self
+            .tablebases
+            .as_ref()
+            .and_then(|tablebases| Some((tablebases.probe(board)?, tablebases.best_move(board)?)))
+            .filter(|(_, mov)| moves.contains(mov))
+        {
+            self.nodes = 0;
+            self.pv = vec![mov.clone()];
+            progress(&SearchInfo::Iteration {
+                depth: 1,
+                seldepth: 1,
+                multipv: 1,
+                score: Score::from_value(Solver::tablebase_score(outcome, 0)),
+                nodes: 0,
+                time: Duration::ZERO,
+                hashfull: self.transposition_table.hashfull(),
+                pv: vec![mov.clone()],
+            });
+            return Some(mov);
+        }
+
+        let unlimited = limits.infinite || limits.ponder;
+        self.nodes = 0;
+        self.seldepth = 0;
+        self.pv.clear();
+        self.start = Instant::now();
+        self.max_nodes = limits.nodes;
+        self.pondering = limits.ponder;
+        self.ponder_budget = limits.time_budget(board.get_turn(), self.move_overhead);
+        self.time_budget = if unlimited { None } else { self.ponder_budget };
+        self.budget_start = Duration::ZERO;
+        let max_depth = match (limits.depth, limits.mate) {
+            (Some(depth), _) => depth.clamp(1, MAX_DEPTH),
+            // the king is captured one ply after the mate
+            (None, Some(mate)) => (2 * mate.min(MAX_DEPTH / 2) + 1).min(MAX_DEPTH),
+            _ if unlimited || self.time_budget.is_some() || self.max_nodes.is_some() => MAX_DEPTH,
+            _ => DEFAULT_DEPTH,
+        };
+
+        // lazy SMP: helpers search the same position until this thread is done, filling the
+        // shared transposition table
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        let helpers: Vec<_> = (1..self.threads)
+            .map(|index| {
+                let mut helper = self.helper(helpers_stop.clone());
+                let board = board.clone();
+                let mut moves = moves.clone();
+                // different root move orders send the threads to different parts of the tree
+                let len = moves.len();
+                moves.rotate_left(index % len);
+                thread::spawn(move || {
+                    helper.iterate(&board, moves, MAX_DEPTH, &mut |_| {});
+                    helper.nodes
+                })
+            })
+            .collect();
+
+        let best = self.iterate(board, moves, max_depth, &mut progress);
+
+        helpers_stop.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            self.nodes += helper.join().unwrap_or(0);
+        }
+
+        Some(best)
+    }
+
+    /// Iterative deepening over the root `moves` up to `max_depth`, returning the best move
+    fn iterate<F>(
+        &mut self,
+        board: &Board,
+        mut moves: Vec<Move>,
+        max_depth: u8,
+        progress: &mut F,
+    ) -> Move
+    where
+        F: FnMut(&SearchInfo),
+    {
+        let mut best = moves[0].clone();
+        for depth in 1..=max_depth {
+            let mut aborted = false;
+
+            // each line is the best of the moves not already reported at this depth
+            for line in 0..self.multipv.min(moves.len()) {
+                let (result, stopped) = self.search_root(board, &moves, line, depth, progress);
+                if let Some((index, score, pv)) = result {
+                    // the previous best move is searched first, so a partial iteration is
+                    // still usable
+                    if line == 0 {
+                        best = moves[index].clone();
+                    }
+                    if !stopped {
+                        if line == 0 {
+                            self.pv = pv.clone();
+                        }
+                        let mov = moves.remove(index);
+                        moves.insert(line, mov);
+                        progress(&SearchInfo::Iteration {
+                            depth,
+                            seldepth: self.seldepth as u8,
+                            multipv: line + 1,
+                            score: Score::from_value(score),
+                            nodes: self.nodes,
+                            time: self.start.elapsed(),
+                            hashfull: self.transposition_table.hashfull(),
+                            pv,
+                        });
+                    }
+                }
+                if stopped {
+                    aborted = true;
+                    break;
                 }
-                evals += min_max_evals;
+            }
+            if aborted {
+                break;
+            }
+
+            // the next iteration would not finish in time
+            if self.time_budget.is_some_and(|budget| {
+                self.start.elapsed().saturating_sub(self.budget_start)
+                    >= budget.saturating_sub(self.budget_start) / 2
+            }) {
+                break;
             }
         }
 
-        println!("evaluations: {evals}");
         best
     }
 
-    fn min_max(&self, board: &Board, depth: u8) -> Option<(f32, u32)> {
-        //let mut best = None;
-        let mut score = -500.0;
-        let mut evals = 1;
+    /// Search the root moves from index `first` on at `depth`. Returns the index, score and
+    /// principal variation of the best move searched, and whether the search was stopped.
+    fn search_root<F>(
+        &mut self,
+        board: &Board,
+        moves: &[Move],
+        first: usize,
+        depth: u8,
+        progress: &mut F,
+    ) -> (Option<(usize, i32, Vec<Move>)>, bool)
+    where
+        F: FnMut(&SearchInfo),
+    {
+        let mut alpha = -MATE_SCORE - 1;
+        let mut best = None;
+
+        for (index, mov) in moves.iter().enumerate().skip(first) {
+            let Some(child) = board.apply(mov.clone()) else {
+                continue;
+            };
+            if self.start.elapsed() >= CURRMOVE_DELAY {
+                progress(&SearchInfo::CurrentMove {
+                    depth,
+                    mov: mov.clone(),
+                    number: index + 1,
+                });
+            }
+
+            let mut child_pv = vec![];
+            self.evaluator.push(board, mov, &child);
+            let result =
+                self.alpha_beta(&child, depth - 1, 1, -MATE_SCORE - 1, -alpha, &mut child_pv);
+            self.evaluator.pop();
+
+            match result {
+                Some(score) if -score > alpha => {
+                    alpha = -score;
+                    let mut pv = vec![mov.clone()];
+                    pv.append(&mut child_pv);
+                    best = Some((index, alpha, pv));
+                }
+                Some(_) => {}
+                None => return (best, true),
+            }
+        }
+
+        (best, false)
+    }
+
+    fn history_index(board: &Board, mov: &Move) -> usize {
+        (board.get_turn() as usize * 64 + mov.get_src().get_index() as usize) * 64
+            + mov.get_dst().get_index() as usize
+    }
+
+    fn should_stop(&mut self) -> bool {
+        // the opponent played the expected move, the search now runs on our own clock
+        if self.pondering && self.ponderhit.load(Ordering::Relaxed) {
+            self.pondering = false;
+            let elapsed = self.start.elapsed();
+            self.time_budget = self.ponder_budget.map(|budget| elapsed + budget);
+            self.budget_start = elapsed;
+        }
+
+        self.stop.load(Ordering::Relaxed)
+            || self
+                .max_nodes
+                .is_some_and(|max_nodes| self.nodes > max_nodes)
+            || (self.nodes % 1024 == 0
+                && self
+                    .time_budget
+                    .is_some_and(|budget| self.start.elapsed() >= budget))
+    }
+
+    /// Alpha-beta negamax returning the score from the point of view of the side to move,
+    /// or `None` when the search was stopped. `pv` receives the principal variation.
+    fn alpha_beta(
+        &mut self,
+        board: &Board,
+        depth: u8,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> Option<i32> {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        pv.clear();
+        if self.should_stop() {
+            return None;
+        }
+
+        // the side to move had its king captured
+        if board.checkmate() {
+            return Some(-MATE_SCORE + ply);
+        }
+
+        if let Some(outcome) = self
+            .tablebases
+            .as_ref()
+            .and_then(|tablebases| tablebases.probe(board))
+        {
+            return Some(Solver::tablebase_score(outcome, ply));
+        }
+
+        if depth == 0 {
+            return Some(self.evaluator.evaluate(board));
+        }
 
-        if depth == 0 || board.checkmate() {
-            //return Some(board.eval(), evals));
-            return Some((1.0, evals));
+        let key = board.zobryst_hash(&board.get_turn());
+        let mut hash_move = None;
+        let entry = self.transposition_table.probe(key);
+        if let Some(entry) = entry {
+            hash_move = entry.get_move().cloned();
+            if entry.get_depth() >= depth {
+                let score = entry.get_score(ply);
+                match entry.get_bound() {
+                    Bound::Exact => {
+                        pv.extend(hash_move);
+                        return Some(score.clamp(alpha, beta));
+                    }
+                    Bound::Lower if score >= beta => return Some(beta),
+                    Bound::Upper if score <= alpha => return Some(alpha),
+                    _ => {}
+                }
+            }
         }
 
-        for piece in self.move_generator.generate_moves(board) {
-            for mov in piece.into_iter() {
-                let b = board.apply(mov.clone())?;
-                let sc = self.min_max(&b, depth - 1);
-                if sc.unwrap().0 > score {
-                    score = sc.unwrap().0;
+        let mut moves: Vec<Move> = self
+            .move_generator
+            .generate_moves(board)
+            .iter()
+            .flatten()
+            .collect();
+        moves.sort_by_key(|mov| std::cmp::Reverse(self.history[Solver::history_index(board, mov)]));
+        if let Some(index) =
+            hash_move.and_then(|hash_move| moves.iter().position(|mov| *mov == hash_move))
+        {
+            let mov = moves.remove(index);
+            moves.insert(0, mov);
+        }
+
+        let mut bound = Bound::Upper;
+        let mut best = None;
+        let mut child_pv = vec![];
+        for mov in moves {
+            let Some(child) = board.apply(mov.clone()) else {
+                continue;
+            };
+            self.evaluator.push(board, &mov, &child);
+            let result = self.alpha_beta(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            self.evaluator.pop();
+
+            let score = -result?;
+            if score >= beta {
+                let quiet = mov.get_promotion().is_none()
+                    && board.piece_at(mov.get_dst()) == Some(PieceType::NoPiece);
+                if quiet {
+                    let index = Solver::history_index(board, &mov);
+                    self.history[index] =
+                        self.history[index].saturating_add(depth as u32 * depth as u32);
+                }
+                self.transposition_table
+                    .store(key, depth, ply, beta, Bound::Lower, Some(mov));
+                return Some(beta);
+            }
+            if score > alpha {
+                alpha = score;
+                bound = Bound::Exact;
+                pv.clear();
+                // moves capturing or exposing a king are not part of a legal line
+                if score.abs() < MATE_SCORE - ply - 2 {
+                    pv.push(mov.clone());
+                    pv.append(&mut child_pv);
                 }
-                evals += sc.unwrap().1;
+                best = Some(mov);
             }
         }
 
-        Some((score, evals))
+        self.transposition_table
+            .store(key, depth, ply, alpha, bound, best);
+        Some(alpha)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::tablebase::Material;
+
+    #[rstest]
+    #[case(SearchLimits { movetime: Some(Duration::from_millis(500)), ..Default::default() }, Side::White, 500)]
+    #[case(SearchLimits { wtime: Some(Duration::from_secs(60)), ..Default::default() }, Side::White, 2000)]
+    #[case(SearchLimits { wtime: Some(Duration::from_secs(60)), winc: Some(Duration::from_secs(1)), movestogo: Some(10), ..Default::default() }, Side::White, 6750)]
+    #[case(SearchLimits { wtime: Some(Duration::from_millis(100)), movestogo: Some(1), ..Default::default() }, Side::White, 50)]
+    #[case(SearchLimits { btime: Some(Duration::from_secs(30)), ..Default::default() }, Side::Black, 1000)]
+    fn test_time_budget(#[case] limits: SearchLimits, #[case] side: Side, #[case] millis: u64) {
+        assert_eq!(
+            limits.time_budget(side, DEFAULT_MOVE_OVERHEAD),
+            Some(Duration::from_millis(millis))
+        );
+    }
+
+    #[test]
+    fn test_untimed_search_has_no_budget() {
+        let limits = SearchLimits {
+            wtime: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(limits.time_budget(Side::Black, DEFAULT_MOVE_OVERHEAD), None);
+    }
+
+    #[rstest]
+    #[case("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8")]
+    #[case("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", "a8a1")]
+    fn test_finds_mate_in_one(#[case] fen: &str, #[case] expected: &str) {
+        let mut solver = Solver::new();
+        let mov = solver.search(&Board::from_fen(fen), &SearchLimits::with_depth(3));
+        assert_eq!(mov.unwrap().to_algebraic(), expected);
+    }
+
+    #[rstest]
+    #[case(35, Score::Centipawns(35))]
+    #[case(-MATE_THRESHOLD + 1, Score::Centipawns(-MATE_THRESHOLD + 1))]
+    #[case(MATE_SCORE - 3, Score::Mate(1))]
+    #[case(MATE_SCORE - 7, Score::Mate(3))]
+    #[case(-MATE_SCORE + 4, Score::Mate(-2))]
+    fn test_score_from_value(#[case] value: i32, #[case] expected: Score) {
+        assert_eq!(Score::from_value(value), expected);
+    }
+
+    #[test]
+    fn test_progress_reports_iterations() {
+        let mut solver = Solver::new();
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mut iterations = vec![];
+        solver.search_with_progress(&board, &SearchLimits::with_depth(3), |info| {
+            if let SearchInfo::Iteration {
+                depth, score, pv, ..
+            } = info
+            {
+                iterations.push((*depth, *score, pv.clone()));
+            }
+        });
+
+        assert_eq!(iterations.len(), 3);
+        let (depth, score, pv) = iterations.last().unwrap();
+        assert_eq!(*depth, 3);
+        assert_eq!(*score, Score::Mate(1));
+        let pv: Vec<String> = pv.iter().map(|mov| mov.to_algebraic()).collect();
+        assert_eq!(pv, vec!["a1a8"]);
+    }
+
+    #[rstest]
+    #[case("k7/8/1K6/8/8/8/8/7R w - - 0 1", Score::Mate(1), "h1h8")]
+    #[case("8/8/8/8/8/8/k7/R6K b - - 0 1", Score::Centipawns(0), "a2a1")]
+    fn test_progress_reports_tablebase_hit(
+        #[case] fen: &str,
+        #[case] expected_score: Score,
+        #[case] expected_move: &str,
+    ) {
+        let mut tablebases = Tablebases::new();
+        tablebases.generate(&Material::from_name("KRvK").unwrap());
+        let mut solver = Solver::new();
+        solver.set_tablebases(Some(tablebases));
+
+        let mut iterations = vec![];
+        let best =
+            solver.search_with_progress(&Board::from_fen(fen), &SearchLimits::default(), |info| {
+                if let SearchInfo::Iteration { score, pv, .. } = info {
+                    iterations.push((*score, pv.clone()));
+                }
+            });
+
+        let best = best.unwrap();
+        assert_eq!(best.to_algebraic(), expected_move);
+        assert_eq!(iterations, vec![(expected_score, vec![best])]);
+    }
+
+    #[test]
+    fn test_multipv_reports_distinct_lines() {
+        let mut solver = Solver::new();
+        solver.set_multipv(3);
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mut lines = vec![];
+        let best = solver.search_with_progress(&board, &SearchLimits::with_depth(3), |info| {
+            if let SearchInfo::Iteration {
+                depth: 3,
+                multipv,
+                score,
+                pv,
+                ..
+            } = info
+            {
+                lines.push((*multipv, *score, pv[0].clone()));
+            }
+        });
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].0, 1);
+        assert_eq!(lines[0].1, Score::Mate(1));
+        assert_eq!(Some(lines[0].2.clone()), best);
+        assert_ne!(lines[1].2, lines[2].2);
+        assert_ne!(lines[1].2, lines[0].2);
+    }
+
+    #[test]
+    fn test_helper_threads() {
+        let mut solver = Solver::new();
+        solver.set_threads(3);
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mov = solver.search(&board, &SearchLimits::with_depth(3));
+        assert_eq!(mov.unwrap().to_algebraic(), "a1a8");
+    }
+
+    #[test]
+    fn test_ponderhit_ends_ponder_search() {
+        let movetime = Duration::from_millis(200);
+        // the clock is only checked every 1024 nodes
+        let margin = Duration::from_millis(300);
+
+        let mut solver = Solver::new();
+        let ponderhit = solver.get_ponderhit_flag();
+        let limits = SearchLimits {
+            ponder: true,
+            movetime: Some(movetime),
+            ..Default::default()
+        };
+        let (sender, receiver) = mpsc::channel();
+        let search = thread::spawn(move || {
+            let best = solver.search(&Board::from_basic_board(), &limits);
+            sender.send(best).unwrap();
+        });
+
+        // pondering ignores the movetime until the opponent plays the expected move
+        assert_eq!(
+            receiver.recv_timeout(2 * movetime),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+
+        let hit = Instant::now();
+        ponderhit.store(true, Ordering::Relaxed);
+        let best = receiver.recv_timeout(movetime + margin).unwrap();
+        assert!(best.is_some());
+        assert!(hit.elapsed() < movetime + margin);
+        search.join().unwrap();
+    }
+
+    #[test]
+    fn test_ponder_move_follows_pv() {
+        let mut solver = Solver::new();
+        let board = Board::from_basic_board();
+        let best = solver.search(&board, &SearchLimits::with_depth(3)).unwrap();
+        let ponder = solver.get_ponder_move(&board, &best).unwrap();
+
+        assert_eq!(solver.get_pv()[..2], [best.clone(), ponder.clone()]);
+        let child = board.apply(best).unwrap();
+        assert!(MoveGenerator::new().legal_moves(&child).contains(&ponder));
+    }
+
+    #[test]
+    fn test_new_game_clears_tables() {
+        let mut solver = Solver::new();
+        solver.set_hash_size(1);
+        solver.search(&Board::from_basic_board(), &SearchLimits::with_depth(4));
+        assert!(solver.transposition_table.hashfull() > 0);
+        assert!(solver.history.iter().any(|entry| *entry > 0));
+
+        solver.new_game();
+        assert_eq!(solver.transposition_table.hashfull(), 0);
+        assert!(solver.history.iter().all(|entry| *entry == 0));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let mut solver = Solver::new();
+        let limits = SearchLimits {
+            nodes: Some(500),
+            ..Default::default()
+        };
+        assert!(solver.search(&Board::from_basic_board(), &limits).is_some());
+        assert!(solver.get_nodes() <= 501);
+    }
+
+    #[test]
+    fn test_searchmoves() {
+        let mut solver = Solver::new();
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let only = Move::from_full_algebraic("g1f1").unwrap();
+        let limits = SearchLimits {
+            searchmoves: vec![only.clone()],
+            ..SearchLimits::with_depth(3)
+        };
+        assert_eq!(solver.search(&board, &limits), Some(only));
+    }
+
+    #[test]
+    fn test_stop_flag_aborts_search() {
+        let mut solver = Solver::new();
+        solver.get_stop_flag().store(true, Ordering::Relaxed);
+        let limits = SearchLimits {
+            infinite: true,
+            ..Default::default()
+        };
+        assert!(solver.search(&Board::from_basic_board(), &limits).is_some());
+        assert_eq!(solver.get_nodes(), 1);
+    }
+
+    #[test]
+    fn test_no_legal_moves() {
+        let mut solver = Solver::new();
+        let board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+        assert_eq!(solver.best_move(&board), None);
     }
 }
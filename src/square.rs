@@ -16,15 +16,13 @@ impl Square {
         Square { square }
     }
 
-    pub fn from_algebraic(mov: &str) -> Option<Square> {
-        let mov: Vec<char> = mov.chars().collect();
-        if mov.len() == 2 {
-            let src_rank = (mov[1] as u8) - b'1';
-            let src_file = (mov[0] as u8) - b'a';
-
-            Some(Square::from_rank_file(src_rank, src_file))
-        } else {
-            None
+    /// Square named like `e4`, `None` for anything else
+    pub fn from_algebraic(name: &str) -> Option<Square> {
+        match name.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Some(Square::from_rank_file(rank - b'1', file - b'a'))
+            }
+            _ => None,
         }
     }
 
@@ -56,6 +54,13 @@ impl Square {
     pub fn get_index(&self) -> u8 {
         self.square
     }
+
+    /// Number of king moves needed to go from one square to the other
+    pub fn distance(&self, other: Square) -> u8 {
+        self.get_rank()
+            .abs_diff(other.get_rank())
+            .max(self.get_file().abs_diff(other.get_file()))
+    }
 }
 
 impl fmt::Debug for Square {
@@ -63,3 +68,27 @@ impl fmt::Debug for Square {
         write!(f, "{}", self.to_algebraic())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("a1", Some(0))]
+    #[case("e4", Some(28))]
+    #[case("h8", Some(63))]
+    #[case("a0", None)]
+    #[case("i1", None)]
+    #[case("A1", None)]
+    #[case("e", None)]
+    #[case("e44", None)]
+    #[case("é4", None)]
+    fn test_from_algebraic(#[case] name: &str, #[case] index: Option<u8>) {
+        assert_eq!(
+            Square::from_algebraic(name).map(|square| square.get_index()),
+            index
+        );
+    }
+}
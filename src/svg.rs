@@ -0,0 +1,322 @@
+//! SVG diagrams of positions, with optional coordinates, last move and check highlighting and
+//! arrows and circles for annotations.
+
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+use crate::moves::Move;
+use crate::piece::PieceType;
+use crate::square::Square;
+
+const LIGHT_SQUARE: &str = "#f0d9b5";
+const DARK_SQUARE: &str = "#b58863";
+const LAST_MOVE: &str = "#cdd26a";
+const CHECK: &str = "#e0412f";
+const ANNOTATION: &str = "#15781b";
+
+/// Renders a board as an SVG image
+#[derive(Clone, Debug)]
+pub struct Diagram {
+    square_size: u32,
+    flipped: bool,
+    coordinates: bool,
+    last_move: Option<Move>,
+    check: bool,
+    arrows: Vec<(Square, Square)>,
+    circles: Vec<Square>,
+}
+
+impl Default for Diagram {
+    fn default() -> Diagram {
+        Diagram::new()
+    }
+}
+
+impl Diagram {
+    /// White at the bottom, coordinates and check highlighting on
+    pub fn new() -> Diagram {
+        Diagram {
+            square_size: 45,
+            flipped: false,
+            coordinates: true,
+            last_move: None,
+            check: true,
+            arrows: Vec::new(),
+            circles: Vec::new(),
+        }
+    }
+
+    pub fn set_square_size(&mut self, square_size: u32) {
+        self.square_size = square_size;
+    }
+
+    /// Draw the board from black's side
+    pub fn set_flipped(&mut self, flipped: bool) {
+        self.flipped = flipped;
+    }
+
+    pub fn set_coordinates(&mut self, coordinates: bool) {
+        self.coordinates = coordinates;
+    }
+
+    /// Highlight the source and destination squares of a move
+    pub fn set_last_move(&mut self, last_move: Option<Move>) {
+        self.last_move = last_move;
+    }
+
+    /// Highlight the king of the side to move when it is in check
+    pub fn set_check(&mut self, check: bool) {
+        self.check = check;
+    }
+
+    pub fn add_arrow(&mut self, src: Square, dst: Square) {
+        self.arrows.push((src, dst));
+    }
+
+    pub fn add_circle(&mut self, square: Square) {
+        self.circles.push(square);
+    }
+
+    /// Top left corner of a square
+    fn corner(&self, square: Square) -> (u32, u32) {
+        let (column, row) = if self.flipped {
+            (7 - square.get_file(), square.get_rank())
+        } else {
+            (square.get_file(), 7 - square.get_rank())
+        };
+        (
+            column as u32 * self.square_size,
+            row as u32 * self.square_size,
+        )
+    }
+
+    fn center(&self, square: Square) -> (f64, f64) {
+        let (x, y) = self.corner(square);
+        let half = self.square_size as f64 / 2.0;
+        (x as f64 + half, y as f64 + half)
+    }
+
+    fn square_rect(&self, svg: &mut String, square: Square, fill: &str) {
+        let (x, y) = self.corner(square);
+        let size = self.square_size;
+        writeln!(
+            svg,
+            r#"<rect x="{x}" y="{y}" width="{size}" height="{size}" fill="{fill}"/>"#
+        )
+        .unwrap();
+    }
+
+    pub fn render(&self, board: &Board) -> String {
+        let size = self.square_size;
+        let width = 8 * size;
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{width}" viewBox="0 0 {width} {width}">"#
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<defs><marker id="arrowhead" markerWidth="4" markerHeight="4" refX="2" refY="2" orient="auto"><path d="M0,0 L4,2 L0,4 z" fill="{ANNOTATION}"/></marker></defs>"#
+        )
+        .unwrap();
+
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            let light = (square.get_rank() + square.get_file()) % 2 == 1;
+            self.square_rect(
+                &mut svg,
+                square,
+                if light { LIGHT_SQUARE } else { DARK_SQUARE },
+            );
+        }
+
+        if let Some(mov) = &self.last_move {
+            for square in [mov.get_src(), mov.get_dst()] {
+                self.square_rect(&mut svg, square, LAST_MOVE);
+            }
+        }
+
+        let side = board.get_turn();
+        if self.check && MoveGenerator::new().in_check(board, side) {
+            if let Some(king) = board.king_square(side) {
+                self.square_rect(&mut svg, king, CHECK);
+            }
+        }
+
+        if self.coordinates {
+            self.render_coordinates(&mut svg);
+        }
+
+        let font_size = size * 4 / 5;
+        for piece in board {
+            let piece_type = piece.get_type();
+            if piece_type as usize >= PieceType::Marker as usize {
+                continue;
+            }
+            let (x, y) = self.center(piece.get_square());
+            let (fill, stroke) = if piece_type.is_white() {
+                ("#ffffff", "#000000")
+            } else {
+                ("#000000", "#ffffff")
+            };
+            writeln!(
+                svg,
+                r#"<text x="{x}" y="{y}" font-size="{font_size}" text-anchor="middle" dominant-baseline="central" fill="{fill}" stroke="{stroke}" stroke-width="1">{}</text>"#,
+                glyph(piece_type)
+            )
+            .unwrap();
+        }
+
+        let stroke_width = size as f64 / 8.0;
+        for square in &self.circles {
+            let (x, y) = self.center(*square);
+            let radius = size as f64 / 2.0 - stroke_width;
+            writeln!(
+                svg,
+                r#"<circle cx="{x}" cy="{y}" r="{radius}" fill="none" stroke="{ANNOTATION}" stroke-width="{stroke_width}" opacity="0.8"/>"#
+            )
+            .unwrap();
+        }
+
+        for (src, dst) in &self.arrows {
+            let (x1, y1) = self.center(*src);
+            let (x2, y2) = self.center(*dst);
+            // stop short of the centre so the arrowhead ends on it
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+            let shorten = if length > 0.0 {
+                2.0 * stroke_width / length
+            } else {
+                0.0
+            };
+            let (x2, y2) = (x2 - (x2 - x1) * shorten, y2 - (y2 - y1) * shorten);
+            writeln!(
+                svg,
+                r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="{ANNOTATION}" stroke-width="{stroke_width}" stroke-linecap="round" marker-end="url(#arrowhead)" opacity="0.8"/>"#
+            )
+            .unwrap();
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// File letters along the bottom edge and rank numbers along the left edge
+    fn render_coordinates(&self, svg: &mut String) {
+        let size = self.square_size;
+        let font_size = size / 4;
+        let margin = size / 20 + 1;
+        for column in 0..8u8 {
+            let (file, rank) = if self.flipped {
+                (7 - column, 7 - column)
+            } else {
+                (column, column)
+            };
+            // the edge squares alternate dark and light from the corner in both orientations
+            let fill = if column % 2 == 1 {
+                DARK_SQUARE
+            } else {
+                LIGHT_SQUARE
+            };
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-size="{font_size}" text-anchor="end" fill="{fill}">{}</text>"#,
+                (column as u32 + 1) * size - margin,
+                8 * size - margin,
+                (b'a' + file) as char
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                r#"<text x="{margin}" y="{}" font-size="{font_size}" dominant-baseline="hanging" fill="{fill}">{}</text>"#,
+                (7 - column as u32) * size + margin,
+                (b'1' + rank) as char
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Solid chess symbol of a piece, coloured by the fill of the text
+fn glyph(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::WhitePawn | PieceType::BlackPawn => "\u{265f}\u{fe0e}",
+        PieceType::WhiteRook | PieceType::BlackRook => "\u{265c}",
+        PieceType::WhiteKnight | PieceType::BlackKnight => "\u{265e}",
+        PieceType::WhiteBishop | PieceType::BlackBishop => "\u{265d}",
+        PieceType::WhiteQueen | PieceType::BlackQueen => "\u{265b}",
+        _ => "\u{265a}",
+    }
+}
+
+/// Diagram of a board with the default settings
+pub fn board_to_svg(board: &Board) -> String {
+    Diagram::new().render(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::board::STARTPOS;
+
+    fn square(text: &str) -> Square {
+        Square::from_algebraic(text).unwrap()
+    }
+
+    #[test]
+    fn test_svg_pieces_and_size() {
+        let svg = board_to_svg(&Board::from_fen(STARTPOS));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"360\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<text").count(), 32 + 16);
+        assert_eq!(
+            svg.matches(&format!("fill=\"{LIGHT_SQUARE}\"/>")).count(),
+            32
+        );
+    }
+
+    #[rstest]
+    #[case(false, "<rect x=\"0\" y=\"315\"")]
+    #[case(true, "<rect x=\"315\" y=\"0\"")]
+    fn test_svg_orientation(#[case] flipped: bool, #[case] a1: &str) {
+        let mut diagram = Diagram::new();
+        diagram.set_flipped(flipped);
+        diagram.set_coordinates(false);
+        let svg = diagram.render(&Board::new());
+        // a1 is the first square drawn
+        assert!(svg.lines().nth(2).unwrap().starts_with(a1));
+        assert!(!svg.contains("<text"));
+    }
+
+    #[test]
+    fn test_svg_highlights() {
+        let board =
+            Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        let mut diagram = Diagram::new();
+        diagram.set_last_move(Move::from_full_algebraic("d8h4"));
+        let svg = diagram.render(&board);
+        assert_eq!(svg.matches(LAST_MOVE).count(), 2);
+        assert!(svg.contains(&format!(
+            "<rect x=\"180\" y=\"315\" width=\"45\" height=\"45\" fill=\"{CHECK}\"/>"
+        )));
+
+        diagram.set_check(false);
+        assert!(!diagram.render(&board).contains(CHECK));
+        assert!(!board_to_svg(&Board::from_fen(STARTPOS)).contains(CHECK));
+    }
+
+    #[test]
+    fn test_svg_annotations() {
+        let mut diagram = Diagram::new();
+        diagram.add_arrow(square("g1"), square("f3"));
+        diagram.add_arrow(square("e2"), square("e4"));
+        diagram.add_circle(square("d5"));
+        let svg = diagram.render(&Board::from_fen(STARTPOS));
+        assert_eq!(svg.matches("marker-end=\"url(#arrowhead)\"").count(), 2);
+        assert!(svg.contains("<circle cx=\"157.5\" cy=\"157.5\""));
+        assert!(svg.contains("<line x1=\"292.5\" y1=\"337.5\""));
+    }
+}
@@ -0,0 +1,1091 @@
+/*!
+Endgame tablebases with win/draw/loss and distance to mate, generated by retrograde analysis.
+
+A table covers one material set (`KQvK`, `KBNvK`, `KRvKP`, ...) with at most four pieces,
+the white pieces first. Positions with castling rights or an en passant square are not
+covered. Sets where black has the stronger material are probed through the colour-flipped
+table of the mirrored set.
+
+Positions are indexed by the side to move and the square of every piece, with the white king
+restricted to the a1-d1-d4 triangle for pawnless sets and to the a-d files otherwise.
+Each position is stored as one byte: `0` for a draw, `1..=127` for a win in that many moves
+and `128 + n` for a loss in `n` moves (`128` being checkmate).
+
+File format:
+
+| field       | type  | count  | notes                                           |
+|-------------|-------|--------|-------------------------------------------------|
+| magic       | bytes | 4      | `FCTB`                                          |
+| version     | u8    | 1      | `1`                                             |
+| name length | u8    | 1      |                                                 |
+| name        | ascii | length | material set, e.g. `KQvK`                       |
+| entries     | u32   | 1      | little-endian, number of indexed positions      |
+| runs        | u8    | 2 × n  | run length (1 to 255) followed by the run value |
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::bitboard::{squares, Bitboard};
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+use crate::moves::{Move, Scope};
+use crate::piece::PieceType;
+use crate::side::Side;
+use crate::square::Square;
+
+const TABLEBASE_MAGIC: &[u8; 4] = b"FCTB";
+const TABLEBASE_VERSION: u8 = 1;
+const TABLEBASE_EXTENSION: &str = "fctb";
+/// Largest number of pieces, kings included, a table can hold
+pub const MAX_PIECES: usize = 4;
+
+/// White king squares of pawnless tables: the a1-d1-d4 triangle
+const TRIANGLE: [u8; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+const PROMOTIONS: [PieceType; 4] = [
+    PieceType::WhiteQueen,
+    PieceType::WhiteRook,
+    PieceType::WhiteBishop,
+    PieceType::WhiteKnight,
+];
+const NOT_PENDING: u16 = u16::MAX;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Value of a position for the side to move, with the distance to mate in plies
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Win(u16),
+    Draw,
+    Loss(u16),
+}
+
+impl Outcome {
+    fn to_byte(self) -> u8 {
+        match self {
+            Outcome::Draw => 0,
+            Outcome::Win(plies) => {
+                assert!(plies <= 253, "win in {} plies does not fit a table", plies);
+                plies.div_ceil(2) as u8
+            }
+            Outcome::Loss(plies) => {
+                assert!(plies <= 254, "loss in {} plies does not fit a table", plies);
+                128 + (plies / 2) as u8
+            }
+        }
+    }
+
+    fn from_byte(byte: u8) -> Outcome {
+        match byte {
+            0 => Outcome::Draw,
+            1..=127 => Outcome::Win(2 * byte as u16 - 1),
+            _ => Outcome::Loss(2 * (byte as u16 - 128)),
+        }
+    }
+
+    /// Value for the side that played the move into a position with this value
+    fn backed_up(self) -> Outcome {
+        match self {
+            Outcome::Win(plies) => Outcome::Loss(plies + 1),
+            Outcome::Draw => Outcome::Draw,
+            Outcome::Loss(plies) => Outcome::Win(plies + 1),
+        }
+    }
+
+    /// Higher is better: quick wins, then draws, then slow losses
+    fn preference(self) -> i32 {
+        match self {
+            Outcome::Win(plies) => 1000 - plies as i32,
+            Outcome::Draw => 0,
+            Outcome::Loss(plies) => plies as i32 - 1000,
+        }
+    }
+}
+
+fn side_of(piece_type: PieceType) -> Side {
+    if piece_type.is_black() {
+        Side::Black
+    } else {
+        Side::White
+    }
+}
+
+/// Order of the pieces of a side within a material set
+fn kind_order(piece_type: PieceType) -> u8 {
+    match piece_type {
+        PieceType::WhiteKing | PieceType::BlackKing => 0,
+        PieceType::WhiteQueen | PieceType::BlackQueen => 1,
+        PieceType::WhiteRook | PieceType::BlackRook => 2,
+        PieceType::WhiteBishop | PieceType::BlackBishop => 3,
+        PieceType::WhiteKnight | PieceType::BlackKnight => 4,
+        _ => 5,
+    }
+}
+
+fn piece_value(piece_type: PieceType) -> u32 {
+    match piece_type {
+        PieceType::WhiteQueen | PieceType::BlackQueen => 9,
+        PieceType::WhiteRook | PieceType::BlackRook => 5,
+        PieceType::WhiteBishop
+        | PieceType::BlackBishop
+        | PieceType::WhiteKnight
+        | PieceType::BlackKnight => 3,
+        PieceType::WhitePawn | PieceType::BlackPawn => 1,
+        _ => 0,
+    }
+}
+
+fn is_pawn(piece_type: PieceType) -> bool {
+    matches!(piece_type, PieceType::WhitePawn | PieceType::BlackPawn)
+}
+
+/// Apply one of the 8 board symmetries: bit 2 transposes, bit 0 flips files, bit 1 flips ranks
+fn symmetry(square: u8, symmetry: u8) -> u8 {
+    let square = if symmetry & 4 != 0 {
+        (square >> 3) | ((square & 7) << 3)
+    } else {
+        square
+    };
+    square ^ ((symmetry & 1) * 7) ^ ((symmetry & 2) / 2 * 56)
+}
+
+fn pawn_captures(side: Side, square: u8) -> Bitboard {
+    let bit: Bitboard = 1 << square;
+    let (left, right) = match side {
+        Side::White => (bit << 7, bit << 9),
+        Side::Black => (bit >> 9, bit >> 7),
+    };
+    (left & 0x7F7F7F7F7F7F7F7F) | (right & 0xFEFEFEFEFEFEFEFE)
+}
+
+/// Squares attacked by a piece other than a pawn
+fn attacks(
+    move_generator: &MoveGenerator,
+    piece_type: PieceType,
+    square: u8,
+    occupied: Bitboard,
+) -> Bitboard {
+    let from = Square::from_index(square);
+    match piece_type {
+        PieceType::WhiteKing | PieceType::BlackKing => {
+            move_generator.king_attacks(piece_type, from, !0).mov & !(1 << square)
+        }
+        PieceType::WhiteKnight | PieceType::BlackKnight => {
+            move_generator.knight_attacks(piece_type, from, !0).mov
+        }
+        PieceType::WhiteBishop | PieceType::BlackBishop => {
+            move_generator
+                .bishop_attacks(piece_type, from, !occupied)
+                .mov
+        }
+        PieceType::WhiteRook | PieceType::BlackRook => {
+            move_generator.rook_attacks(piece_type, from, !occupied).mov
+        }
+        PieceType::WhiteQueen | PieceType::BlackQueen => {
+            move_generator
+                .bishop_attacks(piece_type, from, !occupied)
+                .mov
+                | move_generator.rook_attacks(piece_type, from, !occupied).mov
+        }
+        _ => 0,
+    }
+}
+
+/// Pieces of both sides, ordered white then black and by kind within each side
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Material {
+    pieces: Vec<PieceType>,
+}
+
+impl Material {
+    fn new(mut pieces: Vec<PieceType>) -> Material {
+        pieces.sort_by_key(|piece_type| (piece_type.is_black(), kind_order(*piece_type)));
+        Material { pieces }
+    }
+
+    /// Parse a material set such as `KQvK` or `KRvKP`, white pieces first
+    pub fn from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let mut pieces = vec![];
+        for (letters, side) in [(white, Side::White), (black, Side::Black)] {
+            if letters.matches('K').count() != 1 {
+                return None;
+            }
+            for letter in letters.chars() {
+                if !letter.is_ascii_uppercase() {
+                    return None;
+                }
+                let piece_type = PieceType::from_string(&letter)?;
+                pieces.push(match side {
+                    Side::White => piece_type,
+                    Side::Black => !piece_type,
+                });
+            }
+        }
+
+        if pieces.len() > MAX_PIECES {
+            return None;
+        }
+        Some(Material::new(pieces))
+    }
+
+    fn side_name(&self, side: Side) -> String {
+        self.pieces
+            .iter()
+            .filter(|piece_type| side_of(**piece_type) == side)
+            .map(|piece_type| piece_type.to_char().to_ascii_uppercase())
+            .collect()
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "{}v{}",
+            self.side_name(Side::White),
+            self.side_name(Side::Black)
+        )
+    }
+
+    fn strength(&self, side: Side) -> u32 {
+        self.pieces
+            .iter()
+            .filter(|piece_type| side_of(**piece_type) == side)
+            .map(|piece_type| piece_value(*piece_type))
+            .sum()
+    }
+
+    /// Tables are only kept for sets where white has the stronger material
+    fn is_canonical(&self) -> bool {
+        (self.strength(Side::White), self.side_name(Side::White))
+            >= (self.strength(Side::Black), self.side_name(Side::Black))
+    }
+
+    fn flipped(&self) -> Material {
+        Material::new(self.pieces.iter().map(|piece_type| !*piece_type).collect())
+    }
+
+    fn canonical(&self) -> Material {
+        if self.is_canonical() {
+            self.clone()
+        } else {
+            self.flipped()
+        }
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|piece_type| is_pawn(*piece_type))
+    }
+
+    fn king_squares(&self) -> usize {
+        if self.has_pawns() {
+            32
+        } else {
+            TRIANGLE.len()
+        }
+    }
+
+    fn positions_per_side(&self) -> usize {
+        self.king_squares() * 64usize.pow(self.pieces.len() as u32 - 1)
+    }
+
+    /// Number of indexed positions
+    pub fn size(&self) -> usize {
+        2 * self.positions_per_side()
+    }
+
+    /// Material sets reached by captures and promotions
+    fn successors(&self) -> Vec<Material> {
+        let mut successors = vec![];
+        for (index, piece_type) in self.pieces.iter().enumerate() {
+            if kind_order(*piece_type) == 0 {
+                continue;
+            }
+            let mut captured = self.pieces.clone();
+            captured.remove(index);
+            successors.push(Material::new(captured));
+
+            if is_pawn(*piece_type) {
+                for promotion in PROMOTIONS {
+                    let mut promoted = self.pieces.clone();
+                    promoted[index] = match side_of(*piece_type) {
+                        Side::White => promotion,
+                        Side::Black => !promotion,
+                    };
+                    successors.push(Material::new(promoted));
+                }
+            }
+        }
+        successors
+    }
+
+    fn decode(&self, index: usize) -> Position {
+        let turn = if index < self.positions_per_side() {
+            Side::White
+        } else {
+            Side::Black
+        };
+        let mut rest = index % self.positions_per_side();
+
+        let mut position = Position {
+            pieces: [(PieceType::NoPiece, 0); MAX_PIECES],
+            len: self.pieces.len(),
+            turn,
+        };
+        for slot in (1..self.pieces.len()).rev() {
+            position.pieces[slot] = (self.pieces[slot], (rest % 64) as u8);
+            rest /= 64;
+        }
+        let king = if self.has_pawns() {
+            (rest / 4 * 8 + rest % 4) as u8
+        } else {
+            TRIANGLE[rest]
+        };
+        position.pieces[0] = (self.pieces[0], king);
+
+        position
+    }
+
+    /// Index of a position of this material set, the smallest among its symmetric images
+    fn index(&self, position: &Position) -> usize {
+        let symmetries = if self.has_pawns() { 2 } else { 8 };
+        let turn = match position.turn {
+            Side::White => 0,
+            Side::Black => 1,
+        };
+
+        let mut best = usize::MAX;
+        for sym in 0..symmetries {
+            let mut pieces = position.pieces;
+            for piece in pieces[..position.len].iter_mut() {
+                piece.1 = symmetry(piece.1, sym);
+            }
+            pieces[..position.len].sort_by_key(|(piece_type, square)| {
+                (piece_type.is_black(), kind_order(*piece_type), *square)
+            });
+
+            let king = pieces[0].1;
+            let king_index = if self.has_pawns() {
+                if king % 8 > 3 {
+                    continue;
+                }
+                (king / 8 * 4 + king % 8) as usize
+            } else {
+                match TRIANGLE.iter().position(|square| *square == king) {
+                    Some(king_index) => king_index,
+                    None => continue,
+                }
+            };
+
+            let index = pieces[1..position.len]
+                .iter()
+                .fold(king_index, |index, (_, square)| {
+                    index * 64 + *square as usize
+                });
+            best = best.min(turn * self.positions_per_side() + index);
+        }
+
+        best
+    }
+}
+
+/// Compact position used during generation and probing
+#[derive(Clone, Copy, Debug)]
+struct Position {
+    pieces: [(PieceType, u8); MAX_PIECES],
+    len: usize,
+    turn: Side,
+}
+
+/// Legal move of a `Position`, with the position it leads to
+struct Child {
+    mov: Move,
+    position: Position,
+    /// the move captured or promoted, leaving the material set
+    exit: bool,
+}
+
+impl Position {
+    fn from_board(board: &Board) -> Option<Position> {
+        if board.has_castling_rights() || board.get_enpassant().is_some() {
+            return None;
+        }
+        if board.occupied(Scope::All).count_ones() as usize > MAX_PIECES {
+            return None;
+        }
+
+        let mut position = Position {
+            pieces: [(PieceType::NoPiece, 0); MAX_PIECES],
+            len: 0,
+            turn: board.get_turn(),
+        };
+        for piece_type in 0..12 {
+            let piece_type: PieceType = num::FromPrimitive::from_u8(piece_type)?;
+            for square in squares(board.occupied(Scope::from(piece_type))) {
+                position.pieces[position.len] = (piece_type, square);
+                position.len += 1;
+            }
+        }
+
+        if position.find(PieceType::WhiteKing).is_none()
+            || position.find(PieceType::BlackKing).is_none()
+        {
+            return None;
+        }
+        Some(position)
+    }
+
+    fn material(&self) -> Material {
+        Material::new(self.pieces[..self.len].iter().map(|(p, _)| *p).collect())
+    }
+
+    fn flipped(&self) -> Position {
+        let mut position = *self;
+        for piece in position.pieces[..self.len].iter_mut() {
+            *piece = (!piece.0, piece.1 ^ 56);
+        }
+        position.turn = !self.turn;
+        position
+    }
+
+    fn find(&self, piece_type: PieceType) -> Option<u8> {
+        self.pieces[..self.len]
+            .iter()
+            .find(|(p, _)| *p == piece_type)
+            .map(|(_, square)| *square)
+    }
+
+    fn occupied(&self) -> Bitboard {
+        self.pieces[..self.len]
+            .iter()
+            .fold(0, |acc, (_, square)| acc | 1 << square)
+    }
+
+    fn occupied_by(&self, side: Side) -> Bitboard {
+        self.pieces[..self.len]
+            .iter()
+            .filter(|(piece_type, _)| side_of(*piece_type) == side)
+            .fold(0, |acc, (_, square)| acc | 1 << square)
+    }
+
+    fn attacked(&self, move_generator: &MoveGenerator, square: u8, side: Side) -> bool {
+        let occupied = self.occupied();
+        self.pieces[..self.len]
+            .iter()
+            .filter(|(piece_type, _)| side_of(*piece_type) == side)
+            .any(|(piece_type, from)| {
+                let targets = if is_pawn(*piece_type) {
+                    pawn_captures(side, *from)
+                } else {
+                    attacks(move_generator, *piece_type, *from, occupied)
+                };
+                targets & (1 << square) != 0
+            })
+    }
+
+    fn in_check(&self, move_generator: &MoveGenerator, side: Side) -> bool {
+        let king = match side {
+            Side::White => PieceType::WhiteKing,
+            Side::Black => PieceType::BlackKing,
+        };
+        match self.find(king) {
+            Some(square) => self.attacked(move_generator, square, !side),
+            None => true,
+        }
+    }
+
+    /// Pieces on distinct squares, no pawns on the first or last rank and the side that just
+    /// moved not in check
+    fn is_valid(&self, move_generator: &MoveGenerator) -> bool {
+        if self.occupied().count_ones() as usize != self.len {
+            return false;
+        }
+        if self.pieces[..self.len]
+            .iter()
+            .any(|(piece_type, square)| is_pawn(*piece_type) && !(8..56).contains(square))
+        {
+            return false;
+        }
+        !self.in_check(move_generator, !self.turn)
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.pieces[index] = self.pieces[self.len - 1];
+        self.len -= 1;
+    }
+
+    fn children(&self, move_generator: &MoveGenerator) -> Vec<Child> {
+        let occupied = self.occupied();
+        let own = self.occupied_by(self.turn);
+        let enemy = self.occupied_by(!self.turn);
+
+        let mut children = vec![];
+        for (index, (piece_type, from)) in self.pieces[..self.len].iter().enumerate() {
+            if side_of(*piece_type) != self.turn {
+                continue;
+            }
+
+            let targets = if is_pawn(*piece_type) {
+                let (step, start): (i8, u8) = match self.turn {
+                    Side::White => (8, 1),
+                    Side::Black => (-8, 6),
+                };
+                let single = (*from as i8 + step) as u8;
+                let double = (*from as i8 + 2 * step) as u8;
+
+                let mut targets = pawn_captures(self.turn, *from) & enemy;
+                if occupied & (1 << single) == 0 {
+                    targets |= 1 << single;
+                    if from / 8 == start && occupied & (1 << double) == 0 {
+                        targets |= 1 << double;
+                    }
+                }
+                targets
+            } else {
+                attacks(move_generator, *piece_type, *from, occupied) & !own
+            };
+
+            for to in squares(targets) {
+                let captured = self.pieces[..self.len]
+                    .iter()
+                    .position(|(_, square)| *square == to);
+                let promotions: Vec<Option<PieceType>> =
+                    if is_pawn(*piece_type) && !(8..56).contains(&to) {
+                        PROMOTIONS
+                            .iter()
+                            .map(|promotion| match self.turn {
+                                Side::White => Some(*promotion),
+                                Side::Black => Some(!*promotion),
+                            })
+                            .collect()
+                    } else {
+                        vec![None]
+                    };
+
+                for promotion in promotions {
+                    let mut position = *self;
+                    position.pieces[index] = (promotion.unwrap_or(*piece_type), to);
+                    if let Some(captured) = captured {
+                        position.remove(captured);
+                    }
+                    if position.in_check(move_generator, self.turn) {
+                        continue;
+                    }
+                    position.turn = !self.turn;
+
+                    let (from, to) = (Square::from_index(*from), Square::from_index(to));
+                    children.push(Child {
+                        mov: match promotion {
+                            Some(promotion) => Move::with_promotion(from, to, promotion),
+                            None => Move::new(from, to),
+                        },
+                        position,
+                        exit: captured.is_some() || promotion.is_some(),
+                    });
+                }
+            }
+        }
+
+        children
+    }
+
+    /// Positions of the same material set leading to this one with a single move
+    fn predecessors(&self, move_generator: &MoveGenerator) -> Vec<Position> {
+        let occupied = self.occupied();
+        let mover = !self.turn;
+
+        let mut predecessors = vec![];
+        for (index, (piece_type, to)) in self.pieces[..self.len].iter().enumerate() {
+            if side_of(*piece_type) != mover {
+                continue;
+            }
+
+            let origins = if is_pawn(*piece_type) {
+                let (single, double, double_rank) = match mover {
+                    Side::White if *to >= 16 => (to - 8, to.wrapping_sub(16), 3),
+                    Side::Black if *to < 48 => (to + 8, to + 16, 4),
+                    _ => continue,
+                };
+                let mut origins: Bitboard = 0;
+                if occupied & (1 << single) == 0 {
+                    origins |= 1 << single;
+                    if to / 8 == double_rank && occupied & (1 << double) == 0 {
+                        origins |= 1 << double;
+                    }
+                }
+                origins
+            } else {
+                attacks(move_generator, *piece_type, *to, occupied) & !occupied
+            };
+
+            for from in squares(origins) {
+                let mut position = *self;
+                position.pieces[index].1 = from;
+                position.turn = mover;
+                if position.is_valid(move_generator) {
+                    predecessors.push(position);
+                }
+            }
+        }
+
+        predecessors
+    }
+}
+
+/// Values of every position of one material set
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Table {
+    material: Material,
+    values: Vec<u8>,
+}
+
+impl Table {
+    pub fn get_material(&self) -> &Material {
+        &self.material
+    }
+
+    fn get(&self, position: &Position) -> Outcome {
+        Outcome::from_byte(self.values[self.material.index(position)])
+    }
+
+    /// Longest distance to mate of the table, in plies
+    pub fn longest_mate(&self) -> u16 {
+        self.values
+            .iter()
+            .filter_map(|value| match Outcome::from_byte(*value) {
+                Outcome::Win(plies) => Some(plies),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.material.name();
+        let mut bytes = TABLEBASE_MAGIC.to_vec();
+        bytes.push(TABLEBASE_VERSION);
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(self.values.len() as u32).to_le_bytes());
+
+        let mut values = self.values.iter().peekable();
+        while let Some(value) = values.next() {
+            let mut run = 1u8;
+            while run < u8::MAX && values.peek() == Some(&value) {
+                values.next();
+                run += 1;
+            }
+            bytes.push(run);
+            bytes.push(*value);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Table> {
+        if bytes.len() < 6 || &bytes[..4] != TABLEBASE_MAGIC {
+            return Err(invalid_data("not a tablebase file"));
+        }
+        if bytes[4] != TABLEBASE_VERSION {
+            return Err(invalid_data("unsupported tablebase version"));
+        }
+        let name_end = 6 + bytes[5] as usize;
+        let name = bytes
+            .get(6..name_end)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid_data("truncated tablebase file"))?;
+        let material = Material::from_name(name)
+            .filter(|material| material.is_canonical())
+            .ok_or_else(|| invalid_data("invalid material set"))?;
+
+        let entries = bytes
+            .get(name_end..name_end + 4)
+            .ok_or_else(|| invalid_data("truncated tablebase file"))?;
+        let entries = u32::from_le_bytes([entries[0], entries[1], entries[2], entries[3]]);
+        if entries as usize != material.size() {
+            return Err(invalid_data("wrong number of entries for the material set"));
+        }
+
+        let runs = &bytes[name_end + 4..];
+        if runs.len() % 2 != 0 {
+            return Err(invalid_data("truncated tablebase file"));
+        }
+        let mut values = Vec::with_capacity(material.size());
+        for run in runs.chunks(2) {
+            values.extend(std::iter::repeat_n(run[1], run[0] as usize));
+        }
+        if values.len() != material.size() {
+            return Err(invalid_data("wrong number of entries for the material set"));
+        }
+
+        Ok(Table { material, values })
+    }
+
+    pub fn from_filename(filename: &str) -> io::Result<Table> {
+        Table::from_bytes(&fs::read(filename)?)
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        fs::write(filename, self.to_bytes())
+    }
+}
+
+/// Collection of tables, probed by material set
+#[derive(Clone, Debug, Default)]
+pub struct Tablebases {
+    tables: HashMap<String, Table>,
+    move_generator: MoveGenerator,
+}
+
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases::default()
+    }
+
+    /// Load every `.fctb` table of a directory
+    pub fn from_directory(directory: &str) -> io::Result<Tablebases> {
+        let mut tablebases = Tablebases::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str())
+                == Some(TABLEBASE_EXTENSION)
+            {
+                let filename = path.to_string_lossy();
+                tablebases.insert(Table::from_filename(&filename)?);
+            }
+        }
+        Ok(tablebases)
+    }
+
+    /// Write every table to `<directory>/<material>.fctb`
+    pub fn save(&self, directory: &str) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        for (name, table) in self.tables.iter() {
+            let path = Path::new(directory).join(format!("{name}.{TABLEBASE_EXTENSION}"));
+            table.save(&path.to_string_lossy())?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, table: Table) {
+        self.tables.insert(table.material.name(), table);
+    }
+
+    pub fn get(&self, material: &Material) -> Option<&Table> {
+        self.tables.get(&material.canonical().name())
+    }
+
+    /// Generate the table of a material set and, first, those of the sets it converts to
+    pub fn generate(&mut self, material: &Material) {
+        let material = material.canonical();
+        if material.pieces.len() <= 2 || self.tables.contains_key(&material.name()) {
+            return;
+        }
+        for successor in material.successors() {
+            self.generate(&successor);
+        }
+
+        let table = self.retrograde(material);
+        self.insert(table);
+    }
+
+    fn probe_position(&self, position: &Position) -> Option<Outcome> {
+        let material = position.material();
+        if material.pieces.len() <= 2 {
+            return Some(Outcome::Draw);
+        }
+        if material.is_canonical() {
+            Some(self.tables.get(&material.name())?.get(position))
+        } else {
+            Some(
+                self.tables
+                    .get(&material.flipped().name())?
+                    .get(&position.flipped()),
+            )
+        }
+    }
+
+    /// Value of the board for the side to move, if it is covered by a loaded table
+    pub fn probe(&self, board: &Board) -> Option<Outcome> {
+        let position = Position::from_board(board)?;
+        if !position.is_valid(&self.move_generator) {
+            return None;
+        }
+        self.probe_position(&position)
+    }
+
+    /// Move keeping the best value: the fastest win, a draw, or the slowest loss
+    pub fn best_move(&self, board: &Board) -> Option<Move> {
+        self.probe(board)?;
+        let position = Position::from_board(board)?;
+
+        position
+            .children(&self.move_generator)
+            .into_iter()
+            .filter_map(|child| {
+                let outcome = self.probe_position(&child.position)?.backed_up();
+                Some((outcome.preference(), child.mov))
+            })
+            .max_by_key(|(preference, _)| *preference)
+            .map(|(_, mov)| mov)
+    }
+
+    /// Retrograde analysis of one material set, whose successors must already be generated.
+    ///
+    /// Positions are resolved in order of increasing distance to mate: starting from the
+    /// checkmates, the predecessors of a lost position are won one ply later, and a position
+    /// whose moves all lead to won positions for the opponent is lost one ply after the
+    /// longest of them. Captures and promotions are looked up in the successor tables.
+    fn retrograde(&self, material: Material) -> Table {
+        let move_generator = &self.move_generator;
+        let size = material.size();
+        let mut values: Vec<Option<Outcome>> = vec![None; size];
+        let mut pending = vec![NOT_PENDING; size];
+        let mut valid = vec![false; size];
+        let mut buckets: Vec<Vec<usize>> = vec![];
+
+        let schedule =
+            |buckets: &mut Vec<Vec<usize>>, pending: &mut [u16], index: usize, distance: u16| {
+                if distance < pending[index] {
+                    pending[index] = distance;
+                    if buckets.len() <= distance as usize {
+                        buckets.resize(distance as usize + 1, vec![]);
+                    }
+                    buckets[distance as usize].push(index);
+                }
+            };
+
+        for index in 0..size {
+            let position = material.decode(index);
+            if !position.is_valid(move_generator) || material.index(&position) != index {
+                continue;
+            }
+            valid[index] = true;
+
+            let children = position.children(move_generator);
+            if children.is_empty() {
+                if position.in_check(move_generator, position.turn) {
+                    schedule(&mut buckets, &mut pending, index, 0);
+                } else {
+                    values[index] = Some(Outcome::Draw);
+                }
+                continue;
+            }
+
+            let mut quiet = 0;
+            let mut draw = false;
+            let mut longest_loss = 0;
+            for child in children {
+                if !child.exit {
+                    quiet += 1;
+                    continue;
+                }
+                match self
+                    .probe_position(&child.position)
+                    .expect("successor tables are generated first")
+                    .backed_up()
+                {
+                    Outcome::Win(plies) => schedule(&mut buckets, &mut pending, index, plies),
+                    Outcome::Draw => draw = true,
+                    Outcome::Loss(plies) => longest_loss = longest_loss.max(plies),
+                }
+            }
+
+            if pending[index] == NOT_PENDING && quiet == 0 {
+                if draw {
+                    values[index] = Some(Outcome::Draw);
+                } else {
+                    schedule(&mut buckets, &mut pending, index, longest_loss);
+                }
+            }
+        }
+
+        let forced_loss = |values: &[Option<Outcome>], position: &Position| {
+            let mut longest = 0;
+            for child in position.children(move_generator) {
+                let outcome = if child.exit {
+                    self.probe_position(&child.position)
+                } else {
+                    values[material.index(&child.position)]
+                };
+                match outcome?.backed_up() {
+                    Outcome::Loss(plies) => longest = longest.max(plies),
+                    _ => return None,
+                }
+            }
+            Some(longest)
+        };
+
+        let mut distance = 0;
+        while distance < buckets.len() {
+            for index in std::mem::take(&mut buckets[distance]) {
+                if values[index].is_some() || pending[index] != distance as u16 {
+                    continue;
+                }
+                let outcome = if distance % 2 == 1 {
+                    Outcome::Win(distance as u16)
+                } else {
+                    Outcome::Loss(distance as u16)
+                };
+                values[index] = Some(outcome);
+
+                for predecessor in material.decode(index).predecessors(move_generator) {
+                    let previous = material.index(&predecessor);
+                    if !valid[previous] || values[previous].is_some() {
+                        continue;
+                    }
+                    match outcome {
+                        Outcome::Loss(plies) => {
+                            schedule(&mut buckets, &mut pending, previous, plies + 1)
+                        }
+                        _ => {
+                            if pending[previous] != NOT_PENDING {
+                                continue;
+                            }
+                            if let Some(plies) = forced_loss(&values, &predecessor) {
+                                schedule(&mut buckets, &mut pending, previous, plies);
+                            }
+                        }
+                    }
+                }
+            }
+            distance += 1;
+        }
+
+        Table {
+            values: values
+                .iter()
+                .map(|value| value.unwrap_or(Outcome::Draw).to_byte())
+                .collect(),
+            material,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    /// KQvK, KRvK, KPvK and the sets they convert to, generated once for every test
+    fn tablebases() -> &'static Tablebases {
+        static TABLEBASES: OnceLock<Tablebases> = OnceLock::new();
+        TABLEBASES.get_or_init(|| {
+            let mut tablebases = Tablebases::new();
+            for name in ["KQvK", "KRvK", "KPvK"] {
+                tablebases.generate(&Material::from_name(name).unwrap());
+            }
+            tablebases
+        })
+    }
+
+    #[rstest]
+    #[case("KQvK", Some("KQvK"))]
+    #[case("KBNvK", Some("KBNvK"))]
+    #[case("KvKRP", Some("KvKRP"))]
+    #[case("KQRvKR", None)]
+    #[case("KQvQ", None)]
+    #[case("kqvk", None)]
+    fn test_material_name(#[case] name: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            Material::from_name(name).map(|material| material.name()),
+            expected.map(|name| name.to_string())
+        );
+    }
+
+    #[test]
+    fn test_index_is_symmetric() {
+        let move_generator = MoveGenerator::new();
+        let material = Material::from_name("KRvK").unwrap();
+        let position =
+            Position::from_board(&Board::from_fen("8/8/8/8/8/1k6/8/1K2R3 w - - 0 1")).unwrap();
+        let mirrored =
+            Position::from_board(&Board::from_fen("3r2k1/8/6K1/8/8/8/8/8 b - - 0 1")).unwrap();
+
+        assert!(position.is_valid(&move_generator));
+        assert_eq!(
+            material.index(&position),
+            material.index(&mirrored.flipped())
+        );
+    }
+
+    #[rstest]
+    #[case("k7/8/1K6/8/8/8/7Q/8 w - - 0 1", Outcome::Win(1))]
+    #[case("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1", Outcome::Loss(0))]
+    #[case("k7/8/1Q6/8/8/8/8/7K b - - 0 1", Outcome::Draw)]
+    #[case("K7/8/1k6/8/8/8/7q/8 b - - 0 1", Outcome::Win(1))]
+    #[case("8/8/8/8/8/8/8/K1k5 w - - 0 1", Outcome::Draw)]
+    fn test_probe(#[case] fen: &str, #[case] outcome: Outcome) {
+        assert_eq!(tablebases().probe(&Board::from_fen(fen)), Some(outcome));
+    }
+
+    #[rstest]
+    #[case("8/8/8/3k4/8/8/8/R3K3 b - - 0 1", -1)]
+    #[case("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", 1)]
+    #[case("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", -1)]
+    #[case("8/8/4k3/8/4K3/4P3/8/8 w - - 0 1", 0)]
+    #[case("k7/8/8/8/8/8/P7/K7 w - - 0 1", 0)]
+    #[case("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", 0)]
+    fn test_probe_win_draw_loss(#[case] fen: &str, #[case] expected: i32) {
+        let outcome = tablebases().probe(&Board::from_fen(fen)).unwrap();
+        assert_eq!(outcome.preference().signum(), expected);
+    }
+
+    #[rstest]
+    #[case("k7/8/1K6/8/8/8/8/7R w KQkq - 0 1")]
+    #[case("k7/8/1K6/8/8/8/8/BN5R w - - 0 1")]
+    #[case("k7/1Q6/1K6/8/8/8/8/8 w - - 0 1")]
+    fn test_probe_uncovered(#[case] fen: &str) {
+        assert_eq!(tablebases().probe(&Board::from_fen(fen)), None);
+    }
+
+    #[rstest]
+    #[case("KQvK", 2 * 10 - 1)]
+    #[case("KRvK", 2 * 16 - 1)]
+    #[case("KPvK", 2 * 28 - 1)]
+    fn test_longest_mate(#[case] name: &str, #[case] plies: u16) {
+        let material = Material::from_name(name).unwrap();
+        assert_eq!(tablebases().get(&material).unwrap().longest_mate(), plies);
+    }
+
+    #[rstest]
+    #[case("k7/8/1K6/8/8/8/8/7R w - - 0 1", Some("h1h8"))]
+    #[case("8/8/8/8/8/1k6/2q5/K7 b - - 0 1", None)]
+    #[case("8/4k3/8/8/8/8/P7/K7 b - - 0 1", None)]
+    fn test_best_move_keeps_value(#[case] fen: &str, #[case] expected: Option<&str>) {
+        let board = Board::from_fen(fen);
+        let best = tablebases().best_move(&board).unwrap();
+        let child = board.apply(best.clone()).unwrap();
+
+        assert_eq!(
+            tablebases().probe(&child).map(Outcome::backed_up),
+            tablebases().probe(&board)
+        );
+        if let Some(expected) = expected {
+            assert_eq!(best.to_algebraic(), expected);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let material = Material::from_name("KQvK").unwrap();
+        let table = tablebases().get(&material).unwrap();
+
+        let bytes = table.to_bytes();
+        assert!(bytes.len() < material.size());
+        assert_eq!(&Table::from_bytes(&bytes).unwrap(), table);
+    }
+
+    #[rstest]
+    #[case(b"FCTX".to_vec())]
+    #[case(b"FCTB\x02".to_vec())]
+    #[case(b"FCTB\x01\x04KQvQ\x00\x00\x00\x00".to_vec())]
+    #[case(b"FCTB\x01\x04KQvK\x00\x40\x01\x00\xff\x00".to_vec())]
+    fn test_reject_corrupt_table(#[case] bytes: Vec<u8>) {
+        assert!(Table::from_bytes(&bytes).is_err());
+    }
+}
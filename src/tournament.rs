@@ -0,0 +1,914 @@
+//! Engine-versus-engine matches: games played under a time control with adjudication,
+//! opening suites, Elo estimates and the sequential probability ratio test.
+
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::board::STARTPOS;
+use crate::engine::Engine;
+use crate::epd::parse_epds;
+use crate::move_generator::MoveGenerator;
+use crate::moves::Move;
+use crate::pgn::{elapsed_annotation, eval_annotation, PgnGame, PgnReader};
+use crate::piece::PieceType;
+use crate::side::Side;
+use crate::solver::{Score, SearchInfo, SearchLimits, Solver};
+use crate::square::Square;
+
+/// Centipawn value given to mate scores by the adjudication
+const MATE_CENTIPAWNS: i32 = 100_000;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Side of a game, choosing moves from a position given as a start and the moves played since
+pub trait Player {
+    fn get_name(&self) -> String;
+
+    fn new_game(&mut self) -> io::Result<()>;
+
+    /// Best move in `board`, reached by playing `moves` from `fen` or the start position, and
+    /// the score reported for it from the side to move
+    fn go(
+        &mut self,
+        fen: Option<&str>,
+        moves: &[Move],
+        board: &Board,
+        limits: &SearchLimits,
+    ) -> io::Result<(Option<Move>, Option<Score>)>;
+}
+
+impl Player for Engine {
+    fn get_name(&self) -> String {
+        self.get_name().unwrap_or("engine").to_string()
+    }
+
+    fn new_game(&mut self) -> io::Result<()> {
+        Engine::new_game(self)
+    }
+
+    fn go(
+        &mut self,
+        fen: Option<&str>,
+        moves: &[Move],
+        _board: &Board,
+        limits: &SearchLimits,
+    ) -> io::Result<(Option<Move>, Option<Score>)> {
+        self.set_position(fen, moves)?;
+        let result = Engine::go(self, limits)?;
+        let score = result.last_scored().and_then(|info| info.score);
+        Ok((result.best_move, score))
+    }
+}
+
+/// `Solver` searching in this process
+pub struct SolverPlayer {
+    name: String,
+    solver: Solver,
+}
+
+impl SolverPlayer {
+    pub fn new(name: &str, solver: Solver) -> SolverPlayer {
+        SolverPlayer {
+            name: name.to_string(),
+            solver,
+        }
+    }
+}
+
+impl Player for SolverPlayer {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> io::Result<()> {
+        self.solver.new_game();
+        Ok(())
+    }
+
+    fn go(
+        &mut self,
+        _fen: Option<&str>,
+        _moves: &[Move],
+        board: &Board,
+        limits: &SearchLimits,
+    ) -> io::Result<(Option<Move>, Option<Score>)> {
+        let mut score = None;
+        let best = self.solver.search_with_progress(board, limits, |info| {
+            if let SearchInfo::Iteration {
+                multipv: 1,
+                score: iteration_score,
+                ..
+            } = info
+            {
+                score = Some(*iteration_score);
+            }
+        });
+        Ok((best, score))
+    }
+}
+
+/// How much each player may think
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeControl {
+    /// Time for the whole game plus an increment per move
+    Clock {
+        base: Duration,
+        increment: Duration,
+    },
+    MoveTime(Duration),
+    Depth(u8),
+    Nodes(u64),
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    /// Clock given as `base+increment` in seconds, such as `10+0.1`
+    fn from_str(text: &str) -> Result<TimeControl, String> {
+        let invalid = || format!("invalid time control {text}");
+        let seconds = |text: &str| -> Result<Duration, String> {
+            let seconds: f64 = text.parse().map_err(|_| invalid())?;
+            if seconds < 0.0 || !seconds.is_finite() {
+                return Err(invalid());
+            }
+            Ok(Duration::from_secs_f64(seconds))
+        };
+
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        Ok(TimeControl::Clock {
+            base: seconds(base)?,
+            increment: seconds(increment)?,
+        })
+    }
+}
+
+/// Early ends of games whose outcome is clear from the reported scores. Zero counts disable
+/// a rule.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Adjudication {
+    /// Draw once both sides scored within `draw_score` for that many moves each
+    pub draw_moves: usize,
+    pub draw_score: i32,
+    /// Win once both sides agreed on a score beyond `resign_score` for that many moves each
+    pub resign_moves: usize,
+    pub resign_score: i32,
+    /// Draw after that many moves
+    pub max_moves: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    fn win_for(side: Side) -> GameResult {
+        match side {
+            Side::White => GameResult::WhiteWins,
+            Side::Black => GameResult::BlackWins,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        })
+    }
+}
+
+/// Value of the PGN `Termination` tag
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Termination {
+    Normal,
+    TimeForfeit,
+    Adjudication,
+    RulesInfraction,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Termination::Normal => "normal",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Adjudication => "adjudication",
+            Termination::RulesInfraction => "rules infraction",
+        })
+    }
+}
+
+/// Start of a game: a position, or the start position when `None`, and moves played from it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Opening {
+    pub fen: Option<String>,
+    pub moves: Vec<Move>,
+}
+
+/// Openings of an EPD file, one position per line
+fn parse_epd_openings(text: &str) -> io::Result<Vec<Opening>> {
+    let epds = parse_epds(text).map_err(invalid_data)?;
+    Ok(epds
+        .into_iter()
+        .map(|epd| Opening {
+            fen: Some(epd.board.to_fen()),
+            moves: vec![],
+        })
+        .collect())
+}
+
+/// Openings of a PGN file, keeping the mainline and the `FEN` tag of each game
+fn parse_pgn_openings<R: BufRead>(reader: R) -> io::Result<Vec<Opening>> {
+    PgnReader::new(reader)
+        .map(|game| {
+            let game = game?;
+            Ok(Opening {
+                fen: game.get_tag("FEN").map(str::to_string),
+                moves: game.mainline(),
+            })
+        })
+        .collect()
+}
+
+/// Openings of an EPD or PGN file, told apart by the extension
+pub fn load_openings<P: AsRef<Path>>(filename: P) -> io::Result<Vec<Opening>> {
+    match filename.as_ref().extension().and_then(|ext| ext.to_str()) {
+        Some("epd") => parse_epd_openings(&fs::read_to_string(filename)?),
+        _ => parse_pgn_openings(BufReader::new(File::open(filename)?)),
+    }
+}
+
+/// Finished game with the score each engine reported for its moves
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub white: String,
+    pub black: String,
+    pub fen: Option<String>,
+    /// Moves from the start, the opening included
+    pub moves: Vec<Move>,
+    /// Score and thinking time of each move, `None` for opening moves
+    pub evals: Vec<Option<(Option<Score>, Duration)>>,
+    pub result: GameResult,
+    pub termination: Termination,
+    /// Human readable reason of the result
+    pub reason: String,
+}
+
+impl Game {
+    /// PGN of the game as round `round` of a match, each engine move annotated with its score
+    /// from white's point of view and its thinking time
+    pub fn to_pgn(&self, round: usize) -> PgnGame {
+        let board = Board::from_fen(self.fen.as_deref().unwrap_or(STARTPOS));
+        let mut pgn = PgnGame::new(board.clone());
+        pgn.set_tag("Event", "fchess match");
+        pgn.set_tag("Round", &round.to_string());
+        pgn.set_tag("White", &self.white);
+        pgn.set_tag("Black", &self.black);
+        pgn.set_tag("Termination", &self.termination.to_string());
+
+        let mut turn = board.get_turn();
+        for (index, mov) in self.moves.iter().enumerate() {
+            let node = pgn.push(mov.clone()).unwrap();
+            if let Some(Some((score, time))) = self.evals.get(index) {
+                let mut comment = vec![];
+                if let Some(score) = score {
+                    comment.push(eval_annotation(match (turn, *score) {
+                        (Side::White, score) => score,
+                        (Side::Black, Score::Centipawns(cp)) => Score::Centipawns(-cp),
+                        (Side::Black, Score::Mate(moves)) => Score::Mate(-moves),
+                    }));
+                }
+                comment.push(elapsed_annotation(*time));
+                node.comments.push(comment.join(" "));
+            }
+            if index + 1 == self.moves.len() {
+                node.comments.push(self.reason.clone());
+            }
+            turn = !turn;
+        }
+        pgn.set_result(&self.result.to_string());
+        pgn
+    }
+}
+
+/// Neither side can mate: bare kings with at most one minor piece between them
+fn insufficient_material(board: &Board) -> bool {
+    let mut minors = 0;
+    for index in 0..64 {
+        match board.piece_at(Square::from_index(index)) {
+            Some(PieceType::WhiteKing)
+            | Some(PieceType::BlackKing)
+            | Some(PieceType::NoPiece)
+            | None => {}
+            Some(PieceType::WhiteKnight)
+            | Some(PieceType::WhiteBishop)
+            | Some(PieceType::BlackKnight)
+            | Some(PieceType::BlackBishop) => minors += 1,
+            Some(_) => return false,
+        }
+    }
+    minors <= 1
+}
+
+/// Result of the position by the rules, given the hashes of the earlier positions
+fn rules_result(
+    move_generator: &MoveGenerator,
+    board: &Board,
+    hashes: &[u64],
+) -> Option<(GameResult, &'static str)> {
+    let turn = board.get_turn();
+    if move_generator.legal_moves(board).is_empty() {
+        return Some(if move_generator.in_check(board, turn) {
+            (GameResult::win_for(!turn), "checkmate")
+        } else {
+            (GameResult::Draw, "stalemate")
+        });
+    }
+    if board.get_half_move_clock() >= 100 {
+        return Some((GameResult::Draw, "fifty move rule"));
+    }
+    let hash = board.zobryst_hash(&turn);
+    if hashes.iter().filter(|&&earlier| earlier == hash).count() >= 2 {
+        return Some((GameResult::Draw, "threefold repetition"));
+    }
+    if insufficient_material(board) {
+        return Some((GameResult::Draw, "insufficient material"));
+    }
+    None
+}
+
+fn centipawns(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => MATE_CENTIPAWNS - moves,
+        Score::Mate(moves) => -MATE_CENTIPAWNS - moves,
+    }
+}
+
+/// Result by adjudication, given the scores of the engine moves from white's point of view
+fn adjudicate(adjudication: &Adjudication, scores: &[Option<i32>]) -> Option<GameResult> {
+    let last = |moves: usize| -> Option<Vec<i32>> {
+        if moves == 0 || scores.len() < 2 * moves {
+            return None;
+        }
+        scores[scores.len() - 2 * moves..].iter().copied().collect()
+    };
+
+    if let Some(last) = last(adjudication.resign_moves) {
+        if last.iter().all(|&score| score >= adjudication.resign_score) {
+            return Some(GameResult::WhiteWins);
+        }
+        if last
+            .iter()
+            .all(|&score| score <= -adjudication.resign_score)
+        {
+            return Some(GameResult::BlackWins);
+        }
+    }
+    if let Some(last) = last(adjudication.draw_moves) {
+        if last
+            .iter()
+            .all(|&score| score.abs() <= adjudication.draw_score)
+        {
+            return Some(GameResult::Draw);
+        }
+    }
+    None
+}
+
+/// Play a game from `opening` between `white` and `black`
+pub fn play_game(
+    white: &mut dyn Player,
+    black: &mut dyn Player,
+    opening: &Opening,
+    time_control: &TimeControl,
+    adjudication: &Adjudication,
+) -> io::Result<Game> {
+    let move_generator = MoveGenerator::new();
+    let fen = opening.fen.clone();
+    let mut board = Board::try_from_fen(fen.as_deref().unwrap_or(STARTPOS))
+        .ok_or_else(|| invalid_data("invalid opening position".to_string()))?;
+    let mut hashes = vec![];
+    let mut moves = vec![];
+    for mov in &opening.moves {
+        let mov = move_generator
+            .legal_moves(&board)
+            .into_iter()
+            .find(|legal| legal.to_algebraic() == mov.to_algebraic())
+            .ok_or_else(|| invalid_data(format!("illegal opening move {}", mov.to_algebraic())))?;
+        hashes.push(board.zobryst_hash(&board.get_turn()));
+        board = board.apply(mov.clone()).unwrap();
+        moves.push(mov);
+    }
+
+    white.new_game()?;
+    black.new_game()?;
+    let mut game = Game {
+        white: white.get_name(),
+        black: black.get_name(),
+        fen,
+        evals: vec![None; moves.len()],
+        moves,
+        result: GameResult::Draw,
+        termination: Termination::Normal,
+        reason: String::new(),
+    };
+    let (mut white_clock, mut black_clock) = match time_control {
+        TimeControl::Clock { base, .. } => (*base, *base),
+        _ => (Duration::ZERO, Duration::ZERO),
+    };
+    let mut scores = vec![];
+
+    loop {
+        if let Some((result, reason)) = rules_result(&move_generator, &board, &hashes) {
+            game.result = result;
+            game.reason = reason.to_string();
+            return Ok(game);
+        }
+        if let Some(result) = adjudicate(adjudication, &scores) {
+            game.result = result;
+            game.termination = Termination::Adjudication;
+            game.reason = "agreed scores".to_string();
+            return Ok(game);
+        }
+        let played = game.evals.iter().filter(|eval| eval.is_some()).count();
+        if adjudication.max_moves > 0 && played >= 2 * adjudication.max_moves {
+            game.termination = Termination::Adjudication;
+            game.reason = "move limit".to_string();
+            return Ok(game);
+        }
+
+        let turn = board.get_turn();
+        let mut limits = SearchLimits::default();
+        match *time_control {
+            TimeControl::Clock { increment, .. } => {
+                limits.wtime = Some(white_clock);
+                limits.btime = Some(black_clock);
+                limits.winc = Some(increment);
+                limits.binc = Some(increment);
+            }
+            TimeControl::MoveTime(time) => limits.movetime = Some(time),
+            TimeControl::Depth(depth) => limits.depth = Some(depth),
+            TimeControl::Nodes(nodes) => limits.nodes = Some(nodes),
+        }
+        let player: &mut dyn Player = match turn {
+            Side::White => &mut *white,
+            Side::Black => &mut *black,
+        };
+
+        let start = Instant::now();
+        let (mov, score) = player.go(game.fen.as_deref(), &game.moves, &board, &limits)?;
+        let elapsed = start.elapsed();
+
+        if let TimeControl::Clock { increment, .. } = *time_control {
+            let clock = match turn {
+                Side::White => &mut white_clock,
+                Side::Black => &mut black_clock,
+            };
+            if elapsed > *clock {
+                game.result = GameResult::win_for(!turn);
+                game.termination = Termination::TimeForfeit;
+                game.reason = format!("{turn:?} loses on time");
+                return Ok(game);
+            }
+            *clock = *clock - elapsed + increment;
+        }
+
+        let legal = mov.and_then(|mov| {
+            move_generator
+                .legal_moves(&board)
+                .into_iter()
+                .find(|legal| {
+                    legal
+                        .to_algebraic()
+                        .eq_ignore_ascii_case(&mov.to_algebraic())
+                })
+        });
+        let Some(mov) = legal else {
+            game.result = GameResult::win_for(!turn);
+            game.termination = Termination::RulesInfraction;
+            game.reason = format!("{turn:?} makes an illegal move");
+            return Ok(game);
+        };
+
+        scores.push(score.map(|score| match turn {
+            Side::White => centipawns(score),
+            Side::Black => -centipawns(score),
+        }));
+        hashes.push(board.zobryst_hash(&turn));
+        board = board.apply(mov.clone()).unwrap();
+        game.moves.push(mov);
+        game.evals.push(Some((score, elapsed)));
+    }
+}
+
+/// Wins, draws and losses of the first player of a match
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Score of two players `elo` apart, between 0 and 1
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo_difference(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl MatchScore {
+    /// Count a game where the first player had `side`
+    pub fn add(&mut self, result: GameResult, side: Side) {
+        match (result, side) {
+            (GameResult::Draw, _) => self.draws += 1,
+            (GameResult::WhiteWins, Side::White) | (GameResult::BlackWins, Side::Black) => {
+                self.wins += 1
+            }
+            _ => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Wins, draws and losses, counting half a game of each missing result when every game
+    /// ended the same way so that a clean sweep has a finite Elo and a nonzero variance
+    fn counts(&self) -> [f64; 3] {
+        let counts = [self.wins as f64, self.draws as f64, self.losses as f64];
+        let games = self.games() as f64;
+        if counts.contains(&games) {
+            counts.map(|count| if count == 0.0 { 0.5 } else { count })
+        } else {
+            counts
+        }
+    }
+
+    /// Number of games, mean points per game and their variance per game, with the counts of
+    /// `counts`
+    fn mean_variance(&self) -> (f64, f64, f64) {
+        let counts = self.counts();
+        let games: f64 = counts.iter().sum();
+        let [wins, draws, losses] = counts.map(|count| count / games);
+        let mean = wins + draws / 2.0;
+        let variance =
+            wins * (1.0 - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2);
+        (games, mean, variance)
+    }
+
+    /// Points per game of the first player
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Elo difference in favour of the first player and the half width of its 95% confidence
+    /// interval
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        if self.games() == 0 {
+            return None;
+        }
+        let (games, mean, variance) = self.mean_variance();
+        let deviation = (variance / games).sqrt();
+        // the interval stays half a game away from the scores of infinite Elo differences
+        let margin = 0.5 / games;
+        let lower = elo_difference((mean - 1.96 * deviation).max(margin));
+        let upper = elo_difference((mean + 1.96 * deviation).min(1.0 - margin));
+        Some((elo_difference(mean), (upper - lower) / 2.0))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SprtVerdict {
+    /// The first player is not `elo1` stronger
+    AcceptH0,
+    /// The first player is not `elo0` stronger or weaker
+    AcceptH1,
+}
+
+/// Sequential probability ratio test of the Elo difference being `elo1` rather than `elo0`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// Probability of accepting H1 when H0 holds
+    pub alpha: f64,
+    /// Probability of accepting H0 when H1 holds
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    /// Log-likelihood ratio bounds for accepting H0 and H1
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Log-likelihood ratio of H1 against H0, with the normal approximation of the
+    /// trinomial game outcomes
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let (games, mean, variance) = score.mean_variance();
+        let (score0, score1) = (expected_score(self.elo0), expected_score(self.elo1));
+        games * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+
+    pub fn verdict(&self, score: &MatchScore) -> Option<SprtVerdict> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(SprtVerdict::AcceptH1)
+        } else if llr <= lower {
+            Some(SprtVerdict::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::pgn::read_games;
+
+    fn player(name: &str) -> SolverPlayer {
+        SolverPlayer::new(name, Solver::new())
+    }
+
+    #[rstest]
+    #[case("10+0.1", Duration::from_secs(10), Duration::from_millis(100))]
+    #[case("60", Duration::from_secs(60), Duration::ZERO)]
+    fn test_parse_time_control(
+        #[case] text: &str,
+        #[case] base: Duration,
+        #[case] increment: Duration,
+    ) {
+        assert_eq!(text.parse(), Ok(TimeControl::Clock { base, increment }));
+    }
+
+    #[test]
+    fn test_parse_openings() {
+        let epd = parse_epd_openings("4k3/8/8/8/8/8/4P3/4K3 w - - bm e4;\n\n").unwrap();
+        assert_eq!(epd.len(), 1);
+        assert_eq!(
+            epd[0].fen.as_deref(),
+            Some("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")
+        );
+        assert!(parse_epd_openings("not a position").is_err());
+
+        let pgn = "[Event \"?\"]\n\n1. e4 {best by test} e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 1-0\n\n\
+                   [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 *\n";
+        let openings = parse_pgn_openings(pgn.as_bytes()).unwrap();
+        let algebraic = |opening: &Opening| -> Vec<String> {
+            opening.moves.iter().map(Move::to_algebraic).collect()
+        };
+        assert_eq!(openings.len(), 2);
+        assert_eq!(algebraic(&openings[0]), ["e2e4", "e7e5", "g1f3", "b8c6"]);
+        assert_eq!(
+            openings[1].fen.as_deref(),
+            Some("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")
+        );
+        assert_eq!(algebraic(&openings[1]), ["e2e4"]);
+        assert!(parse_pgn_openings("1. e5".as_bytes()).is_err());
+    }
+
+    #[rstest]
+    #[case(None, &["f2f3", "e7e5", "g2g4", "d8h4"], GameResult::BlackWins, "checkmate")]
+    #[case(Some("k7/8/1Q6/8/8/8/8/6K1 w - - 0 1"), &[], GameResult::Draw, "stalemate")]
+    #[case(
+        Some("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"),
+        &[],
+        GameResult::Draw,
+        "insufficient material"
+    )]
+    fn test_play_game_ends_by_the_rules(
+        #[case] fen: Option<&str>,
+        #[case] moves: &[&str],
+        #[case] result: GameResult,
+        #[case] reason: &str,
+    ) {
+        let opening = Opening {
+            fen: fen.map(str::to_string),
+            moves: moves
+                .iter()
+                .filter_map(|mov| Move::from_full_algebraic(mov))
+                .collect(),
+        };
+        let game = play_game(
+            &mut player("white"),
+            &mut player("black"),
+            &opening,
+            &TimeControl::Depth(3),
+            &Adjudication::default(),
+        )
+        .unwrap();
+
+        assert_eq!(game.result, result);
+        assert_eq!(game.reason, reason);
+        assert_eq!(game.termination, Termination::Normal);
+    }
+
+    #[test]
+    fn test_play_game_with_move_limit_and_pgn() {
+        let opening = Opening {
+            fen: None,
+            moves: vec![Move::from_full_algebraic("e2e4").unwrap()],
+        };
+        let adjudication = Adjudication {
+            max_moves: 2,
+            ..Default::default()
+        };
+        let game = play_game(
+            &mut player("one"),
+            &mut player("two"),
+            &opening,
+            &TimeControl::Depth(1),
+            &adjudication,
+        )
+        .unwrap();
+
+        assert_eq!(game.moves.len(), 5);
+        assert_eq!(game.evals[0], None);
+        assert_eq!(game.result, GameResult::Draw);
+        assert_eq!(game.termination, Termination::Adjudication);
+
+        let pgn = game.to_pgn(3);
+        assert_eq!(pgn.get_tag("Round"), Some("3"));
+        assert_eq!(pgn.mainline(), game.moves);
+
+        let text = pgn.to_string();
+        assert!(text.contains("[Round \"3\"]\n[White \"one\"]\n[Black \"two\"]"));
+        assert!(text.contains("[Termination \"adjudication\"]\n\n1. e4 "));
+        assert!(text.trim_end().ends_with("{move limit} 1/2-1/2"));
+        assert_eq!(read_games(&text).unwrap(), vec![pgn]);
+    }
+
+    #[test]
+    fn test_pgn_of_game_from_fen() {
+        let game = Game {
+            white: "a".to_string(),
+            black: "b".to_string(),
+            fen: Some("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 7".to_string()),
+            moves: vec![
+                Move::from_full_algebraic("g8h8").unwrap(),
+                Move::from_full_algebraic("a1a8").unwrap(),
+            ],
+            evals: vec![
+                None,
+                Some((Some(Score::Mate(1)), Duration::from_millis(20))),
+            ],
+            result: GameResult::WhiteWins,
+            termination: Termination::Normal,
+            reason: "checkmate".to_string(),
+        };
+        let pgn = game.to_pgn(1).to_string();
+
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 7\"]"));
+        assert!(pgn.contains("\n7... Kh8 8. Ra8# {[%eval #1] [%emt 0:00:00]} {checkmate} 1-0\n"));
+    }
+
+    #[rstest]
+    #[case(&[Some(10), Some(-5), Some(0), Some(3)], GameResult::Draw)]
+    #[case(&[Some(900), Some(1000), Some(800), Some(850)], GameResult::WhiteWins)]
+    #[case(&[Some(-900), Some(-1000), Some(-800), Some(-850)], GameResult::BlackWins)]
+    fn test_adjudicate(#[case] scores: &[Option<i32>], #[case] result: GameResult) {
+        let adjudication = Adjudication {
+            draw_moves: 2,
+            draw_score: 10,
+            resign_moves: 2,
+            resign_score: 800,
+            max_moves: 0,
+        };
+        assert_eq!(adjudicate(&adjudication, scores), Some(result));
+        assert_eq!(adjudicate(&adjudication, &scores[1..]), None);
+        assert_eq!(adjudicate(&Adjudication::default(), scores), None);
+    }
+
+    #[test]
+    fn test_adjudicate_needs_scores() {
+        let adjudication = Adjudication {
+            draw_moves: 1,
+            draw_score: 10,
+            ..Default::default()
+        };
+        assert_eq!(adjudicate(&adjudication, &[Some(0), None]), None);
+    }
+
+    #[test]
+    fn test_match_score_elo() {
+        let mut score = MatchScore::default();
+        assert_eq!(score.elo(), None);
+
+        score.add(GameResult::WhiteWins, Side::White);
+        score.add(GameResult::WhiteWins, Side::Black);
+        score.add(GameResult::Draw, Side::White);
+        score.add(GameResult::BlackWins, Side::Black);
+        assert_eq!(
+            score,
+            MatchScore {
+                wins: 2,
+                draws: 1,
+                losses: 1
+            }
+        );
+        assert_eq!(score.score(), 0.625);
+
+        let (elo, error) = score.elo().unwrap();
+        assert!((elo - 88.7).abs() < 0.1);
+        assert!(error > 0.0);
+    }
+
+    #[rstest]
+    #[case(10, 0, 0)]
+    #[case(0, 0, 10)]
+    #[case(0, 10, 0)]
+    #[case(3, 1, 0)]
+    fn test_match_score_elo_is_finite(#[case] wins: u32, #[case] draws: u32, #[case] losses: u32) {
+        let score = MatchScore {
+            wins,
+            draws,
+            losses,
+        };
+        let (elo, error) = score.elo().unwrap();
+        assert!(elo.is_finite() && error.is_finite());
+        assert!(error > 0.0);
+        assert_eq!(elo > 0.0, wins > losses);
+        assert_eq!(elo < 0.0, wins < losses);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+
+        let even = MatchScore {
+            wins: 5000,
+            draws: 10000,
+            losses: 5000,
+        };
+        assert!(sprt.llr(&even) < 0.0);
+        assert_eq!(sprt.verdict(&even), Some(SprtVerdict::AcceptH0));
+
+        let stronger = MatchScore {
+            wins: 700,
+            draws: 1000,
+            losses: 300,
+        };
+        assert_eq!(sprt.verdict(&stronger), Some(SprtVerdict::AcceptH1));
+        assert_eq!(sprt.verdict(&MatchScore::default()), None);
+    }
+
+    #[rstest]
+    #[case(30, 0, 0, Some(SprtVerdict::AcceptH1))]
+    #[case(0, 0, 30, Some(SprtVerdict::AcceptH0))]
+    #[case(0, 400, 0, Some(SprtVerdict::AcceptH0))]
+    #[case(2, 0, 0, None)]
+    fn test_sprt_sweep(
+        #[case] wins: u32,
+        #[case] draws: u32,
+        #[case] losses: u32,
+        #[case] verdict: Option<SprtVerdict>,
+    ) {
+        let score = MatchScore {
+            wins,
+            draws,
+            losses,
+        };
+        assert_eq!(Sprt::default().verdict(&score), verdict);
+    }
+}
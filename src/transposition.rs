@@ -0,0 +1,310 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::moves::Move;
+use crate::piece::PieceType;
+use crate::solver::MATE_THRESHOLD;
+use crate::square::Square;
+
+/// Memory used when no size is given
+const DEFAULT_MEGABYTES: usize = 16;
+
+/// How a stored score relates to the true score of the position
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The true score is at least the stored score
+    Lower,
+    /// The true score is at most the stored score
+    Upper,
+}
+
+/// Search result of a single position
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranspositionEntry {
+    depth: u8,
+    score: i32,
+    bound: Bound,
+    mov: Option<Move>,
+}
+
+impl TranspositionEntry {
+    pub fn get_depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Score of the position when found `ply` plies from the root
+    pub fn get_score(&self, ply: i32) -> i32 {
+        if self.score >= MATE_THRESHOLD {
+            self.score - ply
+        } else if self.score <= -MATE_THRESHOLD {
+            self.score + ply
+        } else {
+            self.score
+        }
+    }
+
+    pub fn get_bound(&self) -> Bound {
+        self.bound
+    }
+
+    pub fn get_move(&self) -> Option<&Move> {
+        self.mov.as_ref()
+    }
+}
+
+/// Set in the data of every stored entry so that an empty slot never matches
+const USED: u64 = 1 << 26;
+
+impl TranspositionEntry {
+    /// Pack the entry in a single word: move in bits 0-15, depth in 16-23, bound in 24-25,
+    /// the used flag in 26 and the score in the upper half
+    fn encode(&self) -> u64 {
+        let mov = self.mov.as_ref().map_or(0, |mov| {
+            let promotion = mov.get_promotion().map_or(0, |piece| piece as u64 + 1);
+            mov.get_src().get_index() as u64
+                | (mov.get_dst().get_index() as u64) << 6
+                | promotion << 12
+        });
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+
+        mov | (self.depth as u64) << 16 | bound << 24 | USED | (self.score as u32 as u64) << 32
+    }
+
+    fn decode(data: u64) -> Option<TranspositionEntry> {
+        if data & USED == 0 {
+            return None;
+        }
+
+        let src = Square::from_index((data & 0x3f) as u8);
+        let dst = Square::from_index((data >> 6 & 0x3f) as u8);
+        let mov = match (data >> 12 & 0xf) as u8 {
+            _ if src == dst => None,
+            0 => Some(Move::new(src, dst)),
+            promotion => {
+                let piece: PieceType = num::FromPrimitive::from_u8(promotion - 1)?;
+                Some(Move::with_promotion(src, dst, piece))
+            }
+        };
+        let bound = match data >> 24 & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+
+        Some(TranspositionEntry {
+            depth: (data >> 16) as u8,
+            score: (data >> 32) as u32 as i32,
+            bound,
+            mov,
+        })
+    }
+}
+
+/// Slot of the table. The key is stored xored with the data so that an entry torn by
+/// concurrent writes from several search threads fails the key check instead of being used.
+#[derive(Debug, Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Cache of search results keyed by the Zobryst hash, always replacing on collisions.
+/// Probes and stores take a shared reference so the table can be shared between threads
+/// without locking.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::with_megabytes(DEFAULT_MEGABYTES)
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> TranspositionTable {
+        TranspositionTable {
+            slots: (0..size.max(1)).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    /// Table using about `megabytes` of memory
+    pub fn with_megabytes(megabytes: usize) -> TranspositionTable {
+        let slot_size = std::mem::size_of::<Slot>();
+        TranspositionTable::new(megabytes * 1024 * 1024 / slot_size)
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TranspositionEntry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.check.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        TranspositionEntry::decode(data)
+    }
+
+    /// Store the result of a search `ply` plies from the root
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u8,
+        ply: i32,
+        score: i32,
+        bound: Bound,
+        mov: Option<Move>,
+    ) {
+        // mates are stored as a distance from this position rather than from the root
+        let score = if score >= MATE_THRESHOLD {
+            score + ply
+        } else if score <= -MATE_THRESHOLD {
+            score - ply
+        } else {
+            score
+        };
+
+        let data = TranspositionEntry {
+            depth,
+            score,
+            bound,
+            mov,
+        }
+        .encode();
+        let slot = self.slot(key);
+        slot.check.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Permille of the table in use, sampled over the first thousand entries
+    pub fn hashfull(&self) -> u16 {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| slot.data.load(Ordering::Relaxed) & USED != 0)
+            .count();
+        (used * 1000 / sample) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_and_replace() {
+        let table = TranspositionTable::new(16);
+        let mov = Move::from_full_algebraic("e2e4");
+
+        table.store(3, 2, 0, 15, Bound::Exact, mov.clone());
+        let entry = table.probe(3).unwrap();
+        assert_eq!(entry.get_depth(), 2);
+        assert_eq!(entry.get_score(0), 15);
+        assert_eq!(entry.get_bound(), Bound::Exact);
+        assert_eq!(entry.get_move(), mov.as_ref());
+        assert_eq!(table.probe(19), None);
+
+        table.store(19, 1, 0, -4, Bound::Upper, None);
+        assert_eq!(table.probe(3), None);
+        assert_eq!(table.probe(19).unwrap().get_score(0), -4);
+    }
+
+    #[test]
+    fn test_mate_scores_are_relative_to_the_node() {
+        let table = TranspositionTable::new(16);
+        table.store(1, 3, 2, 100_000 - 5, Bound::Exact, None);
+        table.store(2, 3, 2, -100_000 + 4, Bound::Exact, None);
+
+        assert_eq!(table.probe(1).unwrap().get_score(4), 100_000 - 7);
+        assert_eq!(table.probe(2).unwrap().get_score(4), -100_000 + 6);
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let table = TranspositionTable::new(16);
+        let promotion = Move::from_full_algebraic("b7a8q");
+        table.store(
+            5,
+            200,
+            0,
+            -MATE_THRESHOLD + 10,
+            Bound::Lower,
+            promotion.clone(),
+        );
+
+        let entry = table.probe(5).unwrap();
+        assert_eq!(entry.get_depth(), 200);
+        assert_eq!(entry.get_score(0), -MATE_THRESHOLD + 10);
+        assert_eq!(entry.get_bound(), Bound::Lower);
+        assert_eq!(entry.get_move(), promotion.as_ref());
+    }
+
+    #[test]
+    fn test_torn_entry_is_rejected() {
+        let table = TranspositionTable::new(1);
+        table.store(1, 4, 0, 30, Bound::Exact, Move::from_full_algebraic("e2e4"));
+        let check = table.slots[0].check.load(Ordering::Relaxed);
+        table.store(
+            2,
+            9,
+            0,
+            -70,
+            Bound::Upper,
+            Move::from_full_algebraic("d2d4"),
+        );
+
+        // key of the first store with the data of the second, as left by racing writers
+        table.slots[0].check.store(check, Ordering::Relaxed);
+        assert_eq!(table.probe(1), None);
+        assert_eq!(table.probe(2), None);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let table = std::sync::Arc::new(TranspositionTable::new(64));
+        let writers: Vec<_> = (0..4u64)
+            .map(|thread| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    for key in 0..10_000u64 {
+                        let score = (key % 1000) as i32;
+                        table.store(key, thread as u8, 0, score, Bound::Exact, None);
+                        if let Some(entry) = table.probe(key ^ 1) {
+                            assert_eq!(entry.get_score(0), ((key ^ 1) % 1000) as i32);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_hashfull_and_clear() {
+        let table = TranspositionTable::new(4);
+        assert_eq!(table.hashfull(), 0);
+        table.store(0, 1, 0, 0, Bound::Lower, None);
+        table.store(1, 1, 0, 0, Bound::Lower, None);
+        assert_eq!(table.hashfull(), 500);
+
+        table.clear();
+        assert_eq!(table.hashfull(), 0);
+    }
+}
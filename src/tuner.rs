@@ -0,0 +1,289 @@
+use std::fs;
+use std::io;
+
+use regex::Regex;
+
+use crate::board::Board;
+use crate::evaluator::{ClassicalEvaluator, Evaluator};
+use crate::params::EvalParams;
+use crate::side::Side;
+
+/// Position of a labelled dataset with the result of the game it was taken from
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuningPosition {
+    board: Board,
+    /// Game result from white's point of view: 1.0 win, 0.5 draw, 0.0 loss
+    result: f64,
+}
+
+impl TuningPosition {
+    pub fn new(board: Board, result: f64) -> TuningPosition {
+        TuningPosition { board, result }
+    }
+
+    /// Parse a FEN/EPD line labelled with the game result, in any of the usual forms:
+    /// `<fen> [1.0]`, `<fen> 1/2-1/2`, `<fen> c9 "0-1";`. Lines with an invalid FEN give `None`.
+    pub fn from_line(line: &str) -> Option<TuningPosition> {
+        let result_re =
+            Regex::new(r#"\s*(c9\s+)?"?\[?(1-0|0-1|1/2-1/2|1\.0|0\.5|0\.0)\]?"?;?\s*$"#).unwrap();
+
+        let captures = result_re.captures(line)?;
+        let result = match captures.get(2)?.as_str() {
+            "1-0" | "1.0" => 1.0,
+            "0-1" | "0.0" => 0.0,
+            _ => 0.5,
+        };
+        let fen = line[..captures.get(0)?.start()].trim();
+        if fen.is_empty() {
+            return None;
+        }
+
+        Some(TuningPosition::new(Board::try_from_fen(fen)?, result))
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_result(&self) -> f64 {
+        self.result
+    }
+}
+
+/// Read a labelled dataset, skipping empty lines, lines without a result and invalid positions
+pub fn load_dataset(filename: &str) -> io::Result<Vec<TuningPosition>> {
+    Ok(fs::read_to_string(filename)?
+        .lines()
+        .filter_map(TuningPosition::from_line)
+        .collect())
+}
+
+/// Texel tuning of the classical evaluation parameters: minimize the mean squared error
+/// between game results and the winning probability predicted from the static evaluation.
+pub struct Tuner {
+    positions: Vec<TuningPosition>,
+    evaluator: ClassicalEvaluator,
+    k: f64,
+}
+
+impl Tuner {
+    pub fn new(positions: Vec<TuningPosition>) -> Tuner {
+        Tuner {
+            positions,
+            evaluator: ClassicalEvaluator::new(),
+            k: 1.0,
+        }
+    }
+
+    pub fn get_k(&self) -> f64 {
+        self.k
+    }
+
+    pub fn set_k(&mut self, k: f64) {
+        self.k = k;
+    }
+
+    fn sigmoid(&self, score: i32) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-self.k * score as f64 / 400.0))
+    }
+
+    /// Mean squared prediction error of the dataset with the given parameters
+    pub fn error(&mut self, params: &EvalParams) -> f64 {
+        if self.positions.is_empty() {
+            return 0.0;
+        }
+        if self.evaluator.get_params() != params {
+            self.evaluator.set_params(params.clone());
+        }
+
+        let mut error = 0.0;
+        for position in self.positions.iter() {
+            let score = match position.board.get_turn() {
+                Side::White => self.evaluator.evaluate(&position.board),
+                Side::Black => -self.evaluator.evaluate(&position.board),
+            };
+            error += (position.result - self.sigmoid(score)).powi(2);
+        }
+
+        error / self.positions.len() as f64
+    }
+
+    /// Pick the scaling constant `k` that minimizes the error of the given parameters
+    pub fn fit_k(&mut self, params: &EvalParams) -> f64 {
+        let mut best = (self.k, self.error(params));
+        for step in 1..=40 {
+            self.k = step as f64 * 0.05;
+            let error = self.error(params);
+            if error < best.1 {
+                best = (self.k, error);
+            }
+        }
+        self.k = best.0;
+        best.0
+    }
+
+    /// Texel local search: nudge every weight by one in both directions and keep the changes
+    /// that reduce the error, until no change helps or `iterations` passes have been made.
+    /// `progress` is called after each pass with the pass number, error and current weights.
+    pub fn local_search<F>(
+        &mut self,
+        params: EvalParams,
+        iterations: usize,
+        mut progress: F,
+    ) -> EvalParams
+    where
+        F: FnMut(usize, f64, &EvalParams),
+    {
+        let mut best = params;
+        let mut best_error = self.error(&best);
+
+        for iteration in 1..=iterations {
+            let mut improved = false;
+            for index in 0..best.weights_mut().len() {
+                for delta in [1, -1] {
+                    let mut candidate = best.clone();
+                    *candidate.weights_mut()[index] += delta;
+                    let error = self.error(&candidate);
+                    if error < best_error {
+                        best = candidate;
+                        best_error = error;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+
+            progress(iteration, best_error, &best);
+            if !improved {
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// Gradient descent with a finite difference gradient. Each step moves the weight with
+    /// the steepest gradient by `learning_rate` centipawns and the others proportionally.
+    pub fn gradient_descent<F>(
+        &mut self,
+        params: EvalParams,
+        iterations: usize,
+        learning_rate: f64,
+        mut progress: F,
+    ) -> EvalParams
+    where
+        F: FnMut(usize, f64, &EvalParams),
+    {
+        let mut params = params;
+        let mut weights: Vec<f64> = params
+            .clone()
+            .weights_mut()
+            .iter()
+            .map(|weight| **weight as f64)
+            .collect();
+
+        for iteration in 1..=iterations {
+            let mut gradient = vec![0.0; weights.len()];
+            for (index, derivative) in gradient.iter_mut().enumerate() {
+                let mut plus = params.clone();
+                *plus.weights_mut()[index] += 1;
+                let mut minus = params.clone();
+                *minus.weights_mut()[index] -= 1;
+                *derivative = (self.error(&plus) - self.error(&minus)) / 2.0;
+            }
+
+            let steepest = gradient.iter().fold(0.0_f64, |acc, g| acc.max(g.abs()));
+            if steepest == 0.0 {
+                progress(iteration, self.error(&params), &params);
+                break;
+            }
+
+            for ((weight, derivative), target) in weights
+                .iter_mut()
+                .zip(gradient.iter())
+                .zip(params.weights_mut())
+            {
+                *weight -= learning_rate * derivative / steepest;
+                *target = weight.round() as i32;
+            }
+
+            progress(iteration, self.error(&params), &params);
+        }
+
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("4k3/8/8/8/8/8/P7/4K3 w - - 0 1 [1.0]", Some(1.0))]
+    #[case("4k3/8/8/8/8/8/P7/4K3 w - - 0 1 [0.5]", Some(0.5))]
+    #[case("4k3/8/8/8/8/8/P7/4K3 w - - 0 1 0-1", Some(0.0))]
+    #[case("4k3/8/8/8/8/8/P7/4K3 w - - 0 1", None)]
+    #[case("4k3/8/8/8/8/8/P7/4K3/8 w - - 0 1 [1.0]", None)]
+    #[case("4k3/8/8/8/8/8/P7/4K3 x - - 0 1 [1.0]", None)]
+    #[case("8/8/8/8/8/8/P7/4K3 w - - 0 1 [1.0]", None)]
+    #[case("", None)]
+    fn test_parse_dataset_line(#[case] line: &str, #[case] result: Option<f64>) {
+        let position = TuningPosition::from_line(line);
+        assert_eq!(position.as_ref().map(|p| p.get_result()), result);
+        if let Some(position) = position {
+            assert_eq!(
+                position.get_board(),
+                &Board::from_fen("4k3/8/8/8/8/8/P7/4K3 w - - 0 1")
+            );
+        }
+    }
+
+    #[rstest]
+    #[case("4k3/8/8/8/8/8/P7/4K3 b - - 1/2-1/2", Some(0.5))]
+    #[case("4k3/8/8/8/8/8/P7/4K3 b - - c9 \"1-0\";", Some(1.0))]
+    #[case("4k3/8/8/8/8/8/P7/4K3 b - -", None)]
+    fn test_parse_epd_dataset_line(#[case] line: &str, #[case] result: Option<f64>) {
+        let position = TuningPosition::from_line(line);
+        assert_eq!(position.as_ref().map(|p| p.get_result()), result);
+        if let Some(position) = position {
+            assert_eq!(
+                position.get_board(),
+                &Board::from_fen("4k3/8/8/8/8/8/P7/4K3 b - -")
+            );
+        }
+    }
+
+    fn dataset() -> Vec<TuningPosition> {
+        vec![
+            TuningPosition::from_line("4k3/8/8/8/8/8/PP6/4K3 w - - 0 1 [0.5]").unwrap(),
+            TuningPosition::from_line("4k3/pp6/8/8/8/8/8/4K3 w - - 0 1 [0.5]").unwrap(),
+            TuningPosition::from_line("4k3/8/8/8/8/8/8/4K3 w - - 0 1 [0.5]").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_local_search_reduces_error() {
+        let mut tuner = Tuner::new(dataset());
+        let params = EvalParams::default();
+        let initial_error = tuner.error(&params);
+
+        let mut passes = 0;
+        let tuned = tuner.local_search(params, 2, |_, _, _| passes += 1);
+
+        assert_eq!(passes, 2);
+        assert!(tuner.error(&tuned) < initial_error);
+    }
+
+    #[test]
+    fn test_gradient_descent_reduces_error() {
+        let mut tuner = Tuner::new(dataset());
+        let params = EvalParams::default();
+        let initial_error = tuner.error(&params);
+
+        let tuned = tuner.gradient_descent(params, 3, 10.0, |_, _, _| {});
+
+        assert!(tuner.error(&tuned) < initial_error);
+    }
+}
@@ -0,0 +1,1396 @@
+//! Universal Chess Interface: typed commands and responses with their text form, and an
+//! engine driver that plays the engine side of the protocol over any reader and writer.
+
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::board::Board;
+use crate::book::{Book, BookPolicy};
+use crate::evaluator::ClassicalEvaluator;
+use crate::move_generator::MoveGenerator;
+use crate::moves::Move;
+use crate::nnue::NnueEvaluator;
+use crate::solver::{Score, SearchInfo, SearchLimits, Solver};
+use crate::tablebase::Tablebases;
+
+pub use crate::board::STARTPOS;
+
+/// Message sent by the GUI to the engine
+#[derive(Clone, Debug, PartialEq)]
+pub enum UciCommand {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    UciNewGame,
+    /// Position given by a FEN, or the start position when `None`, followed by moves in
+    /// long algebraic notation
+    Position {
+        fen: Option<String>,
+        moves: Vec<String>,
+    },
+    Go(SearchLimits),
+    Stop,
+    PonderHit,
+    Quit,
+}
+
+/// Type, default and range of an engine option
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionKind {
+    Check { default: bool },
+    Spin { default: i64, min: i64, max: i64 },
+    Combo { default: String, vars: Vec<String> },
+    Button,
+    String { default: String },
+}
+
+/// Engine option advertised in the reply to `uci`
+#[derive(Clone, Debug, PartialEq)]
+pub struct UciOption {
+    pub name: String,
+    pub kind: OptionKind,
+}
+
+/// Value of an option given by `setoption`
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    /// Empty when unset
+    String(String),
+}
+
+/// Search progress reported by an `info` line, every field being optional
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Info {
+    pub depth: Option<u8>,
+    pub seldepth: Option<u8>,
+    pub multipv: Option<usize>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<Duration>,
+    pub hashfull: Option<u16>,
+    pub pv: Vec<Move>,
+    pub currmove: Option<Move>,
+    pub currmovenumber: Option<usize>,
+    pub string: Option<String>,
+}
+
+/// Message sent by the engine to the GUI
+#[derive(Clone, Debug, PartialEq)]
+pub enum UciResponse {
+    IdName(String),
+    IdAuthor(String),
+    UciOk,
+    ReadyOk,
+    Option(UciOption),
+    /// Best move, `None` when there is no legal move, and the expected reply
+    BestMove {
+        mov: Option<Move>,
+        ponder: Option<Move>,
+    },
+    Info(Info),
+}
+
+fn parse_value<'a, T: FromStr>(
+    name: &str,
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, String> {
+    tokens
+        .next()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("invalid value for {name}"))
+}
+
+/// Move in long algebraic notation with a lowercase promotion piece
+pub(crate) fn move_to_uci(mov: &Move) -> String {
+    mov.to_algebraic().to_lowercase()
+}
+
+fn parse_move(text: &str) -> Result<Move, String> {
+    Move::from_full_algebraic(text).ok_or_else(|| format!("invalid move {text}"))
+}
+
+/// Move of `moves` written as `text`, ignoring the case of the promotion piece
+fn find_move(moves: &[Move], text: &str) -> Option<Move> {
+    moves
+        .iter()
+        .find(|mov| mov.to_algebraic().eq_ignore_ascii_case(text))
+        .cloned()
+}
+
+/// Search limits of the arguments of a `go` command
+fn parse_go(args: &str) -> Result<SearchLimits, String> {
+    let mut limits = SearchLimits::default();
+    let mut tokens = args.split_whitespace().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "wtime" | "btime" | "winc" | "binc" | "movetime" => {
+                // clocks can be negative when a side has just run out of time
+                let millis: i64 = parse_value(token, &mut tokens)?;
+                let duration = Some(Duration::from_millis(millis.max(0) as u64));
+                match token {
+                    "wtime" => limits.wtime = duration,
+                    "btime" => limits.btime = duration,
+                    "winc" => limits.winc = duration,
+                    "binc" => limits.binc = duration,
+                    _ => limits.movetime = duration,
+                }
+            }
+            "movestogo" => limits.movestogo = Some(parse_value(token, &mut tokens)?),
+            "depth" => limits.depth = Some(parse_value(token, &mut tokens)?),
+            "nodes" => limits.nodes = Some(parse_value(token, &mut tokens)?),
+            "mate" => limits.mate = Some(parse_value(token, &mut tokens)?),
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            "searchmoves" => {
+                while let Some(mov) = tokens
+                    .peek()
+                    .and_then(|text| Move::from_full_algebraic(text))
+                {
+                    limits.searchmoves.push(mov);
+                    tokens.next();
+                }
+            }
+            _ => return Err(format!("unknown go parameter {token}")),
+        }
+    }
+
+    Ok(limits)
+}
+
+/// Arguments of a `position` command: `startpos` or `fen <fen>`, optionally followed by
+/// `moves <move>...`
+fn parse_position(args: &str) -> Result<UciCommand, String> {
+    let (setup, moves) = match args.split_once("moves") {
+        Some((setup, moves)) => (setup.trim(), moves.split_whitespace().collect()),
+        None => (args.trim(), vec![]),
+    };
+    let moves = moves.into_iter().map(str::to_string).collect();
+
+    if setup == "startpos" {
+        Ok(UciCommand::Position { fen: None, moves })
+    } else if let Some(fen) = setup.strip_prefix("fen") {
+        Ok(UciCommand::Position {
+            fen: Some(fen.trim().to_string()),
+            moves,
+        })
+    } else {
+        Err(format!("invalid position command: {args}"))
+    }
+}
+
+/// Arguments of a `setoption` command: `name <id> [value <x>]`, where the name may contain
+/// spaces
+fn parse_setoption(args: &str) -> Result<UciCommand, String> {
+    let args = args
+        .trim()
+        .strip_prefix("name")
+        .ok_or_else(|| format!("invalid setoption command: {args}"))?;
+    let (name, value) = match args.split_once(" value") {
+        Some((name, value)) => (name.trim(), Some(value.trim().to_string())),
+        None => (args.trim(), None),
+    };
+
+    Ok(UciCommand::SetOption {
+        name: name.to_string(),
+        value,
+    })
+}
+
+impl FromStr for UciCommand {
+    type Err = String;
+
+    /// Parse a command line. Unknown words before the command are skipped, as the protocol
+    /// asks.
+    fn from_str(line: &str) -> Result<UciCommand, String> {
+        let mut rest = line.trim();
+        loop {
+            let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let args = args.trim();
+            return match word {
+                "uci" => Ok(UciCommand::Uci),
+                "debug" => match args {
+                    "on" => Ok(UciCommand::Debug(true)),
+                    "off" => Ok(UciCommand::Debug(false)),
+                    _ => Err(format!("invalid debug command: {args}")),
+                },
+                "isready" => Ok(UciCommand::IsReady),
+                "setoption" => parse_setoption(args),
+                "ucinewgame" => Ok(UciCommand::UciNewGame),
+                "position" => parse_position(args),
+                "go" => parse_go(args).map(UciCommand::Go),
+                "stop" => Ok(UciCommand::Stop),
+                "ponderhit" => Ok(UciCommand::PonderHit),
+                "quit" => Ok(UciCommand::Quit),
+                _ if args.is_empty() => Err(format!("unknown command {}", line.trim())),
+                _ => {
+                    rest = args;
+                    continue;
+                }
+            };
+        }
+    }
+}
+
+fn format_millis(f: &mut fmt::Formatter, name: &str, time: Option<Duration>) -> fmt::Result {
+    match time {
+        Some(time) => write!(f, " {name} {}", time.as_millis()),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for UciCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciCommand::Uci => write!(f, "uci"),
+            UciCommand::Debug(on) => write!(f, "debug {}", if *on { "on" } else { "off" }),
+            UciCommand::IsReady => write!(f, "isready"),
+            UciCommand::SetOption { name, value } => {
+                write!(f, "setoption name {name}")?;
+                match value {
+                    Some(value) => write!(f, " value {value}"),
+                    None => Ok(()),
+                }
+            }
+            UciCommand::UciNewGame => write!(f, "ucinewgame"),
+            UciCommand::Position { fen, moves } => {
+                match fen {
+                    Some(fen) => write!(f, "position fen {fen}")?,
+                    None => write!(f, "position startpos")?,
+                }
+                if !moves.is_empty() {
+                    write!(f, " moves {}", moves.join(" "))?;
+                }
+                Ok(())
+            }
+            UciCommand::Go(limits) => {
+                write!(f, "go")?;
+                if !limits.searchmoves.is_empty() {
+                    let moves: Vec<String> = limits.searchmoves.iter().map(move_to_uci).collect();
+                    write!(f, " searchmoves {}", moves.join(" "))?;
+                }
+                if limits.ponder {
+                    write!(f, " ponder")?;
+                }
+                format_millis(f, "wtime", limits.wtime)?;
+                format_millis(f, "btime", limits.btime)?;
+                format_millis(f, "winc", limits.winc)?;
+                format_millis(f, "binc", limits.binc)?;
+                if let Some(movestogo) = limits.movestogo {
+                    write!(f, " movestogo {movestogo}")?;
+                }
+                if let Some(depth) = limits.depth {
+                    write!(f, " depth {depth}")?;
+                }
+                if let Some(nodes) = limits.nodes {
+                    write!(f, " nodes {nodes}")?;
+                }
+                if let Some(mate) = limits.mate {
+                    write!(f, " mate {mate}")?;
+                }
+                format_millis(f, "movetime", limits.movetime)?;
+                if limits.infinite {
+                    write!(f, " infinite")?;
+                }
+                Ok(())
+            }
+            UciCommand::Stop => write!(f, "stop"),
+            UciCommand::PonderHit => write!(f, "ponderhit"),
+            UciCommand::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl UciOption {
+    pub fn check(name: &str, default: bool) -> UciOption {
+        UciOption {
+            name: name.to_string(),
+            kind: OptionKind::Check { default },
+        }
+    }
+
+    pub fn spin(name: &str, default: i64, min: i64, max: i64) -> UciOption {
+        UciOption {
+            name: name.to_string(),
+            kind: OptionKind::Spin { default, min, max },
+        }
+    }
+
+    pub fn combo(name: &str, default: &str, vars: &[&str]) -> UciOption {
+        UciOption {
+            name: name.to_string(),
+            kind: OptionKind::Combo {
+                default: default.to_string(),
+                vars: vars.iter().map(|var| var.to_string()).collect(),
+            },
+        }
+    }
+
+    pub fn string(name: &str, default: &str) -> UciOption {
+        UciOption {
+            name: name.to_string(),
+            kind: OptionKind::String {
+                default: default.to_string(),
+            },
+        }
+    }
+
+    /// Check a `setoption` value against the type and range of the option
+    pub fn parse_value(&self, value: Option<&str>) -> Result<OptionValue, String> {
+        let invalid = || match value {
+            Some(value) => format!("invalid value {value} for option {}", self.name),
+            None => format!("missing value for option {}", self.name),
+        };
+        match (&self.kind, value) {
+            (OptionKind::Check { .. }, Some("true")) => Ok(OptionValue::Check(true)),
+            (OptionKind::Check { .. }, Some("false")) => Ok(OptionValue::Check(false)),
+            (OptionKind::Spin { min, max, .. }, Some(value)) => value
+                .parse()
+                .ok()
+                .filter(|value| (min..=max).contains(&value))
+                .map(OptionValue::Spin)
+                .ok_or_else(invalid),
+            (OptionKind::Combo { vars, .. }, Some(value)) => vars
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(value))
+                .map(|var| OptionValue::Combo(var.clone()))
+                .ok_or_else(invalid),
+            (OptionKind::Button, _) => Ok(OptionValue::Button),
+            (OptionKind::String { .. }, None | Some("<empty>")) => {
+                Ok(OptionValue::String(String::new()))
+            }
+            (OptionKind::String { .. }, Some(value)) => Ok(OptionValue::String(value.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for UciOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.kind {
+            OptionKind::Check { default } => write!(f, "check default {default}"),
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {default} min {min} max {max}")
+            }
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {default}")?;
+                for var in vars {
+                    write!(f, " var {var}")?;
+                }
+                Ok(())
+            }
+            OptionKind::Button => write!(f, "button"),
+            OptionKind::String { default } if default.is_empty() => {
+                write!(f, "string default <empty>")
+            }
+            OptionKind::String { default } => write!(f, "string default {default}"),
+        }
+    }
+}
+
+/// Arguments of an `option` line: `name <id> type <t> [default <x>] [min <x>] [max <x>]
+/// [var <x>]...`, where the name and string values may contain spaces
+fn parse_option(args: &str) -> Result<UciOption, String> {
+    let invalid = || format!("invalid option: {args}");
+    let args = args.trim().strip_prefix("name").ok_or_else(invalid)?;
+    let (name, args) = args.split_once(" type ").ok_or_else(invalid)?;
+    let (kind, args) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+
+    // values of the fields, each running up to the next keyword
+    let mut fields: Vec<(&str, Vec<&str>)> = vec![];
+    for token in args.split_whitespace() {
+        match token {
+            "default" | "min" | "max" | "var" => fields.push((token, vec![])),
+            _ => fields.last_mut().ok_or_else(invalid)?.1.push(token),
+        }
+    }
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, words)| words.join(" "))
+    };
+    let number = |key: &str| -> Result<i64, String> {
+        field(key)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(invalid)
+    };
+
+    let kind = match kind {
+        "check" => OptionKind::Check {
+            default: field("default").ok_or_else(invalid)? == "true",
+        },
+        "spin" => OptionKind::Spin {
+            default: number("default")?,
+            min: number("min")?,
+            max: number("max")?,
+        },
+        "combo" => OptionKind::Combo {
+            default: field("default").unwrap_or_default(),
+            vars: fields
+                .iter()
+                .filter(|(name, _)| *name == "var")
+                .map(|(_, words)| words.join(" "))
+                .collect(),
+        },
+        "button" => OptionKind::Button,
+        "string" => OptionKind::String {
+            default: field("default")
+                .filter(|default| default != "<empty>")
+                .unwrap_or_default(),
+        },
+        _ => return Err(invalid()),
+    };
+
+    Ok(UciOption {
+        name: name.trim().to_string(),
+        kind,
+    })
+}
+
+impl From<&SearchInfo> for Info {
+    fn from(info: &SearchInfo) -> Info {
+        match info {
+            SearchInfo::Iteration {
+                depth,
+                seldepth,
+                multipv,
+                score,
+                nodes,
+                time,
+                hashfull,
+                pv,
+            } => Info {
+                depth: Some(*depth),
+                seldepth: Some(*seldepth),
+                multipv: Some(*multipv),
+                score: Some(*score),
+                nodes: Some(*nodes),
+                nps: Some(nodes * 1000 / (time.as_millis() as u64).max(1)),
+                time: Some(*time),
+                hashfull: Some(*hashfull),
+                pv: pv.clone(),
+                ..Default::default()
+            },
+            SearchInfo::CurrentMove { depth, mov, number } => Info {
+                depth: Some(*depth),
+                currmove: Some(mov.clone()),
+                currmovenumber: Some(*number),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Info {
+    pub fn string(text: &str) -> Info {
+        Info {
+            string: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "info")?;
+        if let Some(depth) = self.depth {
+            write!(f, " depth {depth}")?;
+        }
+        if let Some(seldepth) = self.seldepth {
+            write!(f, " seldepth {seldepth}")?;
+        }
+        if let Some(multipv) = self.multipv {
+            write!(f, " multipv {multipv}")?;
+        }
+        match self.score {
+            Some(Score::Centipawns(cp)) => write!(f, " score cp {cp}")?,
+            Some(Score::Mate(moves)) => write!(f, " score mate {moves}")?,
+            None => {}
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {nodes}")?;
+        }
+        if let Some(nps) = self.nps {
+            write!(f, " nps {nps}")?;
+        }
+        format_millis(f, "time", self.time)?;
+        if let Some(hashfull) = self.hashfull {
+            write!(f, " hashfull {hashfull}")?;
+        }
+        if let Some(currmove) = &self.currmove {
+            write!(f, " currmove {}", move_to_uci(currmove))?;
+        }
+        if let Some(currmovenumber) = self.currmovenumber {
+            write!(f, " currmovenumber {currmovenumber}")?;
+        }
+        if !self.pv.is_empty() {
+            let pv: Vec<String> = self.pv.iter().map(move_to_uci).collect();
+            write!(f, " pv {}", pv.join(" "))?;
+        }
+        // the string runs to the end of the line
+        if let Some(string) = &self.string {
+            write!(f, " string {string}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Arguments of an `info` line. Unknown fields, such as `tbhits` or score bounds, are
+/// skipped.
+fn parse_info(args: &str) -> Result<Info, String> {
+    let mut info = Info::default();
+
+    // the string runs to the end of the line
+    let args = args.trim();
+    let (fields, string) = match args.strip_prefix("string") {
+        Some(string) => ("", Some(string)),
+        None => match args.split_once(" string") {
+            Some((fields, string)) => (fields, Some(string)),
+            None => (args, None),
+        },
+    };
+    info.string = string.map(|string| string.trim().to_string());
+
+    let mut tokens = fields.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = Some(parse_value(token, &mut tokens)?),
+            "seldepth" => info.seldepth = Some(parse_value(token, &mut tokens)?),
+            "multipv" => info.multipv = Some(parse_value(token, &mut tokens)?),
+            "score" => {
+                info.score = match tokens.next() {
+                    Some("cp") => Some(Score::Centipawns(parse_value("cp", &mut tokens)?)),
+                    Some("mate") => Some(Score::Mate(parse_value("mate", &mut tokens)?)),
+                    _ => return Err(format!("invalid score: {fields}")),
+                }
+            }
+            "nodes" => info.nodes = Some(parse_value(token, &mut tokens)?),
+            "nps" => info.nps = Some(parse_value(token, &mut tokens)?),
+            "time" => info.time = Some(Duration::from_millis(parse_value(token, &mut tokens)?)),
+            "hashfull" => info.hashfull = Some(parse_value(token, &mut tokens)?),
+            "currmove" => info.currmove = Some(parse_move(tokens.next().unwrap_or_default())?),
+            "currmovenumber" => info.currmovenumber = Some(parse_value(token, &mut tokens)?),
+            "pv" => {
+                while let Some(mov) = tokens
+                    .peek()
+                    .and_then(|text| Move::from_full_algebraic(text))
+                {
+                    info.pv.push(mov);
+                    tokens.next();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(info)
+}
+
+impl FromStr for UciResponse {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<UciResponse, String> {
+        let line = line.trim();
+        let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+
+        match word {
+            "id" => match args.split_once(char::is_whitespace) {
+                Some(("name", name)) => Ok(UciResponse::IdName(name.trim().to_string())),
+                Some(("author", author)) => Ok(UciResponse::IdAuthor(author.trim().to_string())),
+                _ => Err(format!("invalid id: {args}")),
+            },
+            "uciok" => Ok(UciResponse::UciOk),
+            "readyok" => Ok(UciResponse::ReadyOk),
+            "option" => parse_option(args).map(UciResponse::Option),
+            "bestmove" => {
+                let mut tokens = args.split_whitespace();
+                let mov = match tokens.next() {
+                    Some("0000") | Some("(none)") => None,
+                    Some(text) => Some(parse_move(text)?),
+                    None => return Err("bestmove without a move".to_string()),
+                };
+                let ponder = match (tokens.next(), tokens.next()) {
+                    (Some("ponder"), Some(text)) => Some(parse_move(text)?),
+                    _ => None,
+                };
+                Ok(UciResponse::BestMove { mov, ponder })
+            }
+            "info" => parse_info(args).map(UciResponse::Info),
+            _ => Err(format!("unknown response {line}")),
+        }
+    }
+}
+
+impl fmt::Display for UciResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciResponse::IdName(name) => write!(f, "id name {name}"),
+            UciResponse::IdAuthor(author) => write!(f, "id author {author}"),
+            UciResponse::UciOk => write!(f, "uciok"),
+            UciResponse::ReadyOk => write!(f, "readyok"),
+            UciResponse::Option(option) => write!(f, "{option}"),
+            UciResponse::BestMove { mov, ponder } => {
+                match mov {
+                    Some(mov) => write!(f, "bestmove {}", move_to_uci(mov))?,
+                    None => write!(f, "bestmove 0000")?,
+                }
+                match ponder {
+                    Some(ponder) => write!(f, " ponder {}", move_to_uci(ponder)),
+                    None => Ok(()),
+                }
+            }
+            UciResponse::Info(info) => write!(f, "{info}"),
+        }
+    }
+}
+
+/// Timestamped record of the lines exchanged with the GUI, kept when a log is set
+#[derive(Clone, Default)]
+struct Transcript(Arc<Mutex<Option<Box<dyn Write + Send>>>>);
+
+impl Transcript {
+    fn set(&self, log: Option<Box<dyn Write + Send>>) {
+        *self.0.lock().unwrap() = log;
+    }
+
+    /// Record `line`, received with `>>` or sent with `<<`
+    fn record(&self, direction: &str, line: &str) {
+        if let Some(log) = self.0.lock().unwrap().as_mut() {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            // a failing log must not take the engine down
+            let _ = writeln!(
+                log,
+                "{}.{:03} {direction} {line}",
+                time.as_secs(),
+                time.subsec_millis()
+            )
+            .and_then(|_| log.flush());
+        }
+    }
+}
+
+fn send<W: Write>(
+    output: &Mutex<W>,
+    transcript: &Transcript,
+    response: &UciResponse,
+) -> io::Result<()> {
+    let line = response.to_string();
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{line}")?;
+    output.flush()?;
+    transcript.record("<<", &line);
+    Ok(())
+}
+
+fn send_string<W: Write>(output: &Mutex<W>, transcript: &Transcript, text: &str) -> io::Result<()> {
+    send(output, transcript, &UciResponse::Info(Info::string(text)))
+}
+
+/// Books larger than this are read from the file as positions are looked up
+const BOOK_IN_MEMORY_LIMIT: u64 = 64 << 20;
+
+/// PolyGlot book at `filename`, `None` for an empty name
+fn load_book(filename: &str) -> Result<Option<Book>, String> {
+    if filename.is_empty() {
+        return Ok(None);
+    }
+    if !Path::new(filename).is_file() {
+        return Err(format!("no book file {filename}"));
+    }
+
+    let book = match fs::metadata(filename) {
+        Ok(metadata) if metadata.len() > BOOK_IN_MEMORY_LIMIT => Book::open_on_demand(filename),
+        _ => Book::open(filename),
+    };
+    book.map(Some)
+        .map_err(|err| format!("failed to load book {filename}: {err}"))
+}
+
+/// `go` commands read by `UciEngine::run` and how far they got, numbered from 1 in order. A
+/// `stop` or `ponderhit` applies to every search sent before it, even one not started yet.
+#[derive(Debug, Default)]
+struct SearchRequests {
+    sent: u64,
+    finished: u64,
+    /// Searches up to this number are stopped
+    stopped: u64,
+    /// Ponder searches up to this number had a `ponderhit`
+    ponderhit: u64,
+}
+
+impl SearchRequests {
+    fn is_searching(&self) -> bool {
+        self.sent > self.finished
+    }
+
+    fn stop(&mut self, stop: &AtomicBool) {
+        self.stopped = self.sent;
+        stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Engine side of the protocol, searching with a `Solver`
+pub struct UciEngine {
+    solver: Solver,
+    move_generator: MoveGenerator,
+    board: Board,
+    options: Vec<UciOption>,
+    debug: bool,
+    own_book: bool,
+    book: Option<Book>,
+    book_policy: BookPolicy,
+    /// Weight below which the uniform policy leaves moves out
+    book_min_weight: u16,
+    book_depth: Option<u32>,
+    transcript: Transcript,
+}
+
+impl Default for UciEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UciEngine {
+    pub fn new() -> UciEngine {
+        UciEngine {
+            solver: Solver::new(),
+            move_generator: MoveGenerator::new(),
+            board: Board::from_fen(STARTPOS),
+            options: vec![
+                UciOption::spin("Hash", 16, 1, 4096),
+                UciOption::spin("Threads", 1, 1, 64),
+                UciOption::check("OwnBook", false),
+                UciOption::string("BookFile", ""),
+                UciOption::combo("BookPolicy", "Best", &["Best", "Weighted", "Uniform"]),
+                UciOption::spin("BookMinWeight", 1, 0, u16::MAX as i64),
+                UciOption::spin("BookDepth", 0, 0, 1000),
+                UciOption::spin("MultiPV", 1, 1, 256),
+                UciOption::spin("Move Overhead", 50, 0, 5000),
+                UciOption::string("EvalFile", ""),
+                UciOption::string("TablebasePath", ""),
+                UciOption::string("Debug Log File", ""),
+            ],
+            debug: false,
+            own_book: false,
+            book: None,
+            book_policy: BookPolicy::Best,
+            book_min_weight: 1,
+            book_depth: None,
+            transcript: Transcript::default(),
+        }
+    }
+
+    pub fn get_options(&self) -> &[UciOption] {
+        &self.options
+    }
+
+    /// Record the lines exchanged with the GUI in `log`, with timestamps
+    pub fn set_log(&mut self, log: Option<Box<dyn Write + Send>>) {
+        self.transcript.set(log);
+    }
+
+    /// Append the transcript to the file `filename`, or stop logging for an empty name
+    pub fn set_log_file(&mut self, filename: &str) -> io::Result<()> {
+        if filename.is_empty() {
+            self.set_log(None);
+        } else {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filename)?;
+            self.set_log(Some(Box::new(file)));
+        }
+        Ok(())
+    }
+
+    /// Play the protocol until `quit` or the end of `input`. Commands are executed in order
+    /// on a separate thread, so `stop`, `ponderhit` and `isready` are handled during a search.
+    pub fn run<R: BufRead, W: Write + Send>(&mut self, input: R, output: W) -> io::Result<()> {
+        let output = Mutex::new(output);
+        let stop = self.solver.get_stop_flag();
+        let ponderhit = self.solver.get_ponderhit_flag();
+        let searches = Mutex::new(SearchRequests::default());
+        let transcript = self.transcript.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        let result = thread::scope(|scope| {
+            let engine = &mut *self;
+            let (output, searches) = (&output, &searches);
+            let (executor_stop, executor_ponderhit) = (stop.clone(), ponderhit.clone());
+            let executor = scope.spawn(move || -> io::Result<()> {
+                for command in receiver {
+                    let go = matches!(command, UciCommand::Go(_));
+                    if go {
+                        // the flags belong to this search now, keeping a stop or ponderhit
+                        // that was read for it before it started
+                        let searches = searches.lock().unwrap();
+                        let number = searches.finished + 1;
+                        executor_stop.store(searches.stopped >= number, Ordering::Relaxed);
+                        executor_ponderhit.store(searches.ponderhit >= number, Ordering::Relaxed);
+                    }
+                    let result = engine.execute(command, output);
+                    if go {
+                        searches.lock().unwrap().finished += 1;
+                    }
+                    result?;
+                }
+                Ok(())
+            });
+
+            // an infinite or ponder search only ends with stop, which will not come after the
+            // end of the input
+            let mut open_ended = false;
+            let read = (|| -> io::Result<()> {
+                for line in input.lines() {
+                    let line = line?;
+                    transcript.record(">>", &line);
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    match line.parse() {
+                        Ok(UciCommand::Quit) => {
+                            searches.lock().unwrap().stop(&stop);
+                            break;
+                        }
+                        Ok(UciCommand::Stop) => searches.lock().unwrap().stop(&stop),
+                        Ok(UciCommand::PonderHit) => {
+                            let mut searches = searches.lock().unwrap();
+                            searches.ponderhit = searches.sent;
+                            ponderhit.store(true, Ordering::Relaxed);
+                        }
+                        // answered at once during a search, in order with the other commands
+                        // otherwise
+                        Ok(UciCommand::IsReady) if searches.lock().unwrap().is_searching() => {
+                            send(output, &transcript, &UciResponse::ReadyOk)?
+                        }
+                        Ok(command) => {
+                            if let UciCommand::Go(limits) = &command {
+                                open_ended = limits.infinite || limits.ponder;
+                                searches.lock().unwrap().sent += 1;
+                            }
+                            if sender.send(command).is_err() {
+                                break;
+                            }
+                        }
+                        Err(err) => send_string(output, &transcript, &err)?,
+                    }
+                }
+                Ok(())
+            })();
+
+            if read.is_err() || open_ended {
+                searches.lock().unwrap().stop(&stop);
+            }
+            drop(sender);
+            let executed = executor.join().expect("engine thread panicked");
+            read.and(executed)
+        });
+
+        result
+    }
+
+    /// Execute a command in the order received
+    pub fn execute<W: Write>(&mut self, command: UciCommand, output: &Mutex<W>) -> io::Result<()> {
+        match command {
+            UciCommand::Uci => {
+                send(
+                    output,
+                    &self.transcript,
+                    &UciResponse::IdName("FChess".to_string()),
+                )?;
+                send(
+                    output,
+                    &self.transcript,
+                    &UciResponse::IdAuthor("joajfreitas".to_string()),
+                )?;
+                for option in self.options.iter() {
+                    send(
+                        output,
+                        &self.transcript,
+                        &UciResponse::Option(option.clone()),
+                    )?;
+                }
+                send(output, &self.transcript, &UciResponse::UciOk)
+            }
+            UciCommand::Debug(debug) => {
+                self.debug = debug;
+                Ok(())
+            }
+            UciCommand::IsReady => send(output, &self.transcript, &UciResponse::ReadyOk),
+            UciCommand::SetOption { name, value } => {
+                match self.set_option(&name, value.as_deref()) {
+                    Ok(()) => Ok(()),
+                    Err(err) => send_string(output, &self.transcript, &err),
+                }
+            }
+            UciCommand::UciNewGame => {
+                self.solver.new_game();
+                self.board = Board::from_fen(STARTPOS);
+                Ok(())
+            }
+            UciCommand::Position { fen, moves } => match self.position(fen.as_deref(), &moves) {
+                Ok(board) => {
+                    self.board = board;
+                    if self.debug {
+                        let command = UciCommand::Position { fen, moves };
+                        send_string(output, &self.transcript, &command.to_string())?;
+                    }
+                    Ok(())
+                }
+                Err(err) => send_string(output, &self.transcript, &err),
+            },
+            UciCommand::Go(limits) => self.go(limits, output),
+            UciCommand::Stop | UciCommand::PonderHit | UciCommand::Quit => Ok(()),
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let option = self
+            .options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown option {name}"))?;
+        let value = option.parse_value(value)?;
+
+        match (option.name.clone().as_str(), value) {
+            ("Hash", OptionValue::Spin(megabytes)) => self.solver.set_hash_size(megabytes as usize),
+            ("Threads", OptionValue::Spin(threads)) => self.solver.set_threads(threads as usize),
+            ("OwnBook", OptionValue::Check(own_book)) => self.own_book = own_book,
+            ("BookFile", OptionValue::String(filename)) => self.book = load_book(&filename)?,
+            ("BookPolicy", OptionValue::Combo(policy)) => {
+                self.book_policy = match policy.as_str() {
+                    "Weighted" => BookPolicy::WeightedRandom,
+                    "Uniform" => BookPolicy::Uniform {
+                        min_weight: self.book_min_weight,
+                    },
+                    _ => BookPolicy::Best,
+                }
+            }
+            ("BookMinWeight", OptionValue::Spin(weight)) => {
+                self.book_min_weight = weight as u16;
+                if let BookPolicy::Uniform { min_weight } = &mut self.book_policy {
+                    *min_weight = self.book_min_weight;
+                }
+            }
+            ("BookDepth", OptionValue::Spin(0)) => self.book_depth = None,
+            ("BookDepth", OptionValue::Spin(plies)) => self.book_depth = Some(plies as u32),
+            ("MultiPV", OptionValue::Spin(multipv)) => self.solver.set_multipv(multipv as usize),
+            ("Move Overhead", OptionValue::Spin(millis)) => self
+                .solver
+                .set_move_overhead(Duration::from_millis(millis as u64)),
+            ("EvalFile", OptionValue::String(filename)) if filename.is_empty() => self
+                .solver
+                .set_evaluator(Box::new(ClassicalEvaluator::new())),
+            ("EvalFile", OptionValue::String(filename)) => {
+                let evaluator = NnueEvaluator::from_filename(&filename)
+                    .map_err(|err| format!("failed to load {filename}: {err}"))?;
+                self.solver.set_evaluator(Box::new(evaluator));
+            }
+            ("TablebasePath", OptionValue::String(directory)) if directory.is_empty() => {
+                self.solver.set_tablebases(None)
+            }
+            ("Debug Log File", OptionValue::String(filename)) => self
+                .set_log_file(&filename)
+                .map_err(|err| format!("failed to open {filename}: {err}"))?,
+            ("TablebasePath", OptionValue::String(directory)) => {
+                let tablebases = Tablebases::from_directory(&directory)
+                    .map_err(|err| format!("failed to load {directory}: {err}"))?;
+                self.solver.set_tablebases(Some(tablebases));
+            }
+            _ => {}
+        }
+
+        if let Some(book) = &mut self.book {
+            book.set_policy(self.book_policy);
+            book.set_max_plies(self.book_depth);
+        }
+        Ok(())
+    }
+
+    /// Board set up by a `position` command. The whole command is rejected if the position
+    /// or any of the moves is illegal.
+    fn position(&self, fen: Option<&str>, moves: &[String]) -> Result<Board, String> {
+        let mut board = match fen {
+            None => Board::from_fen(STARTPOS),
+            Some(fen) => Board::try_from_fen(fen).ok_or_else(|| format!("invalid fen {fen}"))?,
+        };
+        if self.move_generator.in_check(&board, !board.get_turn()) {
+            return Err("illegal position: the side not to move is in check".to_string());
+        }
+
+        for text in moves {
+            let mov = find_move(&self.move_generator.legal_moves(&board), text)
+                .ok_or_else(|| format!("illegal move {text}"))?;
+            board = board
+                .apply(mov)
+                .ok_or_else(|| format!("illegal move {text}"))?;
+        }
+
+        Ok(board)
+    }
+
+    fn go<W: Write>(&mut self, mut limits: SearchLimits, output: &Mutex<W>) -> io::Result<()> {
+        // searchmoves are parsed without the position, which gives the promotion colour
+        let legal_moves = self.move_generator.legal_moves(&self.board);
+        limits.searchmoves = limits
+            .searchmoves
+            .iter()
+            .filter_map(|mov| find_move(&legal_moves, &mov.to_algebraic()))
+            .collect();
+        if self.debug {
+            send_string(
+                output,
+                &self.transcript,
+                &format!("search limits {limits:?}"),
+            )?;
+        }
+
+        let book_move = self
+            .book
+            .as_ref()
+            .filter(|_| self.own_book)
+            .and_then(|book| book.get_move(&self.board))
+            .and_then(|mov| find_move(&legal_moves, &mov.to_algebraic()))
+            .filter(|mov| limits.searchmoves.is_empty() || limits.searchmoves.contains(mov));
+        let best = match book_move {
+            Some(mov) => {
+                if self.debug {
+                    send_string(output, &self.transcript, "book move")?;
+                }
+                Some(mov)
+            }
+            None => {
+                let mut sent = Ok(());
+                let transcript = &self.transcript;
+                let best = self
+                    .solver
+                    .search_with_progress(&self.board, &limits, |info| {
+                        if sent.is_ok() {
+                            sent = send(output, transcript, &UciResponse::Info(Info::from(info)));
+                        }
+                    });
+                sent?;
+                if self.debug {
+                    send_string(
+                        output,
+                        &self.transcript,
+                        &format!("searched {} nodes", self.solver.get_nodes()),
+                    )?;
+                }
+                best
+            }
+        };
+
+        // bestmove is only sent once an infinite search is stopped, or a ponder search is
+        // stopped or turned into a normal search
+        let stop = self.solver.get_stop_flag();
+        let ponderhit = self.solver.get_ponderhit_flag();
+        if limits.infinite || limits.ponder {
+            while !stop.load(Ordering::Relaxed)
+                && (limits.infinite || !ponderhit.load(Ordering::Relaxed))
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        let ponder = best
+            .as_ref()
+            .and_then(|best| self.solver.get_ponder_move(&self.board, best));
+        send(
+            output,
+            &self.transcript,
+            &UciResponse::BestMove { mov: best, ponder },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn session(input: &str) -> Vec<String> {
+        engine_session(&mut UciEngine::new(), input)
+    }
+
+    fn engine_session(engine: &mut UciEngine, input: &str) -> Vec<String> {
+        let mut output = vec![];
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[rstest]
+    #[case("uci", UciCommand::Uci)]
+    #[case("debug on", UciCommand::Debug(true))]
+    #[case("joho debug off", UciCommand::Debug(false))]
+    #[case("setoption name Move Overhead value 100", UciCommand::SetOption { name: "Move Overhead".to_string(), value: Some("100".to_string()) })]
+    #[case("setoption name Clear Hash", UciCommand::SetOption { name: "Clear Hash".to_string(), value: None })]
+    #[case("position startpos moves e2e4 e7e5", UciCommand::Position { fen: None, moves: vec!["e2e4".to_string(), "e7e5".to_string()] })]
+    #[case("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1", UciCommand::Position { fen: Some("4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string()), moves: vec![] })]
+    #[case("go wtime -5 btime 1000 movestogo 10", UciCommand::Go(SearchLimits { wtime: Some(Duration::ZERO), btime: Some(Duration::from_secs(1)), movestogo: Some(10), ..Default::default() }))]
+    #[case("go infinite searchmoves e2e4 d2d4", UciCommand::Go(SearchLimits { infinite: true, searchmoves: vec![Move::from_full_algebraic("e2e4").unwrap(), Move::from_full_algebraic("d2d4").unwrap()], ..Default::default() }))]
+    #[case("ponderhit", UciCommand::PonderHit)]
+    fn test_parse_command(#[case] line: &str, #[case] expected: UciCommand) {
+        assert_eq!(line.parse::<UciCommand>(), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("hello")]
+    #[case("position")]
+    #[case("go depth x")]
+    #[case("go sideways")]
+    #[case("setoption Hash")]
+    fn test_parse_invalid_command(#[case] line: &str) {
+        assert!(line.parse::<UciCommand>().is_err());
+    }
+
+    #[rstest]
+    #[case("setoption name Move Overhead value 100")]
+    #[case("position startpos moves e2e4 e7e5")]
+    #[case("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1")]
+    #[case("go searchmoves e2e4 ponder wtime 1000 btime 900 winc 10 binc 10 movestogo 5")]
+    #[case("go depth 4 nodes 1000 mate 2 movetime 500 infinite")]
+    fn test_command_round_trip(#[case] line: &str) {
+        assert_eq!(line.parse::<UciCommand>().unwrap().to_string(), line);
+    }
+
+    #[rstest]
+    #[case("id name FChess")]
+    #[case("id author joajfreitas")]
+    #[case("uciok")]
+    #[case("option name Move Overhead type spin default 50 min 0 max 5000")]
+    #[case("option name BookFile type string default <empty>")]
+    #[case("option name Ponder type check default false")]
+    #[case("option name Style type combo default Normal var Solid var Normal")]
+    #[case("option name Clear Hash type button")]
+    #[case("bestmove e7e8q ponder e2e4")]
+    #[case("bestmove 0000")]
+    #[case("info depth 3 seldepth 4 multipv 1 score cp -12 nodes 100 nps 1000 time 100 hashfull 5 pv e2e4 e7e5")]
+    #[case("info depth 5 currmove e2e4 currmovenumber 1")]
+    #[case("info score mate -2 string mated soon")]
+    fn test_response_round_trip(#[case] line: &str) {
+        assert_eq!(line.parse::<UciResponse>().unwrap().to_string(), line);
+    }
+
+    #[test]
+    fn test_info_without_pv() {
+        let info = Info::from(&SearchInfo::Iteration {
+            depth: 1,
+            seldepth: 1,
+            multipv: 1,
+            score: Score::Mate(-1),
+            nodes: 20,
+            time: Duration::from_millis(10),
+            hashfull: 0,
+            pv: vec![],
+        });
+        assert_eq!(
+            UciResponse::Info(info).to_string(),
+            "info depth 1 seldepth 1 multipv 1 score mate -1 nodes 20 nps 2000 time 10 hashfull 0"
+        );
+    }
+
+    #[test]
+    fn test_info_skips_unknown_fields() {
+        let response = "info depth 2 score cp 10 lowerbound tbhits 0 pv e2e4"
+            .parse::<UciResponse>()
+            .unwrap();
+        let UciResponse::Info(info) = response else {
+            panic!("not an info line");
+        };
+        assert_eq!(info.depth, Some(2));
+        assert_eq!(info.score, Some(Score::Centipawns(10)));
+        assert_eq!(info.pv, vec![Move::from_full_algebraic("e2e4").unwrap()]);
+    }
+
+    #[rstest]
+    #[case(
+        UciOption::spin("Hash", 16, 1, 64),
+        Some("32"),
+        Ok(OptionValue::Spin(32))
+    )]
+    #[case(UciOption::spin("Hash", 16, 1, 64), Some("65"), Err("invalid value 65 for option Hash".to_string()))]
+    #[case(
+        UciOption::check("OwnBook", false),
+        Some("true"),
+        Ok(OptionValue::Check(true))
+    )]
+    #[case(UciOption::check("OwnBook", false), None, Err("missing value for option OwnBook".to_string()))]
+    #[case(
+        UciOption::string("BookFile", ""),
+        Some("<empty>"),
+        Ok(OptionValue::String(String::new()))
+    )]
+    #[case(UciOption::string("BookFile", ""), Some("my book.bin"), Ok(OptionValue::String("my book.bin".to_string())))]
+    fn test_option_values(
+        #[case] option: UciOption,
+        #[case] value: Option<&str>,
+        #[case] expected: Result<OptionValue, String>,
+    ) {
+        assert_eq!(option.parse_value(value), expected);
+    }
+
+    #[test]
+    fn test_session_handshake() {
+        let output = session("uci\nisready\n");
+        assert_eq!(output[0], "id name FChess");
+        assert_eq!(output[output.len() - 2], "uciok");
+        assert_eq!(output[output.len() - 1], "readyok");
+        assert!(
+            output.contains(&"option name Hash type spin default 16 min 1 max 4096".to_string())
+        );
+    }
+
+    #[test]
+    fn test_session_position_with_moves() {
+        let mut engine = UciEngine::new();
+        let output = engine_session(
+            &mut engine,
+            "position fen 4k3/P7/8/8/8/8/8/4K2R w K - 0 1 moves e1g1 e8d7 a7a8q d7c7\n",
+        );
+        assert!(output.is_empty());
+        assert_eq!(
+            engine.board,
+            Board::from_fen("Q7/2k5/8/8/8/8/8/5RK1 w - - 1 3")
+        );
+    }
+
+    #[test]
+    fn test_session_promotion() {
+        let output =
+            session("position fen 8/P6k/8/8/8/8/8/K7 w - - 0 1\ngo depth 1 searchmoves a7a8q\n");
+        assert_eq!(output.last().unwrap(), "bestmove a7a8q");
+    }
+
+    #[test]
+    fn test_session_reports_errors() {
+        let output = session("position startpos moves e2e5\nsetoption name Hash value 0\n");
+        assert_eq!(
+            output,
+            vec![
+                "info string illegal move e2e5",
+                "info string invalid value 0 for option Hash"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_session_transcript() {
+        let filename = std::env::temp_dir().join(format!("fchess-uci-{}.log", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        std::fs::remove_file(filename).ok();
+
+        let mut engine = UciEngine::new();
+        engine.set_log_file(filename).unwrap();
+        let output = engine_session(
+            &mut engine,
+            "isready\nsetoption name debug log file value <empty>\n",
+        );
+        assert_eq!(output, vec!["readyok"]);
+        let output = session(&format!(
+            "setoption name Debug Log File value {filename}\nisready\n"
+        ));
+        assert_eq!(output, vec!["readyok"]);
+
+        let transcript = std::fs::read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        let lines: Vec<(&str, &str)> = transcript
+            .lines()
+            .map(|line| line.split_once(' ').unwrap())
+            .collect();
+        assert!(lines.iter().all(|(time, _)| time.parse::<f64>().is_ok()));
+        let messages: Vec<&str> = lines.iter().map(|(_, message)| *message).collect();
+        // input is recorded as soon as it is read, ahead of the commands being executed
+        assert_eq!(messages[0], ">> isready");
+        assert!(messages[..3].contains(&"<< readyok"));
+        assert!(messages[..3].contains(&">> setoption name debug log file value <empty>"));
+        // the second session may read its input before the log is opened
+        assert_eq!(messages.last(), Some(&"<< readyok"));
+    }
+
+    #[test]
+    fn test_session_log_file_error() {
+        let output = session("setoption name Debug Log File value /nonexistent/fchess.log\n");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("info string failed to open /nonexistent/fchess.log"));
+    }
+
+    #[test]
+    fn test_session_book_options() {
+        let filename = std::env::temp_dir().join(format!("fchess-uci-{}.bin", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let mut bytes = vec![];
+        for (mov, weight) in [(0x031c_u16, 100_u16), (0x02db, 1)] {
+            bytes.extend(0x463b96181691fc9c_u64.to_be_bytes());
+            bytes.extend(mov.to_be_bytes());
+            bytes.extend(weight.to_be_bytes());
+            bytes.extend(0_u32.to_be_bytes());
+        }
+        std::fs::write(filename, bytes).unwrap();
+
+        let output = session(&format!(
+            "debug on\nsetoption name OwnBook value true\nsetoption name BookFile value {filename}\n\
+             setoption name BookPolicy value Uniform\nsetoption name BookMinWeight value 50\n\
+             position startpos\ngo depth 1\nsetoption name BookDepth value 4\n\
+             position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3\ngo depth 1\n"
+        ));
+        std::fs::remove_file(filename).unwrap();
+
+        let book_moves = output
+            .iter()
+            .filter(|line| *line == "info string book move")
+            .count();
+        assert_eq!(book_moves, 1);
+        assert!(output.contains(&"bestmove e2e4".to_string()));
+    }
+
+    #[test]
+    fn test_session_stop_ends_infinite_search() {
+        let output = session("position startpos\ngo infinite\nstop\n");
+        assert!(output.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[rstest]
+    #[case("position startpos\ngo infinite\nstop\nposition startpos moves e2e4\ngo depth 2\n")]
+    #[case("position startpos\ngo ponder depth 2\nponderhit\nposition startpos moves e2e4\ngo depth 2\n")]
+    #[case("go infinite\nstop\ngo infinite\nstop\ngo depth 1\n")]
+    fn test_session_back_to_back_searches(#[case] input: &str) {
+        let output = session(input);
+        let searches = input.matches("go ").count();
+        let best_moves = output
+            .iter()
+            .filter(|line| line.starts_with("bestmove "))
+            .count();
+        assert_eq!(best_moves, searches);
+    }
+}
@@ -0,0 +1,663 @@
+//! Chess Engine Communication Protocol, as spoken by XBoard and WinBoard, on top of the same
+//! `Solver` as the UCI engine.
+
+use std::io;
+use std::io::prelude::*;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::board::STARTPOS;
+use crate::move_generator::MoveGenerator;
+use crate::moves::Move;
+use crate::side::Side;
+use crate::solver::{Score, SearchInfo, SearchLimits, Solver};
+
+/// Features announced in reply to `protover 2`
+const FEATURES: &str = "feature myname=\"FChess\" ping=1 setboard=1 usermove=1 time=1 san=0 \
+                        playother=0 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 name=0 \
+                        done=1";
+/// Offset of mate scores in thinking output, followed by the number of moves to mate
+const MATE_OUTPUT: i32 = 100_000;
+
+/// Message sent by the GUI to the engine
+#[derive(Clone, Debug, PartialEq)]
+pub enum XboardCommand {
+    Xboard,
+    Protover(u32),
+    New,
+    Force,
+    Go,
+    UserMove(String),
+    Undo,
+    Remove,
+    SetBoard(String),
+    /// Moves per time control, or 0 for the whole game, base time and increment
+    Level {
+        moves: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    /// Exact time per move
+    St(Duration),
+    /// Depth limit
+    Sd(u8),
+    /// Time left on the engine's clock
+    Time(Duration),
+    /// Time left on the opponent's clock
+    Otim(Duration),
+    Post,
+    NoPost,
+    Result(String),
+    Ping(u32),
+    /// Move now with the best move found so far
+    MoveNow,
+    Quit,
+    /// Commands that need no action, such as `accepted` or `random`
+    Ignored,
+}
+
+/// Clock of `time`/`otim`, given in centiseconds
+fn parse_centiseconds(args: &str) -> Result<Duration, String> {
+    let centiseconds: i64 = args.parse().map_err(|_| format!("invalid time {args}"))?;
+    Ok(Duration::from_millis(centiseconds.max(0) as u64 * 10))
+}
+
+fn parse_number<T: FromStr>(args: &str) -> Result<T, String> {
+    args.parse().map_err(|_| format!("invalid argument {args}"))
+}
+
+/// Arguments of `level <moves> <base> <increment>`, the base being in minutes or
+/// `minutes:seconds` and the increment in seconds
+fn parse_level(args: &str) -> Result<XboardCommand, String> {
+    let invalid = || format!("invalid level {args}");
+    let fields: Vec<&str> = args.split_whitespace().collect();
+    let [moves, base, increment] = fields[..] else {
+        return Err(invalid());
+    };
+
+    let base = match base.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+            let seconds: u64 = seconds.parse().map_err(|_| invalid())?;
+            Duration::from_secs(60 * minutes + seconds)
+        }
+        None => Duration::from_secs(60 * base.parse::<u64>().map_err(|_| invalid())?),
+    };
+    let increment: f64 = increment.parse().map_err(|_| invalid())?;
+
+    Ok(XboardCommand::Level {
+        moves: moves.parse().map_err(|_| invalid())?,
+        base,
+        increment: Duration::from_secs_f64(increment.max(0.0)),
+    })
+}
+
+impl FromStr for XboardCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<XboardCommand, String> {
+        let line = line.trim();
+        let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        match word {
+            "xboard" => Ok(XboardCommand::Xboard),
+            "protover" => Ok(XboardCommand::Protover(parse_number(args)?)),
+            "new" => Ok(XboardCommand::New),
+            "force" => Ok(XboardCommand::Force),
+            "go" => Ok(XboardCommand::Go),
+            "usermove" => Ok(XboardCommand::UserMove(args.to_string())),
+            "undo" => Ok(XboardCommand::Undo),
+            "remove" => Ok(XboardCommand::Remove),
+            "setboard" => Ok(XboardCommand::SetBoard(args.to_string())),
+            "level" => parse_level(args),
+            "st" => Ok(XboardCommand::St(Duration::from_secs(parse_number(args)?))),
+            "sd" => Ok(XboardCommand::Sd(parse_number(args)?)),
+            "time" => Ok(XboardCommand::Time(parse_centiseconds(args)?)),
+            "otim" => Ok(XboardCommand::Otim(parse_centiseconds(args)?)),
+            "post" => Ok(XboardCommand::Post),
+            "nopost" => Ok(XboardCommand::NoPost),
+            "result" => Ok(XboardCommand::Result(args.to_string())),
+            "ping" => Ok(XboardCommand::Ping(parse_number(args)?)),
+            "?" => Ok(XboardCommand::MoveNow),
+            "quit" => Ok(XboardCommand::Quit),
+            "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name"
+            | "rating" | "ics" | "white" | "black" | "draw" | "variant" => {
+                Ok(XboardCommand::Ignored)
+            }
+            // moves without the usermove prefix
+            _ if args.is_empty() && Move::from_full_algebraic(word).is_some() => {
+                Ok(XboardCommand::UserMove(word.to_string()))
+            }
+            _ => Err(format!("Error (unknown command): {line}")),
+        }
+    }
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) -> io::Result<()> {
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{line}")?;
+    output.flush()
+}
+
+/// Thinking output line: depth, score in centipawns, time in centiseconds, nodes and the
+/// principal variation
+fn thinking(info: &SearchInfo) -> Option<String> {
+    let SearchInfo::Iteration {
+        depth,
+        multipv: 1,
+        score,
+        nodes,
+        time,
+        pv,
+        ..
+    } = info
+    else {
+        return None;
+    };
+
+    let score = match *score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => MATE_OUTPUT + moves,
+        Score::Mate(moves) => -MATE_OUTPUT + moves,
+    };
+    let pv: Vec<String> = pv
+        .iter()
+        .map(|mov| mov.to_algebraic().to_lowercase())
+        .collect();
+    Some(format!(
+        "{depth} {score} {} {nodes} {}",
+        time.as_millis() / 10,
+        pv.join(" ")
+    ))
+}
+
+/// Commands read by `XboardEngine::run` and how far their execution got, numbered from 1 in
+/// order. `?` stops the searches of every command sent before it, even one not started yet,
+/// and `force`, `new`, `result` and `quit` also discard the moves they find.
+#[derive(Debug, Default)]
+struct Commands {
+    sent: u64,
+    executing: u64,
+    /// Searches of commands up to this number are stopped
+    stopped: u64,
+    /// Moves found by the searches of commands up to this number are not played
+    discarded: u64,
+}
+
+impl Commands {
+    fn stop(&mut self, stop: &AtomicBool) {
+        self.stopped = self.sent;
+        stop.store(true, Ordering::Relaxed);
+    }
+
+    fn discard(&mut self, stop: &AtomicBool, discard: &AtomicBool) {
+        self.stop(stop);
+        self.discarded = self.sent;
+        discard.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Engine side of the protocol, searching with a `Solver`
+pub struct XboardEngine {
+    solver: Solver,
+    move_generator: MoveGenerator,
+    board: Board,
+    /// Positions before each move, for `undo` and `remove`
+    history: Vec<Board>,
+    /// Side played by the engine, `None` in force mode
+    engine_side: Option<Side>,
+    post: bool,
+    moves_per_control: u32,
+    base: Option<Duration>,
+    increment: Duration,
+    move_time: Option<Duration>,
+    depth: Option<u8>,
+    time: Option<Duration>,
+    opponent_time: Option<Duration>,
+}
+
+impl Default for XboardEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XboardEngine {
+    pub fn new() -> XboardEngine {
+        XboardEngine {
+            solver: Solver::new(),
+            move_generator: MoveGenerator::new(),
+            board: Board::from_fen(STARTPOS),
+            history: vec![],
+            engine_side: Some(Side::Black),
+            post: false,
+            moves_per_control: 0,
+            base: None,
+            increment: Duration::ZERO,
+            move_time: None,
+            depth: None,
+            time: None,
+            opponent_time: None,
+        }
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Play the protocol until `quit` or the end of `input`. Commands are executed in order
+    /// on a separate thread, so `?`, `result` and `force` can interrupt the search.
+    pub fn run<R: BufRead, W: Write + Send>(&mut self, input: R, output: W) -> io::Result<()> {
+        let output = Mutex::new(output);
+        let stop = self.solver.get_stop_flag();
+        // the move of an interrupted search is not played after `force`, `new` or `result`
+        let discard = AtomicBool::new(false);
+        let commands = Mutex::new(Commands::default());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            let engine = &mut *self;
+            let (output, discard, commands) = (&output, &discard, &commands);
+            let executor_stop = stop.clone();
+            let executor = scope.spawn(move || -> io::Result<()> {
+                for command in receiver {
+                    {
+                        // the flags belong to this command now, keeping a `force` or `new`
+                        // that was read after it was sent
+                        let mut commands = commands.lock().unwrap();
+                        commands.executing += 1;
+                        let number = commands.executing;
+                        executor_stop.store(commands.stopped >= number, Ordering::Relaxed);
+                        discard.store(commands.discarded >= number, Ordering::Relaxed);
+                    }
+                    engine.execute(command, output, discard)?;
+                }
+                Ok(())
+            });
+
+            let read = (|| -> io::Result<()> {
+                for line in input.lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    match line.parse() {
+                        Ok(XboardCommand::Quit) => {
+                            commands.lock().unwrap().discard(&stop, discard);
+                            break;
+                        }
+                        Ok(XboardCommand::MoveNow) => commands.lock().unwrap().stop(&stop),
+                        Ok(command) => {
+                            let mut commands = commands.lock().unwrap();
+                            if matches!(
+                                command,
+                                XboardCommand::Force
+                                    | XboardCommand::New
+                                    | XboardCommand::Result(_)
+                            ) {
+                                commands.discard(&stop, discard);
+                            }
+                            if sender.send(command).is_err() {
+                                break;
+                            }
+                            commands.sent += 1;
+                        }
+                        Err(err) => send(output, &err)?,
+                    }
+                }
+                Ok(())
+            })();
+
+            if read.is_err() {
+                commands.lock().unwrap().stop(&stop);
+            }
+            drop(sender);
+            let executed = executor.join().expect("engine thread panicked");
+            read.and(executed)
+        })
+    }
+
+    /// Execute a command in the order received
+    fn execute<W: Write>(
+        &mut self,
+        command: XboardCommand,
+        output: &Mutex<W>,
+        discard: &AtomicBool,
+    ) -> io::Result<()> {
+        match command {
+            XboardCommand::Xboard | XboardCommand::Ignored | XboardCommand::Quit => {}
+            XboardCommand::MoveNow => {}
+            XboardCommand::Protover(version) if version >= 2 => send(output, FEATURES)?,
+            XboardCommand::Protover(_) => {}
+            XboardCommand::New => {
+                self.board = Board::from_fen(STARTPOS);
+                self.history.clear();
+                self.engine_side = Some(Side::Black);
+                self.depth = None;
+                self.solver.new_game();
+            }
+            XboardCommand::Force => self.engine_side = None,
+            XboardCommand::Go => {
+                self.engine_side = Some(self.board.get_turn());
+                self.think(output, discard)?;
+            }
+            XboardCommand::UserMove(text) => {
+                let Some(mov) = self
+                    .move_generator
+                    .legal_moves(&self.board)
+                    .into_iter()
+                    .find(|mov| mov.to_algebraic().eq_ignore_ascii_case(&text))
+                else {
+                    return send(output, &format!("Illegal move: {text}"));
+                };
+                self.play(mov);
+                if let Some(result) = self.game_result() {
+                    return send(output, &result);
+                }
+                if self.engine_side == Some(self.board.get_turn()) {
+                    self.think(output, discard)?;
+                }
+            }
+            XboardCommand::Undo => self.take_back(1),
+            XboardCommand::Remove => self.take_back(2),
+            XboardCommand::SetBoard(fen) => match Board::try_from_fen(&fen)
+                .filter(|board| !self.move_generator.in_check(board, !board.get_turn()))
+            {
+                Some(board) => {
+                    self.board = board;
+                    self.history.clear();
+                }
+                None => send(output, "tellusererror Illegal position")?,
+            },
+            XboardCommand::Level {
+                moves,
+                base,
+                increment,
+            } => {
+                self.moves_per_control = moves;
+                self.base = Some(base);
+                self.increment = increment;
+                self.move_time = None;
+            }
+            XboardCommand::St(time) => self.move_time = Some(time),
+            XboardCommand::Sd(depth) => self.depth = Some(depth),
+            XboardCommand::Time(time) => self.time = Some(time),
+            XboardCommand::Otim(time) => self.opponent_time = Some(time),
+            XboardCommand::Post => self.post = true,
+            XboardCommand::NoPost => self.post = false,
+            XboardCommand::Result(_) => self.engine_side = None,
+            XboardCommand::Ping(number) => send(output, &format!("pong {number}"))?,
+        }
+
+        Ok(())
+    }
+
+    fn play(&mut self, mov: Move) {
+        let board = self.board.apply(mov).unwrap();
+        self.history.push(std::mem::replace(&mut self.board, board));
+    }
+
+    fn take_back(&mut self, plies: usize) {
+        for _ in 0..plies {
+            if let Some(board) = self.history.pop() {
+                self.board = board;
+            }
+        }
+    }
+
+    /// Result line when the game is over
+    fn game_result(&self) -> Option<String> {
+        if self.board.get_half_move_clock() >= 100 {
+            return Some("1/2-1/2 {Fifty move rule}".to_string());
+        }
+        if !self.move_generator.legal_moves(&self.board).is_empty() {
+            return None;
+        }
+
+        let turn = self.board.get_turn();
+        Some(
+            match (self.move_generator.in_check(&self.board, turn), turn) {
+                (true, Side::White) => "0-1 {Black mates}",
+                (true, Side::Black) => "1-0 {White mates}",
+                (false, _) => "1/2-1/2 {Stalemate}",
+            }
+            .to_string(),
+        )
+    }
+
+    /// Search limits of the current time control for the side to move
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits {
+            depth: self.depth,
+            movetime: self.move_time,
+            ..Default::default()
+        };
+        if self.move_time.is_some() {
+            return limits;
+        }
+
+        let Some(time) = self.time.or(self.base) else {
+            return limits;
+        };
+        let opponent_time = self.opponent_time.or(self.base);
+        let increment = Some(self.increment);
+        match self.board.get_turn() {
+            Side::White => {
+                (limits.wtime, limits.btime) = (Some(time), opponent_time);
+                (limits.winc, limits.binc) = (increment, increment);
+            }
+            Side::Black => {
+                (limits.btime, limits.wtime) = (Some(time), opponent_time);
+                (limits.binc, limits.winc) = (increment, increment);
+            }
+        }
+        if self.moves_per_control > 0 {
+            let played = (self.board.get_full_move_clock().max(1) - 1) as u32;
+            limits.movestogo = Some(self.moves_per_control - played % self.moves_per_control);
+        }
+
+        limits
+    }
+
+    /// Search the current position and play the best move
+    fn think<W: Write>(&mut self, output: &Mutex<W>, discard: &AtomicBool) -> io::Result<()> {
+        if let Some(result) = self.game_result() {
+            return send(output, &result);
+        }
+
+        let limits = self.limits();
+        let post = self.post;
+        let mut sent = Ok(());
+        let best = self
+            .solver
+            .search_with_progress(&self.board, &limits, |info| {
+                if let Some(line) = thinking(info).filter(|_| post && sent.is_ok()) {
+                    sent = send(output, &line);
+                }
+            });
+        sent?;
+
+        if discard.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let Some(best) = best else {
+            return Ok(());
+        };
+        send(
+            output,
+            &format!("move {}", best.to_algebraic().to_lowercase()),
+        )?;
+        self.play(best);
+        if let Some(result) = self.game_result() {
+            send(output, &result)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn session(engine: &mut XboardEngine, input: &str) -> Vec<String> {
+        let mut output = vec![];
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[rstest]
+    #[case("protover 2", XboardCommand::Protover(2))]
+    #[case("usermove e7e8q", XboardCommand::UserMove("e7e8q".to_string()))]
+    #[case("e2e4", XboardCommand::UserMove("e2e4".to_string()))]
+    #[case("level 40 5 0", XboardCommand::Level { moves: 40, base: Duration::from_secs(300), increment: Duration::ZERO })]
+    #[case("level 0 2:30 1.5", XboardCommand::Level { moves: 0, base: Duration::from_secs(150), increment: Duration::from_millis(1500) })]
+    #[case("st 5", XboardCommand::St(Duration::from_secs(5)))]
+    #[case("sd 3", XboardCommand::Sd(3))]
+    #[case("time 1234", XboardCommand::Time(Duration::from_millis(12340)))]
+    #[case("otim -20", XboardCommand::Otim(Duration::ZERO))]
+    #[case("result 1-0 {White mates}", XboardCommand::Result("1-0 {White mates}".to_string()))]
+    #[case("?", XboardCommand::MoveNow)]
+    #[case("accepted usermove", XboardCommand::Ignored)]
+    fn test_parse_command(#[case] line: &str, #[case] expected: XboardCommand) {
+        assert_eq!(line.parse::<XboardCommand>(), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("level 40 5")]
+    #[case("sd deep")]
+    #[case("fly")]
+    fn test_parse_invalid_command(#[case] line: &str) {
+        assert!(line.parse::<XboardCommand>().is_err());
+    }
+
+    #[test]
+    fn test_protover_and_ping() {
+        let output = session(&mut XboardEngine::new(), "xboard\nprotover 2\nping 7\n");
+        assert_eq!(output, vec![FEATURES, "pong 7"]);
+    }
+
+    #[test]
+    fn test_engine_replies_to_user_move() {
+        let mut engine = XboardEngine::new();
+        let output = session(&mut engine, "new\nsd 2\npost\nusermove e2e4\n");
+
+        assert!(output.last().unwrap().starts_with("move "));
+        let thinking: Vec<&str> = output[0].split_whitespace().collect();
+        assert_eq!(thinking[0], "1");
+        assert!(thinking[1].parse::<i32>().is_ok());
+        assert_eq!(engine.history.len(), 2);
+        assert_eq!(engine.get_board().get_turn(), Side::White);
+    }
+
+    #[test]
+    fn test_force_undo_and_remove() {
+        let mut engine = XboardEngine::new();
+        let output = session(
+            &mut engine,
+            "new\nforce\nusermove e2e4\nusermove e7e5\nusermove g1f3\nundo\nremove\n",
+        );
+        assert!(output.is_empty());
+        assert_eq!(engine.history.len(), 0);
+        assert_eq!(engine.get_board(), &Board::from_fen(STARTPOS));
+    }
+
+    #[test]
+    fn test_illegal_move_and_position() {
+        let output = session(
+            &mut XboardEngine::new(),
+            "force\nusermove e2e5\nsetboard 8/8/8/8/8/8/8/8 w - - 0 1\n",
+        );
+        assert_eq!(
+            output,
+            vec!["Illegal move: e2e5", "tellusererror Illegal position"]
+        );
+    }
+
+    #[rstest]
+    #[case(Score::Mate(1), "3 100001 1 500 d8h4")]
+    #[case(Score::Mate(-2), "3 -100002 1 500 d8h4")]
+    #[case(Score::Centipawns(-35), "3 -35 1 500 d8h4")]
+    fn test_thinking_output(#[case] score: Score, #[case] expected: &str) {
+        let info = SearchInfo::Iteration {
+            depth: 3,
+            seldepth: 5,
+            multipv: 1,
+            score,
+            nodes: 500,
+            time: Duration::from_millis(15),
+            hashfull: 0,
+            pv: vec![Move::from_full_algebraic("d8h4").unwrap()],
+        };
+        assert_eq!(thinking(&info).as_deref(), Some(expected));
+    }
+
+    #[rstest]
+    #[case(
+        "new\nforce\nusermove f2f3\nusermove e7e5\nusermove g2g4\nusermove d8h4\n",
+        &["0-1 {Black mates}"]
+    )]
+    #[case(
+        "force\nsetboard rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2\nsd 3\ngo\n",
+        &["move d8h4", "0-1 {Black mates}"]
+    )]
+    #[case(
+        "force\nsetboard k7/8/1Q6/8/8/8/8/6K1 b - - 0 1\ngo\n",
+        &["1/2-1/2 {Stalemate}"]
+    )]
+    fn test_game_result(#[case] input: &str, #[case] expected: &[&str]) {
+        assert_eq!(session(&mut XboardEngine::new(), input), expected);
+    }
+
+    #[test]
+    fn test_force_discards_running_search() {
+        let mut engine = XboardEngine::new();
+        let output = session(
+            &mut engine,
+            "xboard\nnew\nsd 6\ngo\nforce\nusermove e2e4\nping 1\n",
+        );
+
+        assert_eq!(output, vec!["pong 1"]);
+        assert_eq!(engine.history.len(), 1);
+        assert_eq!(engine.get_board().get_turn(), Side::Black);
+    }
+
+    #[test]
+    fn test_move_now_stops_search_sent_before() {
+        let mut engine = XboardEngine::new();
+        let output = session(&mut engine, "new\nst 60\ngo\n?\nsd 2\nusermove g8f6\n");
+
+        let moves: Vec<&String> = output
+            .iter()
+            .filter(|line| line.starts_with("move "))
+            .collect();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(engine.history.len(), 3);
+    }
+
+    #[test]
+    fn test_level_limits() {
+        let mut engine = XboardEngine::new();
+        session(
+            &mut engine,
+            "level 40 5 2\ntime 6000\notim 5000\nforce\nusermove e2e4\n",
+        );
+        let limits = engine.limits();
+
+        assert_eq!(limits.btime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.wtime, Some(Duration::from_secs(50)));
+        assert_eq!(limits.binc, Some(Duration::from_secs(2)));
+        assert_eq!(limits.movestogo, Some(40));
+    }
+}
@@ -0,0 +1,162 @@
+use std::io;
+
+use rstest::rstest;
+
+use fchess::engine::Engine;
+use fchess::uci::{UciCommand, UciResponse};
+use fchess::{Board, Move, MoveGenerator, Score, SearchLimits};
+
+fn spawn() -> Engine {
+    Engine::spawn(env!("CARGO_BIN_EXE_uci"), &[]).unwrap()
+}
+
+#[test]
+fn test_handshake() {
+    let engine = spawn();
+
+    assert_eq!(engine.get_name(), Some("FChess"));
+    assert!(engine.get_author().is_some());
+    assert!(engine
+        .get_options()
+        .iter()
+        .any(|option| option.name == "Hash"));
+    assert!(engine.quit().unwrap().success());
+}
+
+#[rstest]
+#[case("Hash", Some("8"), true)]
+#[case("hash", Some("8"), true)]
+#[case("Hash", Some("big"), false)]
+#[case("Hash", Some("0"), false)]
+#[case("Contempt", Some("10"), false)]
+fn test_set_option(#[case] name: &str, #[case] value: Option<&str>, #[case] valid: bool) {
+    let mut engine = spawn();
+    assert_eq!(engine.set_option(name, value).is_ok(), valid);
+}
+
+/// Engine played by a shell script that answers the handshake and runs the shell commands
+/// `on_go` when asked to search
+fn scripted(on_go: &str) -> Engine {
+    let script = format!(
+        "while read -r command; do case $command in \
+         uci) echo garbage before the handshake; echo id name Scripted; echo uciok ;; \
+         isready) echo readyok ;; \
+         go*) {on_go} ;; \
+         quit) exit ;; \
+         esac; done"
+    );
+    Engine::spawn("sh", &["-c", script.as_str()]).unwrap()
+}
+
+#[test]
+fn test_position_and_moves_stay_in_sync() {
+    let fen = "4k3/P7/8/8/8/8/8/4K2R w K - 0 1";
+    let moves: Vec<Move> = ["e1g1", "e8d7", "a7a8q", "d7c7"]
+        .iter()
+        .filter_map(|mov| Move::from_full_algebraic(mov))
+        .collect();
+    let mut engine = spawn();
+    engine.new_game().unwrap();
+    engine.set_position(Some(fen), &moves).unwrap();
+
+    let mut reported = 0;
+    let limits = SearchLimits {
+        depth: Some(3),
+        ..Default::default()
+    };
+    let result = engine.go_with_progress(&limits, |_| reported += 1).unwrap();
+    assert_eq!(reported, result.info.len());
+
+    // every move the engine reports must be legal in the position played on this side
+    let move_generator = MoveGenerator::new();
+    let play = |board: Board, mov: &Move| {
+        let legal = move_generator
+            .legal_moves(&board)
+            .into_iter()
+            .find(|legal| {
+                legal
+                    .to_algebraic()
+                    .eq_ignore_ascii_case(&mov.to_algebraic())
+            });
+        board.apply(legal.expect("illegal move")).unwrap()
+    };
+    let board = moves.iter().fold(Board::from_fen(fen), &play);
+    play(board.clone(), result.best_move.as_ref().unwrap());
+    result.last_scored().unwrap().pv.iter().fold(board, &play);
+}
+
+#[test]
+fn test_garbled_output_is_skipped() {
+    let mut engine = scripted(
+        "echo info depth 1 score cp 12 pv e2e4; echo '%% garbage'; echo thinking...; \
+         echo info depth 2 score cp 30 pv d2d4 d7d5; echo bestmove d2d4 ponder d7d5",
+    );
+    assert_eq!(engine.get_name(), Some("Scripted"));
+
+    let result = engine.go(&SearchLimits::with_depth(2)).unwrap();
+    assert_eq!(result.best_move, Move::from_full_algebraic("d2d4"));
+    assert_eq!(result.ponder, Move::from_full_algebraic("d7d5"));
+    assert_eq!(result.info.len(), 2);
+    assert_eq!(
+        result.last_scored().unwrap().score,
+        Some(Score::Centipawns(30))
+    );
+}
+
+#[rstest]
+#[case("echo info depth 1 score cp 12; exit", io::ErrorKind::UnexpectedEof)]
+#[case(
+    "echo info depth 1 score cp 12; echo readyok",
+    io::ErrorKind::InvalidData
+)]
+fn test_search_without_bestmove(#[case] on_go: &str, #[case] kind: io::ErrorKind) {
+    let mut engine = scripted(on_go);
+    let error = engine.go(&SearchLimits::with_depth(2)).unwrap_err();
+    assert_eq!(error.kind(), kind);
+}
+
+#[test]
+fn test_search_after_moves() {
+    let mut engine = spawn();
+    let moves: Vec<Move> = ["e2e4", "e7e5"]
+        .iter()
+        .filter_map(|mov| Move::from_full_algebraic(mov))
+        .collect();
+    engine.set_position(None, &moves).unwrap();
+
+    let limits = SearchLimits {
+        depth: Some(2),
+        ..Default::default()
+    };
+    let result = engine.go(&limits).unwrap();
+    assert!(result.best_move.is_some());
+    assert!(result.info.iter().any(|info| info.depth == Some(2)));
+}
+
+#[test]
+fn test_checkmated_position_has_no_best_move() {
+    let mut engine = spawn();
+    engine
+        .set_position(Some("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"), &[])
+        .unwrap();
+
+    let limits = SearchLimits {
+        depth: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(engine.go(&limits).unwrap().best_move, None);
+}
+
+#[test]
+fn test_infinite_search_is_driven_by_hand() {
+    let mut engine = spawn();
+    let limits = SearchLimits {
+        infinite: true,
+        ..Default::default()
+    };
+    assert!(engine.go(&limits).is_err());
+
+    engine.send(&UciCommand::Go(limits)).unwrap();
+    engine.send(&UciCommand::Stop).unwrap();
+    while !matches!(engine.read().unwrap(), UciResponse::BestMove { .. }) {}
+}
@@ -0,0 +1,37 @@
+use std::process::Command;
+
+fn epdtest(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_epdtest"))
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testcases/epd/tactics.epd"
+        ))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_tactics_suite() {
+    let (success, output) = epdtest(&["--depth", "5"]);
+
+    assert!(success, "{}", output);
+    assert!(output.contains("scholar's mate: Qxf7# #1 ok"));
+    assert!(output.contains("Solved 5 of 5"));
+}
+
+#[test]
+fn test_tactics_suite_too_shallow() {
+    let (success, output) = epdtest(&["--depth", "1"]);
+
+    assert!(!success);
+    assert!(output.contains("king and rook mate in two: "));
+    assert!(output.contains(" FAIL (dm 2)"));
+
+    let (success, _) = epdtest(&["--depth", "1", "--min-solved", "2"]);
+    assert!(success);
+}